@@ -0,0 +1,7 @@
+-- Create a spool table for line protocol points that failed to be written to influxdb
+CREATE TABLE influxdb_spool(
+    id BIGSERIAL NOT NULL,
+    PRIMARY KEY (id),
+    payload TEXT NOT NULL,
+    created_at timestamptz NOT NULL
+);
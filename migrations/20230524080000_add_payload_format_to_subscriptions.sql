@@ -0,0 +1,3 @@
+-- Add payload format that selects the decoder for a topic
+ALTER TABLE subscriptions_topics
+    ADD COLUMN payload_format VARCHAR NOT NULL DEFAULT 'json';
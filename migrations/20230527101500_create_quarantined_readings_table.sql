@@ -0,0 +1,13 @@
+-- Create quarantine table for readings rejected by validation
+CREATE TABLE quarantined_readings(
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    topic VARCHAR NOT NULL,
+    device_name VARCHAR,
+    line_point TEXT NOT NULL,
+    reason VARCHAR NOT NULL,
+    received_at timestamptz NOT NULL,
+    created_at timestamptz NOT NULL
+);
+
+CREATE INDEX quarantined_readings_created_at_idx ON quarantined_readings (created_at);
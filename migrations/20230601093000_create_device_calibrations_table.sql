@@ -0,0 +1,14 @@
+-- Create versioned scale calibrations of devices
+CREATE TABLE device_calibrations(
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    device_id uuid NOT NULL,
+    version INTEGER NOT NULL,
+    tare_offset DOUBLE PRECISION NOT NULL,
+    scale_factor DOUBLE PRECISION NOT NULL,
+    temperature_coefficient DOUBLE PRECISION NOT NULL,
+    reference_temperature DOUBLE PRECISION NOT NULL,
+    valid_from timestamptz NOT NULL,
+    created_at timestamptz NOT NULL,
+    UNIQUE (device_id, version)
+);
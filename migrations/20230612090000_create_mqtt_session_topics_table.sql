@@ -0,0 +1,7 @@
+-- Create the topics subscribed in persistent mqtt sessions
+CREATE TABLE mqtt_session_topics(
+    client_id TEXT NOT NULL,
+    topic TEXT NOT NULL,
+    PRIMARY KEY (client_id, topic),
+    updated_at timestamptz NOT NULL
+);
@@ -0,0 +1,2 @@
+-- An organization subscribes many topics, like a wildcard pattern next to its devices
+ALTER TABLE subscriptions_topics DROP CONSTRAINT IF EXISTS subscriptions_organization_id_key;
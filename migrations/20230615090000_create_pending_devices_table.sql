@@ -0,0 +1,13 @@
+-- Devices publishing under a known topic prefix without a subscription, waiting for an admin
+CREATE TABLE pending_devices(
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    organization_id uuid NOT NULL,
+    topic_prefix VARCHAR NOT NULL,
+    device_name VARCHAR NOT NULL,
+    status VARCHAR NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'rejected')),
+    messages BIGINT NOT NULL DEFAULT 1,
+    first_seen_at timestamptz NOT NULL,
+    last_seen_at timestamptz NOT NULL,
+    UNIQUE (topic_prefix, device_name)
+);
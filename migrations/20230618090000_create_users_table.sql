@@ -0,0 +1,8 @@
+-- Create users table for the admin authentication
+CREATE TABLE users(
+    user_id uuid NOT NULL,
+    PRIMARY KEY (user_id),
+    username TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    created_at timestamptz NOT NULL
+);
@@ -0,0 +1,12 @@
+-- Superadmins manage every organization, other users only the organizations they are members of
+ALTER TABLE users ADD COLUMN superadmin BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE organization_members(
+    user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    organization_id uuid NOT NULL,
+    PRIMARY KEY (user_id, organization_id),
+    role TEXT NOT NULL CHECK (role IN ('admin', 'beekeeper')),
+    created_at timestamptz NOT NULL
+);
+
+CREATE INDEX organization_members_organization_id_idx ON organization_members (organization_id);
@@ -0,0 +1,79 @@
+-- Registry of the organizations (apiaries) and devices (hives) the subscriptions refer to
+CREATE TABLE organizations(
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    name TEXT NOT NULL,
+    location TEXT,
+    description TEXT,
+    created_at timestamptz NOT NULL,
+    updated_at timestamptz NOT NULL
+);
+
+CREATE TABLE devices(
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    organization_id uuid NOT NULL REFERENCES organizations (id),
+    name TEXT NOT NULL,
+    location TEXT,
+    description TEXT,
+    created_at timestamptz NOT NULL,
+    updated_at timestamptz NOT NULL
+);
+
+CREATE INDEX devices_organization_id_idx ON devices (organization_id);
+
+-- Register the apiaries and hives that were typed in as free-form ids
+INSERT INTO organizations (id, name, created_at, updated_at)
+SELECT organization_id, 'Apiary ' || organization_id, MIN(created_at), MAX(created_at)
+    FROM (
+        SELECT organization_id, created_at FROM subscriptions_topics
+        UNION ALL
+        SELECT organization_id, created_at FROM organization_members
+    ) AS known_organizations
+    GROUP BY organization_id;
+
+-- A wildcard subscription covers all the hives of its prefix, so it does not reference one
+-- unless its id is the one of a single hive subscription
+ALTER TABLE subscriptions_topics ALTER COLUMN device_id DROP NOT NULL;
+
+UPDATE subscriptions_topics SET device_id = NULL
+    WHERE device_name ~ '(^|/)[+#](/|$)'
+        AND device_id NOT IN (
+            SELECT device_id FROM subscriptions_topics WHERE device_name !~ '(^|/)[+#](/|$)'
+        );
+
+-- A hive is registered in a single apiary, the id of a hive subscribed in several apiaries
+-- has to be changed by hand before migrating
+DO $$
+DECLARE
+    shared_device_ids TEXT;
+BEGIN
+    SELECT string_agg(device_id::text, ', ') INTO shared_device_ids
+        FROM (
+            SELECT device_id FROM subscriptions_topics
+                WHERE device_id IS NOT NULL
+                GROUP BY device_id
+                HAVING COUNT(DISTINCT organization_id) > 1
+        ) AS shared_devices;
+    IF shared_device_ids IS NOT NULL THEN
+        RAISE EXCEPTION 'The hives % are subscribed in several apiaries', shared_device_ids;
+    END IF;
+END $$;
+
+INSERT INTO devices (id, organization_id, name, created_at, updated_at)
+SELECT DISTINCT ON (device_id) device_id, organization_id, device_name, created_at, updated_at
+    FROM subscriptions_topics
+    WHERE device_id IS NOT NULL
+    ORDER BY device_id, device_name ~ '(^|/)[+#](/|$)', created_at;
+
+ALTER TABLE subscriptions_topics
+    ADD CONSTRAINT subscriptions_topics_organization_id_fkey
+        FOREIGN KEY (organization_id) REFERENCES organizations (id),
+    ADD CONSTRAINT subscriptions_topics_device_id_fkey
+        FOREIGN KEY (device_id) REFERENCES devices (id),
+    ADD CONSTRAINT subscriptions_topics_device_id_check
+        CHECK (device_id IS NOT NULL OR device_name ~ '(^|/)[+#](/|$)');
+
+ALTER TABLE organization_members
+    ADD CONSTRAINT organization_members_organization_id_fkey
+        FOREIGN KEY (organization_id) REFERENCES organizations (id) ON DELETE CASCADE;
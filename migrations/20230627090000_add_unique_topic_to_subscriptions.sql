@@ -0,0 +1,16 @@
+-- Rows sharing a topic were all delivered the same messages, keep the oldest one of each
+-- topic so the constraint can be added on existing databases
+DELETE FROM subscriptions_topics
+WHERE id IN (
+    SELECT id FROM (
+        SELECT id, row_number() OVER (
+            PARTITION BY topic_prefix, device_name ORDER BY created_at, id
+        ) AS position
+        FROM subscriptions_topics
+    ) AS topics
+    WHERE position > 1
+);
+
+-- A topic is subscribed once, the admin pages report duplicates to the user
+ALTER TABLE subscriptions_topics
+    ADD CONSTRAINT subscriptions_topics_topic_key UNIQUE (topic_prefix, device_name);
@@ -0,0 +1,10 @@
+-- Tokens of the api clients, only a sha-256 hash of the token is stored
+CREATE TABLE api_tokens(
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    user_id uuid NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at timestamptz NOT NULL,
+    last_used_at timestamptz
+);
@@ -1,15 +1,32 @@
-use crate::configuration::{DatabaseSettings, Settings};
+use crate::authentication::reject_anonymous_users;
+use crate::configuration::{ApplicationSettings, DatabaseSettings, Settings};
+use crate::health::DependencyHealth;
+use crate::metrics::Metrics;
 use crate::routes::{
-    get_admin_dashboard, get_create_admin_subscriptions_topics,
-    get_view_admin_subscriptions_topics, health_check, home,
-    post_create_admin_subscriptions_topics,
+    delete_api_subscriptions_topic, get_admin_dashboard, get_api_openapi,
+    get_api_subscriptions_topic, get_api_subscriptions_topics,
+    get_create_admin_subscriptions_topics, get_delete_admin_subscriptions_topic,
+    get_edit_admin_calibration, get_edit_admin_subscriptions_topic, get_login, get_metrics,
+    get_view_admin_calibrations, get_view_admin_devices, get_view_admin_organizations,
+    get_view_admin_pending_devices, get_view_admin_quarantined_readings,
+    get_view_admin_subscriptions_topics, health_check, health_ready, home, log_out,
+    post_api_subscriptions_topics, post_approve_admin_pending_device, post_create_admin_device,
+    post_create_admin_organization, post_create_admin_subscriptions_topics,
+    post_delete_admin_subscriptions_topic, post_edit_admin_calibration,
+    post_edit_admin_subscriptions_topic, post_login, post_reject_admin_pending_device,
+    put_api_subscriptions_topic, record_request_duration, reject_invalid_api_tokens,
+    reject_malformed_api_request,
 };
+use crate::supervisor::Supervisor;
+use actix_session::storage::RedisSessionStore;
+use actix_session::SessionMiddleware;
 use actix_web::cookie::Key;
 use actix_web::dev::Server;
 use actix_web::web::Data;
 use actix_web::{web, App, HttpServer};
 use actix_web_flash_messages::storage::CookieMessageStore;
 use actix_web_flash_messages::FlashMessagesFramework;
+use actix_web_lab::middleware::from_fn;
 use handlebars::Handlebars;
 use secrecy::{ExposeSecret, Secret};
 use sqlx::postgres::PgPoolOptions;
@@ -17,6 +34,7 @@ use sqlx::PgPool;
 use std::fs;
 use std::net::TcpListener;
 use std::path::PathBuf;
+use tokio_util::sync::CancellationToken;
 use tracing_actix_web::TracingLogger;
 
 #[derive(thiserror::Error, Debug)]
@@ -25,6 +43,8 @@ pub enum Error {
     Io(PathBuf, std::io::Error),
     #[error(transparent)]
     Startup(#[from] std::io::Error),
+    #[error("Failed to connect to the session store")]
+    SessionStore(#[source] anyhow::Error),
 }
 
 pub struct Application {
@@ -33,7 +53,12 @@ pub struct Application {
 }
 
 impl Application {
-    pub async fn build(configuration: Settings) -> Result<Self, Error> {
+    pub async fn build(
+        configuration: Settings,
+        health: DependencyHealth,
+        supervisor: Supervisor,
+        metrics: Metrics,
+    ) -> Result<Self, Error> {
         let connection_pool = get_connection_pool(&configuration.database);
 
         let address = format!(
@@ -45,10 +70,13 @@ impl Application {
         let server = run(
             listener,
             connection_pool,
-            configuration.application.base_url,
-            configuration.application.web_dir_path,
-            configuration.application.hmac_secret,
-        )?;
+            configuration.application,
+            health,
+            supervisor,
+            metrics,
+            configuration.redis_uri,
+        )
+        .await?;
 
         Ok(Self { port, server })
     }
@@ -57,7 +85,15 @@ impl Application {
         self.port
     }
 
-    pub async fn run_until_stopped(self) -> Result<(), Error> {
+    /// Runs the server until `shutdown` is cancelled, then stops accepting connections
+    /// and lets in-flight requests complete within the shutdown deadline.
+    pub async fn run_until_stopped(self, shutdown: CancellationToken) -> Result<(), Error> {
+        let server_handle = self.server.handle();
+        tokio::spawn(async move {
+            shutdown.cancelled().await;
+            server_handle.stop(true).await;
+        });
+
         self.server.await.map_err(Error::Startup)
     }
 }
@@ -70,15 +106,23 @@ pub fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
 
 pub struct ApplicationBaseUrl(pub String);
 
-fn run(
+async fn run(
     listener: TcpListener,
     db_pool: PgPool,
-    base_url: String,
-    web_dir_path: String,
-    hmac_secret: Secret<String>,
+    configuration: ApplicationSettings,
+    health: DependencyHealth,
+    supervisor: Supervisor,
+    metrics: Metrics,
+    redis_uri: Secret<String>,
 ) -> Result<Server, Error> {
+    let shutdown_deadline = configuration.shutdown_deadline();
+    let web_dir_path = configuration.web_dir_path;
+    let hmac_secret = configuration.hmac_secret;
     let db_pool = Data::new(db_pool);
-    let base_url = Data::new(ApplicationBaseUrl(base_url));
+    let base_url = Data::new(ApplicationBaseUrl(configuration.base_url));
+    let health = Data::new(health);
+    let supervisor = Data::new(supervisor);
+    let metrics = Data::new(metrics);
 
     fs::create_dir_all(web_dir_path.as_str())
         .map_err(|e| Error::Io(web_dir_path.parse().unwrap(), e))?;
@@ -86,6 +130,9 @@ fn run(
     let secret_key = Key::from(hmac_secret.expose_secret().as_bytes());
     let message_store = CookieMessageStore::builder(secret_key.clone()).build();
     let message_framework = FlashMessagesFramework::builder(message_store).build();
+    let redis_store = RedisSessionStore::new(redis_uri.expose_secret())
+        .await
+        .map_err(Error::SessionStore)?;
 
     let mut hbs = Handlebars::new();
     hbs.register_templates_directory(".hbs", "./web/src")
@@ -95,11 +142,49 @@ fn run(
     let server = HttpServer::new(move || {
         App::new()
             .wrap(message_framework.clone())
+            .wrap(SessionMiddleware::new(
+                redis_store.clone(),
+                secret_key.clone(),
+            ))
+            .wrap(from_fn(record_request_duration))
             .wrap(TracingLogger::default())
             .route("/", web::get().to(home))
+            .route("/login", web::get().to(get_login))
+            .route("/login", web::post().to(post_login))
             .service(
                 web::scope("/admin")
+                    .wrap(from_fn(reject_anonymous_users))
                     .route("/dashboard", web::get().to(get_admin_dashboard))
+                    .route("/logout", web::post().to(log_out))
+                    .route(
+                        "/quarantine/readings/view",
+                        web::get().to(get_view_admin_quarantined_readings),
+                    )
+                    .service(
+                        web::scope("/calibrations")
+                            .route("/view", web::get().to(get_view_admin_calibrations))
+                            .route("/edit", web::post().to(post_edit_admin_calibration))
+                            .route("/edit", web::get().to(get_edit_admin_calibration)),
+                    )
+                    .service(
+                        web::scope("/organizations")
+                            .route("/view", web::get().to(get_view_admin_organizations))
+                            .route("/create", web::post().to(post_create_admin_organization)),
+                    )
+                    .route("/devices/view", web::get().to(get_view_admin_devices))
+                    .route("/devices/create", web::post().to(post_create_admin_device))
+                    .service(
+                        web::scope("/devices/pending")
+                            .route("/view", web::get().to(get_view_admin_pending_devices))
+                            .route(
+                                "/{id}/approve",
+                                web::post().to(post_approve_admin_pending_device),
+                            )
+                            .route(
+                                "/{id}/reject",
+                                web::post().to(post_reject_admin_pending_device),
+                            ),
+                    )
                     .service(
                         web::scope("/subscriptions")
                             .route(
@@ -113,10 +198,63 @@ fn run(
                             .route(
                                 "/topics/create",
                                 web::get().to(get_create_admin_subscriptions_topics),
+                            )
+                            .route(
+                                "/topics/{id}/edit",
+                                web::get().to(get_edit_admin_subscriptions_topic),
+                            )
+                            .route(
+                                "/topics/{id}/edit",
+                                web::post().to(post_edit_admin_subscriptions_topic),
+                            )
+                            .route(
+                                "/topics/{id}/delete",
+                                web::get().to(get_delete_admin_subscriptions_topic),
+                            )
+                            .route(
+                                "/topics/{id}/delete",
+                                web::post().to(post_delete_admin_subscriptions_topic),
                             ),
                     ),
             )
+            // Registered before the api scope, the description does not need a token.
+            .route("/api/v1/openapi.json", web::get().to(get_api_openapi))
+            .service(
+                web::scope("/api/v1")
+                    .wrap(from_fn(reject_invalid_api_tokens))
+                    .app_data(
+                        web::JsonConfig::default().error_handler(reject_malformed_api_request),
+                    )
+                    .app_data(
+                        web::QueryConfig::default().error_handler(reject_malformed_api_request),
+                    )
+                    .app_data(
+                        web::PathConfig::default().error_handler(reject_malformed_api_request),
+                    )
+                    .route(
+                        "/subscriptions/topics",
+                        web::get().to(get_api_subscriptions_topics),
+                    )
+                    .route(
+                        "/subscriptions/topics",
+                        web::post().to(post_api_subscriptions_topics),
+                    )
+                    .route(
+                        "/subscriptions/topics/{id}",
+                        web::get().to(get_api_subscriptions_topic),
+                    )
+                    .route(
+                        "/subscriptions/topics/{id}",
+                        web::put().to(put_api_subscriptions_topic),
+                    )
+                    .route(
+                        "/subscriptions/topics/{id}",
+                        web::delete().to(delete_api_subscriptions_topic),
+                    ),
+            )
             .route("/health_check", web::get().to(health_check))
+            .route("/health/ready", web::get().to(health_ready))
+            .route("/metrics", web::get().to(get_metrics))
             .service(
                 actix_files::Files::new("/web", web_dir_path.as_str())
                     .redirect_to_slash_directory()
@@ -125,7 +263,12 @@ fn run(
             .app_data(db_pool.clone())
             .app_data(base_url.clone())
             .app_data(handlebars.clone())
+            .app_data(health.clone())
+            .app_data(supervisor.clone())
+            .app_data(metrics.clone())
     })
+    .disable_signals()
+    .shutdown_timeout(shutdown_deadline.as_secs())
     .listen(listener)?
     .run();
     Ok(server)
@@ -0,0 +1,151 @@
+use super::UserId;
+use crate::domain::{Role, UserName};
+use crate::utils::e500;
+use actix_web::dev::Payload;
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest};
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use uuid::Uuid;
+
+/// Organizations the logged in user can see and change, loaded for every admin request.
+#[derive(Debug)]
+pub struct UserAccess {
+    superadmin: bool,
+    memberships: HashMap<Uuid, Role>,
+}
+
+impl UserAccess {
+    #[tracing::instrument(name = "Load the access of a user", skip(pool))]
+    pub async fn load(user_id: Uuid, pool: &PgPool) -> Result<UserAccess, anyhow::Error> {
+        let superadmin =
+            sqlx::query_scalar!("SELECT superadmin FROM users WHERE user_id = $1", user_id)
+                .fetch_optional(pool)
+                .await
+                .context("Failed to retrieve the user.")?
+                .unwrap_or(false);
+        let memberships = sqlx::query!(
+            "SELECT organization_id, role FROM organization_members WHERE user_id = $1",
+            user_id
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to retrieve the organization memberships.")?
+        .into_iter()
+        .map(|row| Role::parse(row.role).map(|role| (row.organization_id, role)))
+        .collect::<Result<_, _>>()
+        .map_err(anyhow::Error::msg)?;
+        Ok(Self {
+            superadmin,
+            memberships,
+        })
+    }
+
+    pub fn is_superadmin(&self) -> bool {
+        self.superadmin
+    }
+
+    /// Ids of the organizations the user is a member of, superadmins are not limited to them.
+    pub fn organization_ids(&self) -> Vec<Uuid> {
+        self.memberships.keys().copied().collect()
+    }
+
+    pub fn can_view(&self, organization_id: Uuid) -> bool {
+        self.superadmin || self.memberships.contains_key(&organization_id)
+    }
+
+    pub fn can_edit(&self, organization_id: Uuid) -> bool {
+        self.superadmin
+            || self
+                .memberships
+                .get(&organization_id)
+                .is_some_and(Role::can_edit)
+    }
+}
+
+impl FromRequest for UserAccess {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<UserAccess, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let user_id = req.extensions().get::<UserId>().copied();
+        let pool = req.app_data::<web::Data<PgPool>>().cloned();
+        Box::pin(async move {
+            let user_id = user_id.ok_or_else(|| e500("The user has not logged in"))?;
+            let pool = pool.ok_or_else(|| e500("The database pool is not configured"))?;
+            UserAccess::load(*user_id, &pool).await.map_err(e500)
+        })
+    }
+}
+
+/// Adds the user to the organization, or changes the role of an existing member.
+#[tracing::instrument(name = "Add a member to an organization", skip(pool))]
+pub async fn add_organization_member(
+    username: &UserName,
+    organization_id: Uuid,
+    role: Role,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    let added = sqlx::query!(
+        r#"
+        INSERT INTO organization_members (user_id, organization_id, role, created_at)
+        SELECT user_id, $2, $3, $4 FROM users WHERE username = $1
+        ON CONFLICT (user_id, organization_id) DO UPDATE SET role = EXCLUDED.role
+        "#,
+        username.as_ref(),
+        organization_id,
+        role.as_str(),
+        Utc::now()
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the organization member.")?;
+    anyhow::ensure!(
+        added.rows_affected() == 1,
+        "There is no user named {}.",
+        username.as_ref()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UserAccess;
+    use crate::domain::Role;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    #[test]
+    fn members_only_access_their_organizations() {
+        let (apiary, other_apiary) = (Uuid::new_v4(), Uuid::new_v4());
+        let access = UserAccess {
+            superadmin: false,
+            memberships: HashMap::from([(apiary, Role::Admin)]),
+        };
+        assert!(access.can_view(apiary) && access.can_edit(apiary));
+        assert!(!access.can_view(other_apiary) && !access.can_edit(other_apiary));
+    }
+
+    #[test]
+    fn beekeepers_have_read_only_access() {
+        let apiary = Uuid::new_v4();
+        let access = UserAccess {
+            superadmin: false,
+            memberships: HashMap::from([(apiary, Role::Beekeeper)]),
+        };
+        assert!(access.can_view(apiary));
+        assert!(!access.can_edit(apiary));
+    }
+
+    #[test]
+    fn superadmins_access_every_organization() {
+        let access = UserAccess {
+            superadmin: true,
+            memberships: HashMap::new(),
+        };
+        assert!(access.can_edit(Uuid::new_v4()));
+    }
+}
@@ -0,0 +1,88 @@
+use crate::authentication::AuthError;
+use crate::domain::UserName;
+use anyhow::Context;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use chrono::Utc;
+use rand::RngCore;
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Creates an api token of the user and returns it, only its hash is stored so the token
+/// cannot be shown again.
+#[tracing::instrument(name = "Create an api token", skip(pool))]
+pub async fn create_api_token(
+    username: &UserName,
+    name: &str,
+    pool: &PgPool,
+) -> Result<Secret<String>, anyhow::Error> {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let token = Secret::new(URL_SAFE_NO_PAD.encode(bytes));
+    let created = sqlx::query!(
+        r#"
+        INSERT INTO api_tokens (id, user_id, name, token_hash, created_at)
+        SELECT $2, user_id, $3, $4, $5 FROM users WHERE username = $1
+        "#,
+        username.as_ref(),
+        Uuid::new_v4(),
+        name,
+        compute_token_hash(&token),
+        Utc::now()
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the api token.")?;
+    anyhow::ensure!(
+        created.rows_affected() == 1,
+        "There is no user named {}.",
+        username.as_ref()
+    );
+    Ok(token)
+}
+
+/// Returns the user of the token and records its use.
+#[tracing::instrument(name = "Validate api token", skip(token, pool))]
+pub async fn validate_api_token(token: Secret<String>, pool: &PgPool) -> Result<Uuid, AuthError> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE api_tokens SET last_used_at = $2
+        WHERE token_hash = $1
+        RETURNING user_id
+        "#,
+        compute_token_hash(&token),
+        Utc::now()
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to retrieve the api token.")?;
+
+    row.map(|row| row.user_id)
+        .ok_or_else(|| anyhow::anyhow!("Unknown api token."))
+        .map_err(AuthError::InvalidCredentials)
+}
+
+/// The tokens are random enough for a plain sha-256 hash, unlike the passwords.
+fn compute_token_hash(token: &Secret<String>) -> String {
+    STANDARD.encode(Sha256::digest(token.expose_secret().as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compute_token_hash;
+    use secrecy::Secret;
+
+    #[test]
+    fn token_hash_does_not_contain_the_token() {
+        let hash = compute_token_hash(&Secret::new("a-random-api-token".into()));
+
+        assert_eq!(hash.len(), 44);
+        assert!(!hash.contains("a-random-api-token"));
+        assert_eq!(
+            hash,
+            compute_token_hash(&Secret::new("a-random-api-token".into()))
+        );
+    }
+}
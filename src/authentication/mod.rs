@@ -0,0 +1,9 @@
+mod access;
+mod api_token;
+mod middleware;
+mod password;
+
+pub use access::{add_organization_member, UserAccess};
+pub use api_token::{create_api_token, validate_api_token};
+pub use middleware::{reject_anonymous_users, UserId};
+pub use password::{create_user, validate_credentials, AuthError, Credentials};
@@ -0,0 +1,168 @@
+use crate::domain::UserName;
+use crate::telemetry::spawn_blocking_with_tracing;
+use anyhow::Context;
+use argon2::password_hash::SaltString;
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use chrono::Utc;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(thiserror::Error, Debug)]
+pub enum AuthError {
+    #[error("Invalid credentials.")]
+    InvalidCredentials(#[source] anyhow::Error),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+pub struct Credentials {
+    pub username: String,
+    pub password: Secret<String>,
+}
+
+#[tracing::instrument(name = "Get stored credentials", skip(username, pool))]
+async fn get_stored_credentials(
+    username: &str,
+    pool: &PgPool,
+) -> Result<Option<(Uuid, Secret<String>)>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT user_id, password_hash
+        FROM users
+        WHERE username = $1
+        "#,
+        username,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to retrieve stored credentials.")?
+    .map(|row| (row.user_id, Secret::new(row.password_hash)));
+    Ok(row)
+}
+
+#[tracing::instrument(name = "Validate credentials", skip(credentials, pool))]
+pub async fn validate_credentials(
+    credentials: Credentials,
+    pool: &PgPool,
+) -> Result<Uuid, AuthError> {
+    let mut user_id = None;
+    // Unknown users are verified against a dummy hash too, so the response time does not
+    // tell whether a user name exists.
+    let mut expected_password_hash = Secret::new(
+        "$argon2id$v=19$m=15000,t=2,p=1$\
+        gZiV/M1gPc22ElAH/Jh1Hw$\
+        CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
+            .to_string(),
+    );
+
+    if let Some((stored_user_id, stored_password_hash)) =
+        get_stored_credentials(&credentials.username, pool).await?
+    {
+        user_id = Some(stored_user_id);
+        expected_password_hash = stored_password_hash;
+    }
+
+    spawn_blocking_with_tracing(move || {
+        verify_password_hash(expected_password_hash, credentials.password)
+    })
+    .await
+    .context("Failed to spawn blocking task.")??;
+
+    user_id
+        .ok_or_else(|| anyhow::anyhow!("Unknown username."))
+        .map_err(AuthError::InvalidCredentials)
+}
+
+#[tracing::instrument(
+    name = "Validate credentials",
+    skip(expected_password_hash, password_candidate)
+)]
+fn verify_password_hash(
+    expected_password_hash: Secret<String>,
+    password_candidate: Secret<String>,
+) -> Result<(), AuthError> {
+    let expected_password_hash = PasswordHash::new(expected_password_hash.expose_secret())
+        .context("Failed to parse hash in PHC string format.")?;
+
+    Argon2::default()
+        .verify_password(
+            password_candidate.expose_secret().as_bytes(),
+            &expected_password_hash,
+        )
+        .context("Invalid password.")
+        .map_err(AuthError::InvalidCredentials)
+}
+
+/// Stores a new user with an argon2id hash of `password`, superadmins manage every organization.
+#[tracing::instrument(name = "Create a user", skip(password, pool))]
+pub async fn create_user(
+    username: &UserName,
+    password: Secret<String>,
+    superadmin: bool,
+    pool: &PgPool,
+) -> Result<Uuid, anyhow::Error> {
+    let password_length = password.expose_secret().chars().count();
+    anyhow::ensure!(
+        (12..=128).contains(&password_length),
+        "The password has to be between 12 and 128 characters long."
+    );
+
+    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
+        .await
+        .context("Failed to spawn blocking task.")??;
+    let user_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, password_hash, superadmin, created_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        user_id,
+        username.as_ref(),
+        password_hash.expose_secret(),
+        superadmin,
+        Utc::now()
+    )
+    .execute(pool)
+    .await
+    .with_context(|| format!("Failed to store the user {}.", username.as_ref()))?;
+    Ok(user_id)
+}
+
+fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, anyhow::Error> {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let password_hash = Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        Params::new(15000, 2, 1, None).unwrap(),
+    )
+    .hash_password(password.expose_secret().as_bytes(), &salt)?
+    .to_string();
+    Ok(Secret::new(password_hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compute_password_hash, verify_password_hash, AuthError};
+    use claims::assert_ok;
+    use secrecy::Secret;
+
+    #[test]
+    fn password_is_verified_against_its_argon2id_hash() {
+        let hash = compute_password_hash(Secret::new("correct horse battery".into())).unwrap();
+
+        assert_ok!(verify_password_hash(
+            hash,
+            Secret::new("correct horse battery".into())
+        ));
+    }
+
+    #[test]
+    fn wrong_password_is_rejected_as_invalid_credentials() {
+        let hash = compute_password_hash(Secret::new("correct horse battery".into())).unwrap();
+
+        let outcome = verify_password_hash(hash, Secret::new("wrong horse battery".into()));
+
+        assert!(matches!(outcome, Err(AuthError::InvalidCredentials(_))));
+    }
+}
@@ -0,0 +1,74 @@
+use crate::application::get_connection_pool;
+use crate::authentication::{add_organization_member, create_api_token, create_user};
+use crate::configuration::Settings;
+use crate::domain::{Id, Role, UserName};
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use std::io::{BufRead, Write};
+
+const USAGE: &str = "Usage:
+    beesbuddy-bumblebee create-admin <username>
+    beesbuddy-bumblebee create-user <username>
+    beesbuddy-bumblebee add-member <username> <organization id> <admin|beekeeper>
+    beesbuddy-bumblebee create-api-token <username> <token name>";
+
+/// Runs a management command instead of the service.
+///
+/// `create-admin` creates a superadmin and `create-user` a user without any organization,
+/// both read the password from the standard input so it does not end up in the shell history.
+/// `add-member` gives a user a role in an organization. `create-api-token` prints a new token
+/// of the api for a user, it cannot be shown again.
+pub async fn run_command(
+    configuration: &Settings,
+    args: impl Iterator<Item = String>,
+) -> Result<(), anyhow::Error> {
+    let args: Vec<String> = args.collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args[..] {
+        [command @ ("create-admin" | "create-user"), username] => {
+            let superadmin = command == "create-admin";
+            let username = UserName::parse(username.to_string()).map_err(anyhow::Error::msg)?;
+            let password = read_password()?;
+            let pool = get_connection_pool(&configuration.database);
+            let user_id = create_user(&username, password, superadmin, &pool).await?;
+            println!("Created the user {} with id {user_id}", username.as_ref());
+            Ok(())
+        }
+        ["add-member", username, organization_id, role] => {
+            let username = UserName::parse(username.to_string()).map_err(anyhow::Error::msg)?;
+            let organization_id =
+                Id::parse(organization_id.to_string()).map_err(anyhow::Error::msg)?;
+            let role = Role::parse(role.to_string()).map_err(anyhow::Error::msg)?;
+            let pool = get_connection_pool(&configuration.database);
+            add_organization_member(&username, *organization_id.as_ref(), role, &pool).await?;
+            println!(
+                "{} is now {} of the organization {}",
+                username.as_ref(),
+                role.as_str(),
+                organization_id.as_ref()
+            );
+            Ok(())
+        }
+        ["create-api-token", username, name] => {
+            let username = UserName::parse(username.to_string()).map_err(anyhow::Error::msg)?;
+            let pool = get_connection_pool(&configuration.database);
+            let token = create_api_token(&username, name, &pool).await?;
+            println!("{}", token.expose_secret());
+            Ok(())
+        }
+        _ => anyhow::bail!(USAGE),
+    }
+}
+
+fn read_password() -> Result<Secret<String>, anyhow::Error> {
+    eprint!("Password: ");
+    std::io::stderr().flush()?;
+    let mut password = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut password)
+        .context("Failed to read the password.")?;
+    Ok(Secret::new(
+        password.trim_end_matches(['\r', '\n']).to_string(),
+    ))
+}
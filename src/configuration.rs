@@ -1,9 +1,13 @@
 use crate::influxdb_client::InfluxDbClient;
 use secrecy::{ExposeSecret, Secret};
-use serde_aux::field_attributes::deserialize_number_from_string;
+use serde_aux::field_attributes::{
+    deserialize_number_from_string, deserialize_option_number_from_string,
+};
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
 use sqlx::ConnectOptions;
+use std::collections::BTreeMap;
 use std::convert::{TryFrom, TryInto};
+use std::num::NonZeroUsize;
 
 #[derive(serde::Deserialize, Clone)]
 pub struct Settings {
@@ -11,6 +15,9 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub mqtt: MqttSettings,
     pub influxdb: InfluxDbSettings,
+    pub validation: ValidationSettings,
+    pub supervisor: SupervisorSettings,
+    pub redis_uri: Secret<String>,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -21,6 +28,14 @@ pub struct ApplicationSettings {
     pub base_url: String,
     pub web_dir_path: String,
     pub hmac_secret: Secret<String>,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub shutdown_deadline_seconds: u64,
+}
+
+impl ApplicationSettings {
+    pub fn shutdown_deadline(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.shutdown_deadline_seconds)
+    }
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -63,6 +78,81 @@ pub struct MqttSettings {
     pub host: String,
     pub username: String,
     pub password: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub reconciliation_interval_seconds: u64,
+    /// A random client id is generated on every connection when not set.
+    pub client_id: Option<String>,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub keep_alive_seconds: u64,
+    pub clean_session: bool,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub qos: u8,
+    /// Maximum number of outgoing QoS 1 and 2 packets waiting for an acknowledgement.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub inflight: u16,
+    /// Capacity of the channel between the client and the event loop.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub request_channel_capacity: usize,
+    pub transport: MqttTransport,
+    /// Broker url for the websocket transports, e.g. `wss://broker:443/mqtt`.
+    pub websocket_url: Option<String>,
+    #[serde(default)]
+    pub tls: MqttTlsSettings,
+    /// Subscribes with shared subscriptions so the broker balances the messages across
+    /// the instances of the group instead of sending every message to every instance.
+    pub shared_subscriptions: bool,
+    pub shared_subscription_group: String,
+    pub protocol_version: MqttProtocolVersion,
+    /// Topic aliases the broker may use for MQTT 5 publishes, 0 disables them.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub topic_alias_max: u16,
+    /// How long an MQTT 5 broker keeps a persistent session after the connection is lost.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub session_expiry_interval_seconds: u32,
+    /// Zero based level of the topic holding the device name, used for the publishes
+    /// of wildcard subscriptions whose payload has no device name.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub device_name_topic_level: usize,
+    /// Subscribes every topic prefix with a `+` wildcard to find devices that are not
+    /// registered yet.
+    pub discovery_mode: bool,
+}
+
+impl MqttSettings {
+    pub fn reconciliation_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.reconciliation_interval_seconds)
+    }
+
+    pub fn keep_alive(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.keep_alive_seconds)
+    }
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MqttTransport {
+    Tcp,
+    Tls,
+    Ws,
+    Wss,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MqttProtocolVersion {
+    #[serde(rename = "3.1.1")]
+    V4,
+    #[serde(rename = "5")]
+    V5,
+}
+
+/// Certificates of the `tls` and `wss` transports, files are PEM encoded.
+#[derive(serde::Deserialize, Clone, Default)]
+pub struct MqttTlsSettings {
+    /// The platform certificates are trusted when not set.
+    pub ca_file: Option<String>,
+    /// Client certificate and key for mutual TLS, both or none have to be set.
+    pub client_certificate_file: Option<String>,
+    pub client_key_file: Option<String>,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -73,6 +163,50 @@ pub struct InfluxDbSettings {
     pub bucket_id: String,
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub timeout_milliseconds: u64,
+    pub batch: InfluxDbBatchSettings,
+    pub spool: InfluxDbSpoolSettings,
+}
+
+/// The point count sizes the channel of the batch writer and the requests count its
+/// semaphore, neither works with 0 so it is refused when the configuration is loaded.
+#[derive(serde::Deserialize, Clone)]
+pub struct InfluxDbBatchSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_points: NonZeroUsize,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_bytes: usize,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub flush_interval_milliseconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_in_flight_requests: NonZeroUsize,
+}
+
+impl InfluxDbBatchSettings {
+    pub fn flush_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.flush_interval_milliseconds)
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct InfluxDbSpoolSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_points: i64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub replay_batch_size: i64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub replay_interval_milliseconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_backoff_milliseconds: u64,
+}
+
+impl InfluxDbSpoolSettings {
+    pub fn replay_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.replay_interval_milliseconds)
+    }
+
+    pub fn max_backoff(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.max_backoff_milliseconds)
+    }
 }
 
 impl InfluxDbSettings {
@@ -93,6 +227,55 @@ impl InfluxDbSettings {
     }
 }
 
+#[derive(serde::Deserialize, Clone)]
+pub struct ValidationSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub rate_of_change_window_minutes: i64,
+    pub fields: BTreeMap<String, FieldLimitSettings>,
+}
+
+impl ValidationSettings {
+    pub fn rate_of_change_window(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.rate_of_change_window_minutes)
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct FieldLimitSettings {
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub min: Option<f64>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub max: Option<f64>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub max_change_per_minute: Option<f64>,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct SupervisorSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub initial_backoff_milliseconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_backoff_milliseconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_restarts: usize,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub restart_window_seconds: u64,
+}
+
+impl SupervisorSettings {
+    pub fn initial_backoff(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.initial_backoff_milliseconds)
+    }
+
+    pub fn max_backoff(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.max_backoff_milliseconds)
+    }
+
+    pub fn restart_window(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.restart_window_seconds)
+    }
+}
+
 pub fn get_configuration() -> Result<Settings, config::ConfigError> {
     let base_path = std::env::current_dir().expect("Failed to determine the current directory");
     let configuration_directory = base_path.join("configuration");
@@ -0,0 +1,126 @@
+use crate::domain::id::Id;
+use crate::domain::{Measurement, MeasurementValue, SensorReading};
+use chrono::{DateTime, Utc};
+
+/// A version of the scale calibration of a device.
+///
+/// The calibrated weight is computed as
+/// `(weight - offset - tare_offset) * scale_factor + temperature_coefficient * (temperature - reference_temperature)`
+/// where the temperature compensation is skipped for readings without a temperature.
+#[derive(Debug, Clone)]
+pub struct Calibration {
+    pub device_id: uuid::Uuid,
+    pub version: i32,
+    pub tare_offset: f64,
+    pub scale_factor: f64,
+    pub temperature_coefficient: f64,
+    pub reference_temperature: f64,
+    pub valid_from: DateTime<Utc>,
+}
+
+impl Calibration {
+    pub fn weight_kg(&self, reading: &SensorReading) -> Option<f64> {
+        let weight = reading.value("weight")?;
+        let offset = reading.value("offset").unwrap_or_default();
+        let compensation = reading
+            .value("temperature")
+            .map(|t| self.temperature_coefficient * (t - self.reference_temperature))
+            .unwrap_or_default();
+
+        Some((weight - offset - self.tare_offset) * self.scale_factor + compensation)
+    }
+
+    /// Adds the calibrated `weight_kg` next to the raw values, together with the
+    /// calibration version it was computed with.
+    pub fn apply(&self, reading: &mut SensorReading) {
+        if let Some(weight_kg) = self.weight_kg(reading) {
+            reading.measurements.insert(
+                "weight_kg".to_string(),
+                Measurement {
+                    value: MeasurementValue::Float(weight_kg),
+                    unit: Some("kg".to_string()),
+                },
+            );
+            reading.measurements.insert(
+                "calibration_version".to_string(),
+                Measurement {
+                    value: MeasurementValue::Integer(i64::from(self.version)),
+                    unit: None,
+                },
+            );
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NewCalibration {
+    pub device_id: Id,
+    pub tare_offset: f64,
+    pub scale_factor: f64,
+    pub temperature_coefficient: f64,
+    pub reference_temperature: f64,
+    pub valid_from: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Calibration;
+    use crate::domain::{MeasurementValue, SensorReading};
+    use chrono::Utc;
+    use claims::assert_none;
+    use serde_json::json;
+
+    fn calibration() -> Calibration {
+        Calibration {
+            device_id: uuid::Uuid::new_v4(),
+            version: 3,
+            tare_offset: 1000.0,
+            scale_factor: 0.001,
+            temperature_coefficient: 0.01,
+            reference_temperature: 20.0,
+            valid_from: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn weight_is_tared_scaled_and_compensated() {
+        let reading = SensorReading::try_from(json!({
+            "weight": 51_000,
+            "offset": 0,
+            "temperature": 30.0
+        }))
+        .unwrap();
+
+        let weight_kg = calibration().weight_kg(&reading).unwrap();
+        assert!((weight_kg - 50.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn compensation_is_skipped_without_temperature() {
+        let reading = SensorReading::try_from(json!({"weight": 51_000, "offset": 500})).unwrap();
+
+        let weight_kg = calibration().weight_kg(&reading).unwrap();
+        assert!((weight_kg - 49.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn calibrated_weight_is_added_next_to_raw_values() {
+        let mut reading = SensorReading::try_from(json!({"weight": 51_000})).unwrap();
+        calibration().apply(&mut reading);
+
+        assert_eq!(reading.value("weight"), Some(51_000.0));
+        assert_eq!(reading.value("weight_kg"), Some(50.0));
+        assert_eq!(
+            reading.measurements["calibration_version"].value,
+            MeasurementValue::Integer(3)
+        );
+    }
+
+    #[test]
+    fn readings_without_weight_are_left_untouched() {
+        let mut reading = SensorReading::try_from(json!({"temperature": 21.0})).unwrap();
+        calibration().apply(&mut reading);
+
+        assert_none!(reading.value("weight_kg"));
+    }
+}
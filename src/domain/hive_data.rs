@@ -1,7 +1,14 @@
-use serde::Deserialize;
+use crate::domain::SensorReading;
+use crate::line_protocol::LinePoint;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Deserializer};
 use serde_aux::field_attributes::deserialize_number_from_string;
 
-#[derive(Deserialize, Debug)]
+// Epoch values below this bound are treated as seconds, above it as milliseconds.
+// It is reached in seconds only in the year 5138, in milliseconds already in 1973.
+const EPOCH_MILLISECONDS_THRESHOLD: i64 = 100_000_000_000;
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct HiveData {
     pub device_name: String,
     #[serde(deserialize_with = "deserialize_number_from_string")]
@@ -16,6 +23,8 @@ pub struct HiveData {
     pub battery_level: f32,
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub signal_quality: u32,
+    #[serde(default, deserialize_with = "deserialize_device_timestamp")]
+    pub timestamp: Option<DateTime<Utc>>,
 }
 
 impl TryFrom<Vec<u8>> for HiveData {
@@ -33,10 +42,113 @@ impl TryFrom<Vec<u8>> for HiveData {
 }
 
 impl HiveData {
-    pub fn format_line_point(&self) -> String {
+    /// Builds the `hive_sensors` line protocol point with a millisecond timestamp.
+    ///
+    /// The device timestamp is used when the payload carries one, `received_at` otherwise.
+    pub fn line_point(&self, received_at: DateTime<Utc>) -> LinePoint {
+        SensorReading::from(self.clone()).line_point(received_at)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawTimestamp {
+    Epoch(i64),
+    Text(String),
+}
+
+/// Accepts epoch seconds, epoch milliseconds (as numbers or numeric strings) and RFC3339.
+pub(crate) fn deserialize_device_timestamp<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let raw = match Option::<RawTimestamp>::deserialize(deserializer)? {
+        Some(raw) => raw,
+        None => return Ok(None),
+    };
+
+    let timestamp = match raw {
+        RawTimestamp::Epoch(epoch) => from_epoch(epoch),
+        RawTimestamp::Text(text) => match text.trim().parse::<i64>() {
+            Ok(epoch) => from_epoch(epoch),
+            Err(_) => DateTime::parse_from_rfc3339(text.trim())
+                .ok()
+                .map(|t| t.with_timezone(&Utc)),
+        },
+    };
+
+    timestamp
+        .map(Some)
+        .ok_or_else(|| serde::de::Error::custom("invalid device timestamp"))
+}
+
+fn from_epoch(epoch: i64) -> Option<DateTime<Utc>> {
+    if epoch.abs() < EPOCH_MILLISECONDS_THRESHOLD {
+        Utc.timestamp_opt(epoch, 0).single()
+    } else {
+        Utc.timestamp_millis_opt(epoch).single()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HiveData;
+    use chrono::{TimeZone, Utc};
+    use claims::{assert_err, assert_none, assert_ok};
+
+    fn payload_with_timestamp(timestamp: &str) -> Vec<u8> {
         format!(
-            "hive_sensors,device_name={} temperature={},humidity={},weight={},offset={},battery_level={},signal_quality={}",
-            self.device_name, self.temperature, self.humidity, self.weight, self.offset, self.battery_level, self.signal_quality
+            r#"{{"device_name":"hive-1","weight":"1000","offset":"10","temperature":"21.5","humidity":"40","battery_level":"3.7","signal_quality":"20"{timestamp}}}"#
         )
+        .into_bytes()
+    }
+
+    #[test]
+    fn missing_timestamp_is_accepted() {
+        let data = assert_ok!(HiveData::try_from(payload_with_timestamp("")));
+        assert_none!(data.timestamp);
+    }
+
+    #[test]
+    fn epoch_seconds_and_milliseconds_are_parsed() {
+        let expected = Utc.timestamp_opt(1_684_000_000, 0).unwrap();
+
+        for timestamp in [
+            r#","timestamp":1684000000"#,
+            r#","timestamp":1684000000000"#,
+            r#","timestamp":"1684000000""#,
+        ] {
+            let data = assert_ok!(HiveData::try_from(payload_with_timestamp(timestamp)));
+            assert_eq!(data.timestamp, Some(expected));
+        }
+    }
+
+    #[test]
+    fn rfc3339_timestamp_is_parsed() {
+        let data = assert_ok!(HiveData::try_from(payload_with_timestamp(
+            r#","timestamp":"2023-05-13T19:46:40+02:00""#
+        )));
+        assert_eq!(
+            data.timestamp,
+            Some(Utc.timestamp_opt(1_684_000_000, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn invalid_timestamp_is_rejected() {
+        assert_err!(HiveData::try_from(payload_with_timestamp(
+            r#","timestamp":"yesterday""#
+        )));
+    }
+
+    #[test]
+    fn line_point_falls_back_to_receive_time() {
+        let data = assert_ok!(HiveData::try_from(payload_with_timestamp("")));
+        let received_at = Utc.timestamp_millis_opt(1_684_000_000_123).unwrap();
+
+        assert_eq!(
+            data.line_point(received_at).to_string(),
+            "hive_sensors,device_name=hive-1 battery_level=3.7,humidity=40,offset=10i,signal_quality=20i,temperature=21.5,weight=1000i 1684000000123"
+        );
     }
 }
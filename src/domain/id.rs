@@ -12,6 +12,12 @@ impl Id {
     }
 }
 
+impl From<Uuid> for Id {
+    fn from(id: Uuid) -> Self {
+        Self(id)
+    }
+}
+
 impl AsRef<Uuid> for Id {
     fn as_ref(&self) -> &Uuid {
         &self.0
@@ -3,7 +3,28 @@ mod id;
 mod username;
 mod email;
 mod hive_data;
+mod payload_decoder;
+mod quarantined_reading;
+mod reading_validator;
+mod sensor_reading;
+mod calibration;
+mod topic_filter;
+mod pending_device;
+mod role;
+mod registry;
 
 pub use subscriber_topic::{ViewSubscriberTopic, NewSubscriberTopic};
 pub use id::Id;
-pub use hive_data::HiveData;
\ No newline at end of file
+pub use username::UserName;
+pub use hive_data::HiveData;
+pub use payload_decoder::{PayloadDecoder, PayloadFormat};
+pub use quarantined_reading::ViewQuarantinedReading;
+pub use reading_validator::ReadingValidator;
+pub use sensor_reading::{Measurement, MeasurementValue, SensorReading};
+pub use calibration::{Calibration, NewCalibration};
+pub use topic_filter::{DeviceNamePattern, TopicPrefix};
+pub use pending_device::ViewPendingDevice;
+pub use role::Role;
+pub use registry::{
+    optional_text, NewDevice, NewOrganization, RegistryName, ViewDevice, ViewOrganization,
+};
\ No newline at end of file
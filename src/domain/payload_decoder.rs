@@ -0,0 +1,291 @@
+use crate::domain::{HiveData, SensorReading};
+use anyhow::Context;
+use chrono::{TimeZone, Utc};
+
+/// Turns a raw mqtt payload into a sensor reading.
+pub trait PayloadDecoder: Send + Sync {
+    fn decode(&self, payload: &[u8]) -> Result<SensorReading, anyhow::Error>;
+}
+
+/// Payload format of a subscription, stored in `subscriptions_topics.payload_format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadFormat {
+    Json,
+    Cbor,
+    Csv,
+    Binary,
+}
+
+impl PayloadFormat {
+    pub const ALL: [PayloadFormat; 4] = [Self::Json, Self::Cbor, Self::Csv, Self::Binary];
+
+    pub fn parse(s: String) -> Result<PayloadFormat, String> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.as_str() == s.trim().to_lowercase())
+            .ok_or_else(|| format!("{} is not a supported payload format.", s))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PayloadFormat::Json => "json",
+            PayloadFormat::Cbor => "cbor",
+            PayloadFormat::Csv => "csv",
+            PayloadFormat::Binary => "binary",
+        }
+    }
+
+    pub fn decoder(&self) -> &'static dyn PayloadDecoder {
+        match self {
+            PayloadFormat::Json => &JsonDecoder,
+            PayloadFormat::Cbor => &CborDecoder,
+            PayloadFormat::Csv => &CsvDecoder,
+            PayloadFormat::Binary => &BinaryDecoder,
+        }
+    }
+}
+
+impl AsRef<str> for PayloadFormat {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+/// JSON object with a device name, a timestamp and any named measurements.
+pub struct JsonDecoder;
+
+impl PayloadDecoder for JsonDecoder {
+    fn decode(&self, payload: &[u8]) -> Result<SensorReading, anyhow::Error> {
+        SensorReading::try_from(payload.to_vec())
+    }
+}
+
+/// CBOR map with the same content as the JSON payload.
+pub struct CborDecoder;
+
+impl PayloadDecoder for CborDecoder {
+    fn decode(&self, payload: &[u8]) -> Result<SensorReading, anyhow::Error> {
+        let value: serde_json::Value = ciborium::de::from_reader(payload)
+            .context("Error during cbor payload deserializing")?;
+        SensorReading::try_from(value)
+    }
+}
+
+/// A single comma-separated line:
+/// `device_name,weight,offset,temperature,humidity,battery_level,signal_quality[,timestamp]`
+/// where the optional timestamp is given in epoch seconds.
+pub struct CsvDecoder;
+
+impl PayloadDecoder for CsvDecoder {
+    fn decode(&self, payload: &[u8]) -> Result<SensorReading, anyhow::Error> {
+        let line = std::str::from_utf8(payload).context("Error during csv payload reading")?;
+        let values: Vec<&str> = line.trim().split(',').map(str::trim).collect();
+
+        if values.len() != 7 && values.len() != 8 {
+            anyhow::bail!("Expected 7 or 8 csv values, got {}", values.len());
+        }
+
+        let timestamp = match values.get(7) {
+            Some(value) => Some(
+                value
+                    .parse::<i64>()
+                    .ok()
+                    .and_then(|epoch| Utc.timestamp_opt(epoch, 0).single())
+                    .with_context(|| format!("Invalid csv timestamp `{value}`"))?,
+            ),
+            None => None,
+        };
+
+        Ok(HiveData {
+            device_name: values[0].to_string(),
+            weight: parse_csv_value(values[1], "weight")?,
+            offset: parse_csv_value(values[2], "offset")?,
+            temperature: parse_csv_value(values[3], "temperature")?,
+            humidity: parse_csv_value(values[4], "humidity")?,
+            battery_level: parse_csv_value(values[5], "battery_level")?,
+            signal_quality: parse_csv_value(values[6], "signal_quality")?,
+            timestamp,
+        }
+        .into())
+    }
+}
+
+fn parse_csv_value<T: std::str::FromStr>(value: &str, name: &str) -> Result<T, anyhow::Error> {
+    value
+        .parse::<T>()
+        .map_err(|_| anyhow::anyhow!("Invalid csv value `{value}` for {name}"))
+}
+
+/// Packed little-endian frame sent by the older scale firmware:
+///
+/// | bytes | type     | value                                 |
+/// |-------|----------|---------------------------------------|
+/// | 1     | u8       | device name length `n`                |
+/// | n     | utf-8    | device name                           |
+/// | 4     | u32      | weight                                |
+/// | 4     | u32      | offset                                |
+/// | 4     | f32      | temperature                           |
+/// | 4     | f32      | humidity                              |
+/// | 4     | f32      | battery level                         |
+/// | 4     | u32      | signal quality                        |
+/// | 4     | u32      | optional timestamp, epoch seconds     |
+pub struct BinaryDecoder;
+
+const BINARY_READINGS_LENGTH: usize = 24;
+
+impl PayloadDecoder for BinaryDecoder {
+    fn decode(&self, payload: &[u8]) -> Result<SensorReading, anyhow::Error> {
+        let (name_length, rest) = payload.split_first().context("Empty binary payload")?;
+        let name_length = usize::from(*name_length);
+
+        if rest.len() != name_length + BINARY_READINGS_LENGTH
+            && rest.len() != name_length + BINARY_READINGS_LENGTH + 4
+        {
+            anyhow::bail!(
+                "Unexpected binary payload length {} for a device name of {} bytes",
+                payload.len(),
+                name_length
+            );
+        }
+
+        let (device_name, readings) = rest.split_at(name_length);
+        let device_name = std::str::from_utf8(device_name)
+            .context("Error during binary device name reading")?
+            .to_string();
+        let word = |index: usize| -> [u8; 4] {
+            let start = index * 4;
+            [
+                readings[start],
+                readings[start + 1],
+                readings[start + 2],
+                readings[start + 3],
+            ]
+        };
+
+        let timestamp = if readings.len() > BINARY_READINGS_LENGTH {
+            let epoch = i64::from(u32::from_le_bytes(word(6)));
+            Some(
+                Utc.timestamp_opt(epoch, 0)
+                    .single()
+                    .context("Invalid binary timestamp")?,
+            )
+        } else {
+            None
+        };
+
+        Ok(HiveData {
+            device_name,
+            weight: u32::from_le_bytes(word(0)),
+            offset: u32::from_le_bytes(word(1)),
+            temperature: f32::from_le_bytes(word(2)),
+            humidity: f32::from_le_bytes(word(3)),
+            battery_level: f32::from_le_bytes(word(4)),
+            signal_quality: u32::from_le_bytes(word(5)),
+            timestamp,
+        }
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PayloadFormat;
+    use crate::domain::HiveData;
+    use chrono::{TimeZone, Utc};
+    use claims::{assert_err, assert_none, assert_ok};
+
+    fn decode(format: PayloadFormat, payload: &[u8]) -> HiveData {
+        let reading = assert_ok!(format.decoder().decode(payload));
+        assert_ok!(HiveData::try_from(&reading))
+    }
+
+    #[test]
+    fn known_formats_are_parsed() {
+        for format in PayloadFormat::ALL {
+            assert_eq!(
+                assert_ok!(PayloadFormat::parse(format.as_str().to_uppercase())),
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_format_is_rejected() {
+        assert_err!(PayloadFormat::parse("xml".to_string()));
+    }
+
+    #[test]
+    fn json_payload_is_decoded() {
+        let payload = br#"{"device_name":"hive-1","weight":1000,"offset":10,"temperature":21.5,"humidity":40,"battery_level":3.7,"signal_quality":20}"#;
+        let data = decode(PayloadFormat::Json, payload);
+        assert_eq!(data.device_name, "hive-1");
+        assert_eq!(data.weight, 1000);
+    }
+
+    #[test]
+    fn cbor_payload_is_decoded() {
+        let mut payload = Vec::new();
+        ciborium::ser::into_writer(
+            &serde_json::json!({
+                "device_name": "hive-1",
+                "weight": 1000,
+                "offset": 10,
+                "temperature": 21.5,
+                "humidity": 40.0,
+                "battery_level": 3.7,
+                "signal_quality": 20,
+                "timestamp": 1684000000
+            }),
+            &mut payload,
+        )
+        .unwrap();
+
+        let data = decode(PayloadFormat::Cbor, &payload);
+        assert_eq!(data.weight, 1000);
+        assert_eq!(data.humidity, 40.0);
+        assert_eq!(data.timestamp, Utc.timestamp_opt(1_684_000_000, 0).single());
+    }
+
+    #[test]
+    fn csv_payload_is_decoded() {
+        let data = decode(
+            PayloadFormat::Csv,
+            b"hive-1,1000,10,21.5,40,3.7,20,1684000000\n",
+        );
+        assert_eq!(data.device_name, "hive-1");
+        assert_eq!(data.signal_quality, 20);
+        assert_eq!(data.timestamp, Utc.timestamp_opt(1_684_000_000, 0).single());
+    }
+
+    #[test]
+    fn csv_payload_with_missing_values_is_rejected() {
+        assert_err!(PayloadFormat::Csv.decoder().decode(b"hive-1,1000,10"));
+    }
+
+    #[test]
+    fn binary_payload_is_decoded() {
+        let mut payload = vec![6u8];
+        payload.extend_from_slice(b"hive-1");
+        payload.extend_from_slice(&1000u32.to_le_bytes());
+        payload.extend_from_slice(&10u32.to_le_bytes());
+        payload.extend_from_slice(&21.5f32.to_le_bytes());
+        payload.extend_from_slice(&40f32.to_le_bytes());
+        payload.extend_from_slice(&3.7f32.to_le_bytes());
+        payload.extend_from_slice(&20u32.to_le_bytes());
+
+        let data = decode(PayloadFormat::Binary, &payload);
+        assert_eq!(data.device_name, "hive-1");
+        assert_eq!(data.temperature, 21.5);
+        assert_eq!(data.signal_quality, 20);
+        assert_none!(data.timestamp);
+
+        payload.extend_from_slice(&1_684_000_000u32.to_le_bytes());
+        let data = decode(PayloadFormat::Binary, &payload);
+        assert_eq!(data.timestamp, Utc.timestamp_opt(1_684_000_000, 0).single());
+    }
+
+    #[test]
+    fn truncated_binary_payload_is_rejected() {
+        assert_err!(PayloadFormat::Binary.decoder().decode(&[6u8, b'h', b'i']));
+    }
+}
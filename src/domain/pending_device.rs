@@ -0,0 +1,13 @@
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone)]
+pub struct ViewPendingDevice {
+    pub id: uuid::Uuid,
+    pub organization_id: uuid::Uuid,
+    pub topic_prefix: String,
+    pub device_name: String,
+    pub status: String,
+    pub messages: i64,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
@@ -0,0 +1,10 @@
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone)]
+pub struct ViewQuarantinedReading {
+    pub topic: String,
+    pub device_name: Option<String>,
+    pub line_point: String,
+    pub reason: String,
+    pub received_at: DateTime<Utc>,
+}
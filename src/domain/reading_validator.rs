@@ -0,0 +1,170 @@
+use crate::configuration::{FieldLimitSettings, ValidationSettings};
+use crate::domain::SensorReading;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{BTreeMap, HashMap};
+
+/// Rejects readings outside of physically plausible bounds.
+///
+/// Every field is checked against its configured min/max bounds and against the last
+/// accepted value of the same device. The rate of change is only checked while the
+/// last accepted value is younger than the rate of change window, so a device whose
+/// readings legitimately jumped is accepted again after a while.
+pub struct ReadingValidator {
+    fields: BTreeMap<String, FieldLimitSettings>,
+    rate_of_change_window: Duration,
+    last_accepted: HashMap<String, HashMap<String, (DateTime<Utc>, f64)>>,
+}
+
+impl ReadingValidator {
+    pub fn new(settings: ValidationSettings) -> Self {
+        Self {
+            rate_of_change_window: settings.rate_of_change_window(),
+            fields: settings.fields,
+            last_accepted: HashMap::new(),
+        }
+    }
+
+    /// Validates the reading of `device` taken at `taken_at` and remembers its values
+    /// when it is accepted. The error describes why the reading was rejected.
+    pub fn validate(
+        &mut self,
+        device: &str,
+        reading: &SensorReading,
+        taken_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        let last_accepted = self.last_accepted.get(device);
+
+        for (name, limits) in &self.fields {
+            let value = match reading.value(name) {
+                Some(value) => value,
+                None => continue,
+            };
+
+            if let Some(min) = limits.min.filter(|min| value < *min) {
+                return Err(format!("{name} {value} is below the minimum of {min}"));
+            }
+            if let Some(max) = limits.max.filter(|max| value > *max) {
+                return Err(format!("{name} {value} is above the maximum of {max}"));
+            }
+
+            let last = last_accepted.and_then(|values| values.get(name));
+            if let (Some(max_change), Some((last_at, last_value))) =
+                (limits.max_change_per_minute, last)
+            {
+                let elapsed = (taken_at - *last_at).num_milliseconds().abs();
+                if elapsed > 0 && elapsed <= self.rate_of_change_window.num_milliseconds() {
+                    let change = (value - last_value).abs() / (elapsed as f64 / 60_000.0);
+                    if change > max_change {
+                        return Err(format!(
+                            "{name} changed from {last_value} to {value}, {change:.2} per minute is above the maximum of {max_change}"
+                        ));
+                    }
+                }
+            }
+        }
+
+        let values = self.last_accepted.entry(device.to_string()).or_default();
+        for (name, measurement) in &reading.measurements {
+            values.insert(name.clone(), (taken_at, measurement.value.as_f64()));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReadingValidator;
+    use crate::configuration::{FieldLimitSettings, ValidationSettings};
+    use crate::domain::SensorReading;
+    use chrono::{Duration, TimeZone, Utc};
+    use claims::{assert_err, assert_ok};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    fn validator() -> ReadingValidator {
+        ReadingValidator::new(ValidationSettings {
+            rate_of_change_window_minutes: 60,
+            fields: BTreeMap::from([
+                (
+                    "weight".to_string(),
+                    FieldLimitSettings {
+                        min: Some(0.0),
+                        max: Some(300_000.0),
+                        max_change_per_minute: Some(2_000.0),
+                    },
+                ),
+                (
+                    "humidity".to_string(),
+                    FieldLimitSettings {
+                        min: Some(0.0),
+                        max: Some(100.0),
+                        max_change_per_minute: None,
+                    },
+                ),
+            ]),
+        })
+    }
+
+    fn reading(value: serde_json::Value) -> SensorReading {
+        SensorReading::try_from(value).unwrap()
+    }
+
+    #[test]
+    fn values_within_bounds_are_accepted() {
+        let mut validator = validator();
+        assert_ok!(validator.validate(
+            "hive-1",
+            &reading(json!({"weight": 50_000, "humidity": 55.0})),
+            Utc::now()
+        ));
+    }
+
+    #[test]
+    fn values_out_of_bounds_are_rejected() {
+        let mut validator = validator();
+        assert_err!(validator.validate(
+            "hive-1",
+            &reading(json!({"weight": 4_000_000_000u32})),
+            Utc::now()
+        ));
+        assert_err!(validator.validate("hive-1", &reading(json!({"humidity": 250})), Utc::now()));
+    }
+
+    #[test]
+    fn too_fast_changes_are_rejected() {
+        let mut validator = validator();
+        let at = Utc.timestamp_opt(1_684_000_000, 0).unwrap();
+
+        assert_ok!(validator.validate("hive-1", &reading(json!({"weight": 50_000})), at));
+        assert_ok!(validator.validate(
+            "hive-1",
+            &reading(json!({"weight": 51_000})),
+            at + Duration::minutes(1)
+        ));
+        assert_err!(validator.validate(
+            "hive-1",
+            &reading(json!({"weight": 80_000})),
+            at + Duration::minutes(2)
+        ));
+        // Other devices are not compared with each other.
+        assert_ok!(validator.validate(
+            "hive-2",
+            &reading(json!({"weight": 80_000})),
+            at + Duration::minutes(2)
+        ));
+    }
+
+    #[test]
+    fn rate_of_change_is_not_checked_outside_of_the_window() {
+        let mut validator = validator();
+        let at = Utc.timestamp_opt(1_684_000_000, 0).unwrap();
+
+        assert_ok!(validator.validate("hive-1", &reading(json!({"weight": 50_000})), at));
+        assert_ok!(validator.validate(
+            "hive-1",
+            &reading(json!({"weight": 250_000})),
+            at + Duration::minutes(61)
+        ));
+    }
+}
@@ -0,0 +1,86 @@
+use crate::domain::id::Id;
+use unicode_segmentation::UnicodeSegmentation;
+use uuid::Uuid;
+
+/// Name of an apiary or a hive in the registry.
+#[derive(Debug, Clone)]
+pub struct RegistryName(String);
+
+impl RegistryName {
+    pub fn parse(s: String) -> Result<RegistryName, String> {
+        let s = s.trim().to_string();
+        if s.is_empty() || s.graphemes(true).count() > 256 {
+            return Err(format!("{:?} is not a valid name.", s));
+        }
+        Ok(Self(s))
+    }
+}
+
+impl AsRef<str> for RegistryName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Optional free text of the registry forms, blank fields are stored as null.
+pub fn optional_text(s: String) -> Option<String> {
+    let s = s.trim();
+    (!s.is_empty()).then(|| s.to_string())
+}
+
+#[derive(Debug, Clone)]
+pub struct NewOrganization {
+    pub name: RegistryName,
+    pub location: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewDevice {
+    pub organization_id: Id,
+    pub name: RegistryName,
+    pub location: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ViewOrganization {
+    pub id: Uuid,
+    pub name: String,
+    pub location: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ViewDevice {
+    pub id: Uuid,
+    pub organization_id: Uuid,
+    pub organization_name: String,
+    pub name: String,
+    pub location: Option<String>,
+    pub description: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{optional_text, RegistryName};
+    use claims::{assert_err, assert_none, assert_ok, assert_some_eq};
+
+    #[test]
+    fn names_are_trimmed() {
+        let name = assert_ok!(RegistryName::parse("  Meadow apiary ".to_string()));
+        assert_eq!(name.as_ref(), "Meadow apiary");
+    }
+
+    #[test]
+    fn blank_and_too_long_names_are_rejected() {
+        assert_err!(RegistryName::parse(" ".to_string()));
+        assert_err!(RegistryName::parse("a".repeat(257)));
+    }
+
+    #[test]
+    fn blank_optional_text_is_none() {
+        assert_none!(optional_text("  ".to_string()));
+        assert_some_eq!(optional_text(" north field ".to_string()), "north field");
+    }
+}
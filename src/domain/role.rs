@@ -0,0 +1,55 @@
+/// Role of a user within an organization, superadmins are not members and manage all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Admin,
+    Beekeeper,
+}
+
+impl Role {
+    pub const ALL: [Role; 2] = [Self::Admin, Self::Beekeeper];
+
+    pub fn parse(s: String) -> Result<Role, String> {
+        Self::ALL
+            .into_iter()
+            .find(|role| role.as_str() == s.trim().to_lowercase())
+            .ok_or_else(|| format!("{} is not a valid role.", s))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Admin => "admin",
+            Role::Beekeeper => "beekeeper",
+        }
+    }
+
+    /// Beekeepers have read-only access to the organization.
+    pub fn can_edit(&self) -> bool {
+        match self {
+            Role::Admin => true,
+            Role::Beekeeper => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Role;
+    use claims::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn roles_are_parsed_case_insensitively() {
+        assert_ok_eq!(Role::parse("Admin".to_string()), Role::Admin);
+        assert_ok_eq!(Role::parse(" beekeeper".to_string()), Role::Beekeeper);
+    }
+
+    #[test]
+    fn superadmin_is_not_an_organization_role() {
+        assert_err!(Role::parse("superadmin".to_string()));
+    }
+
+    #[test]
+    fn only_admins_can_edit() {
+        assert!(Role::Admin.can_edit());
+        assert!(!Role::Beekeeper.can_edit());
+    }
+}
@@ -0,0 +1,338 @@
+use crate::domain::hive_data::deserialize_device_timestamp;
+use crate::domain::HiveData;
+use crate::line_protocol::{widen_f32, FieldValue, LinePoint};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Measurements of the current firmware with the type they are always written with.
+const KNOWN_MEASUREMENTS: [(&str, MeasurementKind); 6] = [
+    ("weight", MeasurementKind::Integer),
+    ("offset", MeasurementKind::Integer),
+    ("temperature", MeasurementKind::Float),
+    ("humidity", MeasurementKind::Float),
+    ("battery_level", MeasurementKind::Float),
+    ("signal_quality", MeasurementKind::Integer),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MeasurementKind {
+    Integer,
+    Float,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MeasurementValue {
+    Integer(i64),
+    Float(f64),
+}
+
+impl MeasurementValue {
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            MeasurementValue::Integer(v) => *v as f64,
+            MeasurementValue::Float(v) => *v,
+        }
+    }
+}
+
+impl From<MeasurementValue> for FieldValue {
+    fn from(value: MeasurementValue) -> Self {
+        match value {
+            MeasurementValue::Integer(v) => FieldValue::Integer(v),
+            MeasurementValue::Float(v) => FieldValue::Float(v),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    pub value: MeasurementValue,
+    pub unit: Option<String>,
+}
+
+/// A reading of any set of sensors reported by a device.
+///
+/// The measurements of the current firmware are validated and typed, any other numeric
+/// measurement is kept as a float so it is written with the same type in every point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SensorReading {
+    pub device_name: Option<String>,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub measurements: BTreeMap<String, Measurement>,
+}
+
+impl SensorReading {
+    pub fn value(&self, name: &str) -> Option<f64> {
+        self.measurements.get(name).map(|m| m.value.as_f64())
+    }
+
+    /// Builds the `hive_sensors` line protocol point with a millisecond timestamp.
+    ///
+    /// The device timestamp is used when the payload carries one, `received_at` otherwise.
+    pub fn line_point(&self, received_at: DateTime<Utc>) -> LinePoint {
+        let timestamp = self.timestamp.unwrap_or(received_at);
+        let mut point = LinePoint::new("hive_sensors")
+            .tag("device_name", self.device_name.clone().unwrap_or_default());
+
+        for (name, measurement) in &self.measurements {
+            point = point.field(name.as_str(), measurement.value);
+        }
+
+        point.timestamp(timestamp.timestamp_millis())
+    }
+}
+
+impl TryFrom<Vec<u8>> for SensorReading {
+    type Error = anyhow::Error;
+
+    fn try_from(encoded_value: Vec<u8>) -> Result<Self, Self::Error> {
+        let value = serde_json::from_slice::<Value>(&encoded_value)
+            .context("Error during raw payload deserializing")?;
+        Self::try_from(value)
+    }
+}
+
+impl TryFrom<Value> for SensorReading {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let mut object = match value {
+            Value::Object(object) => object,
+            other => anyhow::bail!("Expected a payload object, got {other}"),
+        };
+
+        let device_name = match object.remove("device_name") {
+            None | Some(Value::Null) => None,
+            Some(Value::String(name)) => Some(name),
+            Some(other) => anyhow::bail!("Invalid device name {other}"),
+        };
+        let timestamp = match object.remove("timestamp") {
+            None => None,
+            Some(value) => deserialize_device_timestamp(value)?,
+        };
+
+        let mut measurements = BTreeMap::new();
+        for (name, value) in object {
+            let known_kind = KNOWN_MEASUREMENTS
+                .iter()
+                .find(|(known, _)| *known == name)
+                .map(|(_, kind)| *kind);
+
+            match (parse_measurement(value), known_kind) {
+                (Some((value, unit)), Some(kind)) => {
+                    let value = typed_value(value, kind)
+                        .with_context(|| format!("Invalid value {value} for {name}"))?;
+                    measurements.insert(name, Measurement { value, unit });
+                }
+                (Some((value, unit)), None) => {
+                    let value = MeasurementValue::Float(value);
+                    measurements.insert(name, Measurement { value, unit });
+                }
+                (None, Some(_)) => anyhow::bail!("Invalid value for {name}"),
+                // Unknown measurements that are not numeric are not written.
+                (None, None) => {}
+            }
+        }
+        // Influxdb rejects a point without any field.
+        if measurements.is_empty() {
+            anyhow::bail!("The payload has no numeric measurement");
+        }
+
+        Ok(Self {
+            device_name,
+            timestamp,
+            measurements,
+        })
+    }
+}
+
+/// Reads a number, a numeric string or a `{"value": .., "unit": ..}` object.
+fn parse_measurement(value: Value) -> Option<(f64, Option<String>)> {
+    match value {
+        Value::Number(number) => number.as_f64().map(|v| (v, None)),
+        Value::String(text) => text.trim().parse::<f64>().ok().map(|v| (v, None)),
+        Value::Object(mut object) => {
+            let unit = match object.remove("unit") {
+                Some(Value::String(unit)) => Some(unit),
+                _ => None,
+            };
+            let (value, _) = parse_measurement(object.remove("value")?)?;
+            Some((value, unit))
+        }
+        _ => None,
+    }
+    .filter(|(v, _)| v.is_finite())
+}
+
+fn typed_value(value: f64, kind: MeasurementKind) -> Option<MeasurementValue> {
+    match kind {
+        MeasurementKind::Float => Some(MeasurementValue::Float(value)),
+        MeasurementKind::Integer => {
+            if value.fract() == 0.0 && (0.0..=f64::from(u32::MAX)).contains(&value) {
+                Some(MeasurementValue::Integer(value as i64))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+fn measurement(value: MeasurementValue) -> Measurement {
+    Measurement { value, unit: None }
+}
+
+impl From<HiveData> for SensorReading {
+    fn from(data: HiveData) -> Self {
+        let measurements = BTreeMap::from([
+            (
+                "weight".to_string(),
+                measurement(MeasurementValue::Integer(i64::from(data.weight))),
+            ),
+            (
+                "offset".to_string(),
+                measurement(MeasurementValue::Integer(i64::from(data.offset))),
+            ),
+            (
+                "temperature".to_string(),
+                measurement(MeasurementValue::Float(widen_f32(data.temperature))),
+            ),
+            (
+                "humidity".to_string(),
+                measurement(MeasurementValue::Float(widen_f32(data.humidity))),
+            ),
+            (
+                "battery_level".to_string(),
+                measurement(MeasurementValue::Float(widen_f32(data.battery_level))),
+            ),
+            (
+                "signal_quality".to_string(),
+                measurement(MeasurementValue::Integer(i64::from(data.signal_quality))),
+            ),
+        ]);
+
+        Self {
+            device_name: Some(data.device_name),
+            timestamp: data.timestamp,
+            measurements,
+        }
+    }
+}
+
+impl TryFrom<&SensorReading> for HiveData {
+    type Error = anyhow::Error;
+
+    fn try_from(reading: &SensorReading) -> Result<Self, Self::Error> {
+        let required = |name: &str| {
+            reading
+                .value(name)
+                .with_context(|| format!("Missing {name} measurement"))
+        };
+
+        Ok(HiveData {
+            device_name: reading.device_name.clone().context("Missing device name")?,
+            weight: required("weight")? as u32,
+            offset: required("offset")? as u32,
+            temperature: required("temperature")? as f32,
+            humidity: required("humidity")? as f32,
+            battery_level: required("battery_level")? as f32,
+            signal_quality: required("signal_quality")? as u32,
+            timestamp: reading.timestamp,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MeasurementValue, SensorReading};
+    use crate::domain::HiveData;
+    use chrono::{TimeZone, Utc};
+    use claims::{assert_err, assert_none, assert_ok};
+    use serde_json::json;
+
+    #[test]
+    fn unknown_numeric_measurements_pass_through_as_floats() {
+        let reading = assert_ok!(SensorReading::try_from(json!({
+            "device_name": "hive-1",
+            "weight": 1000,
+            "temperature_2": 18,
+            "bee_count": "342",
+            "acoustic_level": {"value": 41.5, "unit": "dB"},
+            "firmware": "v1.2"
+        })));
+
+        assert_eq!(reading.measurements.len(), 4);
+        assert_eq!(
+            reading.measurements["weight"].value,
+            MeasurementValue::Integer(1000)
+        );
+        assert_eq!(
+            reading.measurements["temperature_2"].value,
+            MeasurementValue::Float(18.0)
+        );
+        assert_eq!(reading.value("bee_count"), Some(342.0));
+        assert_eq!(
+            reading.measurements["acoustic_level"].unit.as_deref(),
+            Some("dB")
+        );
+    }
+
+    #[test]
+    fn missing_measurements_are_accepted() {
+        let reading = assert_ok!(SensorReading::try_from(json!({
+            "device_name": "hive-1",
+            "temperature": 21.5
+        })));
+        assert_none!(reading.value("weight"));
+        assert_err!(HiveData::try_from(&reading));
+    }
+
+    #[test]
+    fn payload_without_numeric_measurements_is_rejected() {
+        assert_err!(SensorReading::try_from(json!({
+            "device_name": "x",
+            "firmware": "v1"
+        })));
+    }
+
+    #[test]
+    fn known_measurements_are_validated() {
+        assert_err!(SensorReading::try_from(json!({"weight": -5})));
+        assert_err!(SensorReading::try_from(json!({"weight": 10.5})));
+        assert_err!(SensorReading::try_from(json!({"humidity": "wet"})));
+    }
+
+    #[test]
+    fn hive_data_round_trips_through_a_sensor_reading() {
+        let reading = assert_ok!(SensorReading::try_from(json!({
+            "device_name": "hive-1",
+            "weight": "1000",
+            "offset": 10,
+            "temperature": 21.5,
+            "humidity": 40,
+            "battery_level": 3.7,
+            "signal_quality": 20,
+            "timestamp": 1684000000
+        })));
+
+        let data = assert_ok!(HiveData::try_from(&reading));
+        assert_eq!(data.weight, 1000);
+        assert_eq!(SensorReading::from(data), reading);
+    }
+
+    #[test]
+    fn line_point_contains_extra_measurements() {
+        let reading = assert_ok!(SensorReading::try_from(json!({
+            "device_name": "hive-1",
+            "weight": 1000,
+            "bee_count": 342
+        })));
+        let received_at = Utc.timestamp_millis_opt(1_684_000_000_123).unwrap();
+
+        assert_eq!(
+            reading.line_point(received_at).to_string(),
+            "hive_sensors,device_name=hive-1 bee_count=342,weight=1000i 1684000000123"
+        );
+    }
+}
@@ -1,17 +1,104 @@
 use crate::domain::id::Id;
+use crate::domain::topic_filter::{self, DeviceNamePattern, TopicPrefix};
+use crate::domain::PayloadFormat;
 
 #[derive(Debug, Clone)]
 pub struct NewSubscriberTopic {
     pub organization_id: Id,
-    pub device_id: Id,
-    pub device_name: String,
-    pub topic_prefix: String,
+    /// Only a wildcard pattern, covering all the devices of the prefix, has no device.
+    pub device_id: Option<Id>,
+    pub device_name: DeviceNamePattern,
+    pub topic_prefix: TopicPrefix,
+    pub payload_format: PayloadFormat,
+}
+
+impl NewSubscriberTopic {
+    /// Topic filter the subscription will be subscribed with.
+    pub fn topic(&self) -> String {
+        format!(
+            "{}/{}",
+            self.topic_prefix.as_ref(),
+            self.device_name.as_ref()
+        )
+    }
+
+    /// Whether the subscription covers several devices with a wildcard pattern.
+    pub fn is_wildcard(&self) -> bool {
+        topic_filter::is_wildcard(self.device_name.as_ref())
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ViewSubscriberTopic {
+    pub id: uuid::Uuid,
     pub organization_id: uuid::Uuid,
-    pub device_id: uuid::Uuid,
+    pub device_id: Option<uuid::Uuid>,
     pub device_name: String,
     pub topic_prefix: String,
+    pub payload_format: String,
+}
+
+impl ViewSubscriberTopic {
+    /// Topic filter the subscription is subscribed with.
+    pub fn topic(&self) -> String {
+        format!("{}/{}", self.topic_prefix, self.device_name)
+    }
+
+    /// Whether the subscription covers several devices with a wildcard pattern.
+    pub fn is_wildcard(&self) -> bool {
+        topic_filter::is_wildcard(&self.device_name)
+    }
+
+    /// The subscription of `subscriptions` that matches `topic` most specifically, so a
+    /// device with its own subscription is not handled by the wildcard of its prefix.
+    pub fn best_match(subscriptions: Vec<Self>, topic: &str) -> Option<Self> {
+        subscriptions
+            .into_iter()
+            .filter(|subscription| topic_filter::topic_matches(&subscription.topic(), topic))
+            .max_by_key(|subscription| topic_filter::specificity(&subscription.topic()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ViewSubscriberTopic;
+    use uuid::Uuid;
+
+    fn subscription(device_name: &str) -> ViewSubscriberTopic {
+        ViewSubscriberTopic {
+            id: Uuid::new_v4(),
+            organization_id: Uuid::new_v4(),
+            device_id: Some(Uuid::new_v4()),
+            device_name: device_name.into(),
+            topic_prefix: "apiary-7".into(),
+            payload_format: "json".into(),
+        }
+    }
+
+    #[test]
+    fn device_subscription_wins_over_the_wildcards() {
+        let subscriptions = vec![subscription("#"), subscription("hive-1"), subscription("+")];
+
+        let matched = ViewSubscriberTopic::best_match(subscriptions, "apiary-7/hive-1").unwrap();
+
+        assert_eq!(matched.device_name, "hive-1");
+        assert!(!matched.is_wildcard());
+    }
+
+    #[test]
+    fn single_level_wildcard_wins_over_multi_level_wildcard() {
+        let subscriptions = vec![subscription("#"), subscription("+"), subscription("hive-1")];
+
+        let matched = ViewSubscriberTopic::best_match(subscriptions, "apiary-7/hive-2").unwrap();
+
+        assert_eq!(matched.device_name, "+");
+        assert!(matched.is_wildcard());
+    }
+
+    #[test]
+    fn topics_without_a_matching_subscription_have_no_match() {
+        let subscriptions = vec![subscription("+"), subscription("hive-1")];
+
+        assert!(ViewSubscriberTopic::best_match(subscriptions, "apiary-7/hive-2/data").is_none());
+    }
 }
@@ -0,0 +1,135 @@
+/// Topic prefix of a subscription, a plain topic without wildcards.
+#[derive(Debug, Clone)]
+pub struct TopicPrefix(String);
+
+impl TopicPrefix {
+    pub fn parse(s: String) -> Result<TopicPrefix, String> {
+        let s = s.trim().to_string();
+        if s.is_empty() || s.starts_with('/') || s.ends_with('/') {
+            return Err(format!("{:?} is not a valid topic prefix.", s));
+        }
+        if s.contains(['+', '#']) {
+            return Err(format!("The topic prefix {} cannot contain wildcards.", s));
+        }
+        Ok(Self(s))
+    }
+}
+
+impl AsRef<str> for TopicPrefix {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Device part of a subscription topic, either the name of a single device or a pattern
+/// of mqtt wildcards matching all the devices of a prefix, like `+` or `#`.
+#[derive(Debug, Clone)]
+pub struct DeviceNamePattern(String);
+
+impl DeviceNamePattern {
+    pub fn parse(s: String) -> Result<DeviceNamePattern, String> {
+        let s = s.trim().to_string();
+        let levels: Vec<&str> = s.split('/').collect();
+        let valid = levels.iter().enumerate().all(|(i, level)| match *level {
+            "" => false,
+            "+" => true,
+            "#" => i == levels.len() - 1,
+            level => !level.contains(['+', '#']),
+        });
+        if !valid {
+            return Err(format!("{:?} is not a valid device name or pattern.", s));
+        }
+        Ok(Self(s))
+    }
+}
+
+impl AsRef<str> for DeviceNamePattern {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+pub fn is_wildcard(filter: &str) -> bool {
+    filter.split('/').any(|level| level == "+" || level == "#")
+}
+
+/// Whether `topic` is matched by the mqtt topic `filter`.
+pub fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut topic_levels = topic.split('/');
+    for level in filter.split('/') {
+        match (level, topic_levels.next()) {
+            ("#", _) => return true,
+            ("+", Some(_)) => {}
+            (level, Some(topic_level)) if level == topic_level => {}
+            _ => return false,
+        }
+    }
+    topic_levels.next().is_none()
+}
+
+/// Orders the filters matching the same topic, the filter with the most plain levels wins
+/// and `+` wins over `#`.
+pub fn specificity(filter: &str) -> (usize, bool) {
+    let plain_levels = filter
+        .split('/')
+        .filter(|level| *level != "+" && *level != "#")
+        .count();
+    (plain_levels, !filter.ends_with('#'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_wildcard, specificity, topic_matches, DeviceNamePattern, TopicPrefix};
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn device_names_and_wildcard_patterns_are_valid() {
+        for pattern in ["hive-1", "+", "#", "+/data", "hive-1/#"] {
+            assert_ok!(DeviceNamePattern::parse(pattern.to_string()));
+        }
+    }
+
+    #[test]
+    fn misplaced_wildcards_are_rejected() {
+        for pattern in ["", "hive+", "#/data", "hive-#", "hive//1"] {
+            assert_err!(DeviceNamePattern::parse(pattern.to_string()));
+        }
+    }
+
+    #[test]
+    fn topic_prefix_cannot_contain_wildcards() {
+        assert_ok!(TopicPrefix::parse("beesbuddy/apiary-7".to_string()));
+        for prefix in ["", "apiary/+", "#", "/apiary-7", "apiary-7/"] {
+            assert_err!(TopicPrefix::parse(prefix.to_string()));
+        }
+    }
+
+    #[test]
+    fn single_level_wildcard_matches_one_level() {
+        assert!(topic_matches("apiary-7/+", "apiary-7/hive-1"));
+        assert!(!topic_matches("apiary-7/+", "apiary-7/hive-1/data"));
+        assert!(!topic_matches("apiary-7/+", "apiary-8/hive-1"));
+    }
+
+    #[test]
+    fn multi_level_wildcard_matches_every_level_below() {
+        assert!(topic_matches("apiary-7/#", "apiary-7/hive-1"));
+        assert!(topic_matches("apiary-7/#", "apiary-7/hive-1/data"));
+        assert!(!topic_matches("apiary-7/#", "apiary-8/hive-1"));
+    }
+
+    #[test]
+    fn plain_filters_match_the_same_topic_only() {
+        assert!(topic_matches("apiary-7/hive-1", "apiary-7/hive-1"));
+        assert!(!topic_matches("apiary-7/hive-1", "apiary-7/hive-10"));
+        assert!(!is_wildcard("apiary-7/hive-1"));
+        assert!(is_wildcard("apiary-7/+"));
+    }
+
+    #[test]
+    fn plain_filters_are_more_specific_than_wildcards() {
+        assert!(specificity("apiary-7/hive-1") > specificity("apiary-7/+"));
+        assert!(specificity("apiary-7/+") > specificity("apiary-7/#"));
+        assert!(specificity("apiary-7/+/data") > specificity("apiary-7/+"));
+    }
+}
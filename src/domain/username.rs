@@ -4,9 +4,8 @@ use unicode_segmentation::UnicodeSegmentation;
 pub struct UserName(String);
 
 impl UserName {
-    /// Returns an instance of `SubscriberName` if the input satisfies all
-    /// our validation constraints on subscriber names.
-    /// It panics otherwise.
+    /// Returns an instance of `UserName` if the input satisfies all
+    /// our validation constraints on user names.
     pub fn parse(s: String) -> Result<UserName, String> {
         // `.trim()` returns a view over the input `s` without trailing
         // whitespace-like characters.
@@ -28,7 +27,7 @@ impl UserName {
         let contains_forbidden_characters = s.chars().any(|g| forbidden_characters.contains(&g));
 
         if is_empty_or_whitespace || is_too_long || contains_forbidden_characters {
-            Err(format!("{} is not a valid subscriber name.", s))
+            Err(format!("{} is not a valid user name.", s))
         } else {
             Ok(Self(s))
         }
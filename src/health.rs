@@ -0,0 +1,94 @@
+use crate::influxdb_client::InfluxDbWriteError;
+use chrono::{DateTime, Utc};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Outcome of the latest influxdb writes.
+#[derive(Debug, Clone, Default)]
+pub struct InfluxDbWrites {
+    pub last_success_at: Option<DateTime<Utc>>,
+    pub last_failure_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+impl InfluxDbWrites {
+    /// Whether the latest write attempt failed.
+    pub fn is_failing(&self) -> bool {
+        match (self.last_success_at, self.last_failure_at) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(success_at), Some(failure_at)) => failure_at > success_at,
+        }
+    }
+}
+
+/// Connection state of the dependencies as seen by the workers, read by the readiness
+/// endpoint.
+#[derive(Debug, Clone, Default)]
+pub struct DependencyHealth {
+    mqtt_connected: Arc<AtomicBool>,
+    subscriptions_listener_connected: Arc<AtomicBool>,
+    influxdb_writes: Arc<Mutex<InfluxDbWrites>>,
+}
+
+impl DependencyHealth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_mqtt_connected(&self, connected: bool) {
+        self.mqtt_connected.store(connected, Ordering::Relaxed);
+    }
+
+    pub fn mqtt_connected(&self) -> bool {
+        self.mqtt_connected.load(Ordering::Relaxed)
+    }
+
+    pub fn set_subscriptions_listener_connected(&self, connected: bool) {
+        self.subscriptions_listener_connected
+            .store(connected, Ordering::Relaxed);
+    }
+
+    pub fn subscriptions_listener_connected(&self) -> bool {
+        self.subscriptions_listener_connected
+            .load(Ordering::Relaxed)
+    }
+
+    /// Rejected points still mean that influxdb is reachable.
+    pub fn record_influxdb_write(&self, outcome: &Result<(), InfluxDbWriteError>) {
+        let mut writes = self.influxdb_writes.lock().unwrap();
+        match outcome {
+            Ok(_) | Err(InfluxDbWriteError::Rejected { .. }) => {
+                writes.last_success_at = Some(Utc::now())
+            }
+            Err(err) => {
+                writes.last_failure_at = Some(Utc::now());
+                writes.last_error = Some(err.to_string());
+            }
+        }
+    }
+
+    pub fn influxdb_writes(&self) -> InfluxDbWrites {
+        self.influxdb_writes.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DependencyHealth;
+
+    #[test]
+    fn influxdb_is_failing_until_the_next_successful_write() {
+        let health = DependencyHealth::new();
+        assert!(!health.influxdb_writes().is_failing());
+
+        health.record_influxdb_write(&Err(anyhow::anyhow!("timeout").into()));
+        let writes = health.influxdb_writes();
+        assert!(writes.is_failing());
+        assert_eq!(writes.last_error.as_deref(), Some("timeout"));
+
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        health.record_influxdb_write(&Ok(()));
+        assert!(!health.influxdb_writes().is_failing());
+    }
+}
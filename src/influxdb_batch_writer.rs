@@ -0,0 +1,203 @@
+use crate::configuration::InfluxDbBatchSettings;
+use crate::influxdb_client::InfluxDbClient;
+use crate::influxdb_spool::InfluxDbSpool;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tracing::{error, warn};
+
+/// Line protocol points gathered for a single write request.
+#[derive(Debug, Default)]
+pub struct LineBatch {
+    lines: Vec<String>,
+    bytes: usize,
+}
+
+impl LineBatch {
+    pub fn push(&mut self, line: String) {
+        // Every line but the first one is prefixed with a new line separator.
+        self.bytes += line.len() + usize::from(!self.lines.is_empty());
+        self.lines.push(line);
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn is_full(&self, max_points: usize, max_bytes: usize) -> bool {
+        self.len() >= max_points || self.bytes >= max_bytes
+    }
+
+    pub fn take(&mut self) -> Vec<String> {
+        self.bytes = 0;
+        std::mem::take(&mut self.lines)
+    }
+}
+
+/// Handle to a background task that gathers points into batched influxdb writes.
+///
+/// The batch is flushed when it reaches the configured point count or size, when the
+/// flush interval elapses and when every handle has been dropped.
+#[derive(Clone)]
+pub struct InfluxDbBatchWriter {
+    tx: mpsc::Sender<String>,
+}
+
+impl InfluxDbBatchWriter {
+    pub fn spawn(
+        influxdb_client: InfluxDbClient,
+        spool: InfluxDbSpool,
+        settings: InfluxDbBatchSettings,
+    ) -> (Self, JoinHandle<Result<(), anyhow::Error>>) {
+        let (tx, rx) = mpsc::channel(settings.max_points.get());
+        let task = tokio::spawn(batch_writer_loop(rx, influxdb_client, spool, settings));
+        (Self { tx }, task)
+    }
+
+    pub async fn write(&self, point: String) -> Result<(), anyhow::Error> {
+        self.tx
+            .send(point)
+            .await
+            .map_err(|_| anyhow::anyhow!("Influxdb batch writer is stopped"))
+    }
+}
+
+#[tracing::instrument(
+    name = "Influxdb batch writer loop",
+    skip(rx, influxdb_client, spool, settings)
+)]
+async fn batch_writer_loop(
+    mut rx: mpsc::Receiver<String>,
+    influxdb_client: InfluxDbClient,
+    spool: InfluxDbSpool,
+    settings: InfluxDbBatchSettings,
+) -> Result<(), anyhow::Error> {
+    let in_flight = Arc::new(Semaphore::new(settings.max_in_flight_requests.get()));
+    let mut batch = LineBatch::default();
+    let mut flush_interval = tokio::time::interval(settings.flush_interval());
+    flush_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            point = rx.recv() => match point {
+                Some(point) => {
+                    batch.push(point);
+                    if batch.is_full(settings.max_points.get(), settings.max_bytes) {
+                        flush(&mut batch, &influxdb_client, &spool, &in_flight).await;
+                    }
+                }
+                None => break,
+            },
+            _ = flush_interval.tick() => {
+                flush(&mut batch, &influxdb_client, &spool, &in_flight).await;
+            }
+        }
+    }
+
+    flush(&mut batch, &influxdb_client, &spool, &in_flight).await;
+
+    // Wait until every in-flight request has completed.
+    let _ = in_flight
+        .acquire_many(settings.max_in_flight_requests.get() as u32)
+        .await?;
+
+    Ok(())
+}
+
+async fn flush(
+    batch: &mut LineBatch,
+    influxdb_client: &InfluxDbClient,
+    spool: &InfluxDbSpool,
+    in_flight: &Arc<Semaphore>,
+) {
+    if batch.is_empty() {
+        return;
+    }
+
+    let lines = batch.take();
+    let permit = match in_flight.clone().acquire_owned().await {
+        Ok(permit) => permit,
+        Err(err) => {
+            error!("Influxdb batch writer is closed, points are lost = {err:?}");
+            return;
+        }
+    };
+    let influxdb_client = influxdb_client.clone();
+    let spool = spool.clone();
+
+    tokio::spawn(async move {
+        write_batch(&influxdb_client, &spool, lines).await;
+        drop(permit);
+    });
+}
+
+async fn write_batch(influxdb_client: &InfluxDbClient, spool: &InfluxDbSpool, lines: Vec<String>) {
+    // While older points are waiting in the spool, new ones are queued behind them
+    // instead of racing the replayer. Batches written by concurrent requests can still
+    // reach influxdb or the spool out of order, every point carries its own timestamp.
+    if spool.is_empty() {
+        let payload: Vec<&str> = lines.iter().map(String::as_str).collect();
+        match influxdb_client.write_lines(&payload).await {
+            Ok(_) => return,
+            Err(err) => warn!("Influxdb write failed, points go to the spool = {err:?}"),
+        }
+    }
+
+    if let Err(err) = spool.push_all(&lines).await {
+        error!(
+            "Error during points spooling, {} points are lost = {err:?}",
+            lines.len()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LineBatch;
+
+    #[test]
+    fn batch_size_accounts_for_line_separators() {
+        let mut batch = LineBatch::default();
+        batch.push("a b=1i".to_string());
+        batch.push("a b=2i".to_string());
+
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch.bytes(), "a b=1i\na b=2i".len());
+    }
+
+    #[test]
+    fn batch_is_full_when_point_count_is_reached() {
+        let mut batch = LineBatch::default();
+        batch.push("a b=1i".to_string());
+        assert!(!batch.is_full(2, 1024));
+
+        batch.push("a b=2i".to_string());
+        assert!(batch.is_full(2, 1024));
+    }
+
+    #[test]
+    fn batch_is_full_when_byte_size_is_reached() {
+        let mut batch = LineBatch::default();
+        batch.push("a b=1i".to_string());
+        assert!(batch.is_full(100, 6));
+    }
+
+    #[test]
+    fn taking_lines_resets_the_batch() {
+        let mut batch = LineBatch::default();
+        batch.push("a b=1i".to_string());
+
+        assert_eq!(batch.take(), vec!["a b=1i".to_string()]);
+        assert!(batch.is_empty());
+        assert_eq!(batch.bytes(), 0);
+    }
+}
@@ -1,7 +1,42 @@
-use log::{info, warn};
-use reqwest::Client;
+use crate::health::DependencyHealth;
+use crate::metrics::Metrics;
+use log::{error, info, warn};
+use reqwest::{Client, StatusCode};
 use secrecy::{ExposeSecret, Secret};
 
+/// Timestamp precision of the points sent to influxdb, points carry epoch milliseconds.
+pub const WRITE_PRECISION: &str = "ms";
+
+#[derive(thiserror::Error, Debug)]
+pub enum InfluxDbWriteError {
+    /// Influxdb refused the points themselves, like a field type conflict, so writing them
+    /// again fails the same way.
+    #[error("Influxdb rejected the points with status {status}: {message}")]
+    Rejected { status: StatusCode, message: String },
+    /// Server errors and network failures, the write can be retried later.
+    #[error(transparent)]
+    Unavailable(#[from] anyhow::Error),
+}
+
+impl InfluxDbWriteError {
+    /// Client errors caused by the points. Authentication errors, a missing bucket and
+    /// rate limiting depend on the server, not on the points, so they are retried.
+    fn from_status(status: StatusCode, message: String) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED
+            | StatusCode::FORBIDDEN
+            | StatusCode::NOT_FOUND
+            | StatusCode::TOO_MANY_REQUESTS => InfluxDbWriteError::Unavailable(anyhow::anyhow!(
+                "Influxdb write failed with status {status}: {message}"
+            )),
+            status if status.is_client_error() => InfluxDbWriteError::Rejected { status, message },
+            status => InfluxDbWriteError::Unavailable(anyhow::anyhow!(
+                "Influxdb write failed with status {status}: {message}"
+            )),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct InfluxDbClient {
     http_client: Client,
@@ -9,6 +44,8 @@ pub struct InfluxDbClient {
     bucket: String,
     organization: String,
     authorization_token: Secret<String>,
+    health: Option<DependencyHealth>,
+    metrics: Option<Metrics>,
 }
 
 impl InfluxDbClient {
@@ -26,18 +63,81 @@ impl InfluxDbClient {
             bucket,
             organization,
             authorization_token,
+            health: None,
+            metrics: None,
         }
     }
 
-    pub async fn write(&self, payload: &str) -> Result<(), anyhow::Error> {
+    /// Reports the outcome of every write to `health`.
+    pub fn with_health(mut self, health: DependencyHealth) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// Records the latency and the outcome of every write in `metrics`.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub async fn write(&self, payload: &str) -> Result<(), InfluxDbWriteError> {
+        let timer = self
+            .metrics
+            .as_ref()
+            .map(|metrics| metrics.influxdb_write_duration.start_timer());
+        let outcome = self.send(payload).await;
+        if let Some(timer) = timer {
+            timer.observe_duration();
+        }
+        if let Some(health) = &self.health {
+            health.record_influxdb_write(&outcome);
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.record_influxdb_write(&outcome);
+        }
+        outcome
+    }
+
+    /// Writes the lines and drops the ones influxdb rejects, so a bad point does not block
+    /// the points behind it. Only the failures worth a retry are returned.
+    pub async fn write_lines(&self, lines: &[&str]) -> Result<(), InfluxDbWriteError> {
+        // Influxdb does not tell which lines were rejected, so a rejected batch is split in
+        // halves until the bad lines are alone, a bad point costs about log2(n) requests.
+        // Rewriting an accepted point only overwrites it with itself.
+        let mut batches = vec![lines];
+        while let Some(batch) = batches.pop() {
+            match self.write(&batch.join("\n")).await {
+                Err(err @ InfluxDbWriteError::Rejected { .. }) => match batch {
+                    [] => {}
+                    [line] => self.drop_rejected(line, &err),
+                    batch => {
+                        let (first, second) = batch.split_at(batch.len() / 2);
+                        batches.push(second);
+                        batches.push(first);
+                    }
+                },
+                outcome => outcome?,
+            }
+        }
+        Ok(())
+    }
+
+    fn drop_rejected(&self, line: &str, err: &InfluxDbWriteError) {
+        error!("Influxdb rejected the point {line:?}, it is dropped = {err}");
+        if let Some(metrics) = &self.metrics {
+            metrics.influxdb_rejected_points.inc();
+        }
+    }
+
+    async fn send(&self, payload: &str) -> Result<(), InfluxDbWriteError> {
         let url = format!(
-            "{}/api/v2/write?org={}&bucket={}",
-            self.base_url, self.organization, self.bucket
+            "{}/api/v2/write?org={}&bucket={}&precision={}",
+            self.base_url, self.organization, self.bucket, WRITE_PRECISION
         );
 
         info!("data point to store in influxdb: {payload:?}");
 
-        match self
+        let response = self
             .http_client
             .post(&url)
             .header(
@@ -48,17 +148,47 @@ impl InfluxDbClient {
             .header("Accept", "application/json")
             .body(payload.to_string())
             .send()
-            .await?
-            .error_for_status()
-        {
-            Ok(content) => {
-                info!("data point successfully stored with response from server: {content:?}");
-                Ok(()) 
-            },
-            Err(err) => {
+            .await
+            .map_err(|err| {
                 warn!("error during point line data send = {err:?}");
-                anyhow::bail!("Error during point line data send = {err:?}")
-            }
+                anyhow::anyhow!("Error during point line data send = {err:?}")
+            })?;
+
+        let status = response.status();
+        if status.is_success() {
+            info!("data point successfully stored with response from server: {response:?}");
+            return Ok(());
+        }
+        let message = response.text().await.unwrap_or_default();
+        warn!("error during point line data send, status = {status}, response = {message}");
+        Err(InfluxDbWriteError::from_status(status, message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InfluxDbWriteError;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn invalid_points_are_rejected_for_good() {
+        for status in [StatusCode::BAD_REQUEST, StatusCode::UNPROCESSABLE_ENTITY] {
+            let err = InfluxDbWriteError::from_status(status, "field type conflict".into());
+            assert!(matches!(err, InfluxDbWriteError::Rejected { .. }));
+        }
+    }
+
+    #[test]
+    fn server_errors_and_configuration_errors_are_retried() {
+        for status in [
+            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::SERVICE_UNAVAILABLE,
+            StatusCode::UNAUTHORIZED,
+            StatusCode::NOT_FOUND,
+            StatusCode::TOO_MANY_REQUESTS,
+        ] {
+            let err = InfluxDbWriteError::from_status(status, String::new());
+            assert!(matches!(err, InfluxDbWriteError::Unavailable(_)));
         }
     }
 }
@@ -0,0 +1,165 @@
+use crate::metrics::Metrics;
+use chrono::Utc;
+use prometheus::IntGauge;
+use sqlx::PgPool;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// A line protocol payload waiting in the spool to be replayed.
+#[derive(Debug, Clone)]
+pub struct SpooledPoint {
+    pub id: i64,
+    pub payload: String,
+}
+
+/// Durable buffer for line protocol points that could not be written to influxdb.
+///
+/// Points are kept in the `influxdb_spool` table and replayed in insertion order.
+/// When the spool grows over `max_points`, the oldest points are dropped.
+#[derive(Clone)]
+pub struct InfluxDbSpool {
+    pool: PgPool,
+    max_points: i64,
+    depth: Arc<AtomicI64>,
+    depth_gauge: Option<IntGauge>,
+}
+
+impl InfluxDbSpool {
+    pub fn new(pool: PgPool, max_points: i64) -> Self {
+        Self {
+            pool,
+            max_points,
+            depth: Arc::new(AtomicI64::new(0)),
+            depth_gauge: None,
+        }
+    }
+
+    /// Exports the depth of the spool in `metrics`.
+    pub fn with_metrics(mut self, metrics: &Metrics) -> Self {
+        self.depth_gauge = Some(metrics.influxdb_spool_depth.clone());
+        self
+    }
+
+    /// Last known number of points waiting in the spool.
+    pub fn depth(&self) -> i64 {
+        self.depth.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.depth() == 0
+    }
+
+    /// Counts the points of the spool, the depth is then kept up to date by the pushes and
+    /// removals of this process.
+    #[tracing::instrument(name = "Refreshing influxdb spool depth", skip(self))]
+    pub async fn refresh_depth(&self) -> Result<i64, sqlx::Error> {
+        let depth = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM influxdb_spool"#)
+            .fetch_one(&self.pool)
+            .await?;
+        self.depth.store(depth, Ordering::Relaxed);
+        self.update_gauge(depth);
+        Ok(depth)
+    }
+
+    #[tracing::instrument(name = "Pushing point to influxdb spool", skip(self, payload))]
+    pub async fn push(&self, payload: &str) -> Result<(), sqlx::Error> {
+        self.push_all(&[payload.to_string()]).await
+    }
+
+    /// Stores `payloads` in their order with a single insert, the oldest points are only
+    /// dropped once the depth goes over `max_points`.
+    #[tracing::instrument(name = "Pushing points to influxdb spool", skip(self, payloads))]
+    pub async fn push_all(&self, payloads: &[String]) -> Result<(), sqlx::Error> {
+        if payloads.is_empty() {
+            return Ok(());
+        }
+
+        let mut transaction = self.pool.begin().await?;
+
+        let inserted = sqlx::query!(
+            r#"
+    INSERT INTO influxdb_spool (payload, created_at)
+    SELECT payload, $2 FROM UNNEST($1::text[]) WITH ORDINALITY AS points(payload, position)
+        ORDER BY position
+            "#,
+            payloads,
+            Utc::now()
+        )
+        .execute(&mut transaction)
+        .await?
+        .rows_affected() as i64;
+
+        let mut dropped = 0;
+        if self.depth() + inserted > self.max_points {
+            dropped = sqlx::query!(
+                r#"
+    DELETE FROM influxdb_spool
+        WHERE id IN (SELECT id FROM influxdb_spool ORDER BY id DESC OFFSET $1)
+                "#,
+                self.max_points
+            )
+            .execute(&mut transaction)
+            .await?
+            .rows_affected() as i64;
+        }
+
+        transaction.commit().await?;
+
+        let depth = self.add_to_depth(inserted - dropped);
+        if dropped > 0 {
+            warn!(
+                dropped,
+                max_points = self.max_points,
+                "influxdb spool is full, oldest points were dropped"
+            );
+        }
+        info!(
+            points = payloads.len(),
+            queue_depth = depth,
+            "points stored in influxdb spool"
+        );
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Reading oldest points from influxdb spool", skip(self))]
+    pub async fn oldest(&self, limit: i64) -> Result<Vec<SpooledPoint>, sqlx::Error> {
+        sqlx::query_as!(
+            SpooledPoint,
+            r#"
+    SELECT id, payload FROM influxdb_spool ORDER BY id ASC LIMIT $1
+            "#,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(name = "Removing replayed points from influxdb spool", skip(self, points))]
+    pub async fn remove(&self, points: &[SpooledPoint]) -> Result<(), sqlx::Error> {
+        let ids: Vec<i64> = points.iter().map(|p| p.id).collect();
+
+        let removed = sqlx::query!(r#"DELETE FROM influxdb_spool WHERE id = ANY($1)"#, &ids[..])
+            .execute(&self.pool)
+            .await?
+            .rows_affected() as i64;
+
+        let depth = self.add_to_depth(-removed);
+        info!(queue_depth = depth, "points replayed from influxdb spool");
+
+        Ok(())
+    }
+
+    fn add_to_depth(&self, points: i64) -> i64 {
+        let depth = self.depth.fetch_add(points, Ordering::Relaxed) + points;
+        self.update_gauge(depth);
+        depth
+    }
+
+    fn update_gauge(&self, depth: i64) {
+        if let Some(depth_gauge) = &self.depth_gauge {
+            depth_gauge.set(depth);
+        }
+    }
+}
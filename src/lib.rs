@@ -1,8 +1,20 @@
 pub mod application;
+pub mod authentication;
+pub mod cli;
 pub mod configuration;
 pub mod domain;
+pub mod health;
+pub mod influxdb_batch_writer;
 pub mod influxdb_client;
+pub mod influxdb_spool;
+pub mod line_protocol;
+pub mod metrics;
+pub mod mqtt;
+pub mod mqtt_client;
 pub mod routes;
+pub mod session_state;
+pub mod shutdown;
+pub mod supervisor;
 pub mod telemetry;
 pub mod utils;
 pub mod workers;
@@ -0,0 +1,228 @@
+use std::fmt::{Display, Formatter, Write};
+
+/// A typed field value of a line protocol point.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Float(f64),
+    Integer(i64),
+    String(String),
+    Boolean(bool),
+}
+
+impl From<f64> for FieldValue {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<f32> for FieldValue {
+    fn from(value: f32) -> Self {
+        Self::Float(widen_f32(value))
+    }
+}
+
+/// Widens a float through its shortest decimal representation, which keeps `21.1`
+/// from turning into `21.100000381469727`.
+pub fn widen_f32(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or_else(|_| f64::from(value))
+}
+
+impl From<u32> for FieldValue {
+    fn from(value: u32) -> Self {
+        Self::Integer(i64::from(value))
+    }
+}
+
+impl From<i64> for FieldValue {
+    fn from(value: i64) -> Self {
+        Self::Integer(value)
+    }
+}
+
+impl From<String> for FieldValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<bool> for FieldValue {
+    fn from(value: bool) -> Self {
+        Self::Boolean(value)
+    }
+}
+
+/// Builder of a single influxdb line protocol point.
+///
+/// Measurement, tag and field names and values are escaped on formatting. Tags with
+/// empty values and non-finite float fields are left out because influxdb rejects them.
+#[derive(Debug, Clone)]
+pub struct LinePoint {
+    measurement: String,
+    tags: Vec<(String, String)>,
+    fields: Vec<(String, FieldValue)>,
+    timestamp: Option<i64>,
+}
+
+impl LinePoint {
+    pub fn new(measurement: impl Into<String>) -> Self {
+        Self {
+            measurement: measurement.into(),
+            tags: Vec::new(),
+            fields: Vec::new(),
+            timestamp: None,
+        }
+    }
+
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let value = value.into();
+        if !value.is_empty() {
+            self.tags.push((key.into(), value));
+        }
+        self
+    }
+
+    pub fn has_tag(&self, key: &str) -> bool {
+        self.tags.iter().any(|(tag, _)| tag == key)
+    }
+
+    pub fn field(mut self, key: impl Into<String>, value: impl Into<FieldValue>) -> Self {
+        let value = value.into();
+        if !matches!(value, FieldValue::Float(v) if !v.is_finite()) {
+            self.fields.push((key.into(), value));
+        }
+        self
+    }
+
+    pub fn timestamp(mut self, timestamp: i64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+}
+
+impl Display for LinePoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write_escaped(f, &self.measurement, &[',', ' ', '\\'])?;
+
+        // Influxdb recommends tags sorted by key for the best write performance.
+        let mut tags: Vec<&(String, String)> = self.tags.iter().collect();
+        tags.sort_by(|a, b| a.0.cmp(&b.0));
+        for (key, value) in tags {
+            f.write_char(',')?;
+            write_escaped(f, key, &[',', '=', ' ', '\\'])?;
+            f.write_char('=')?;
+            write_escaped(f, value, &[',', '=', ' ', '\\'])?;
+        }
+
+        for (i, (key, value)) in self.fields.iter().enumerate() {
+            f.write_char(if i == 0 { ' ' } else { ',' })?;
+            write_escaped(f, key, &[',', '=', ' ', '\\'])?;
+            f.write_char('=')?;
+            match value {
+                FieldValue::Float(v) => write!(f, "{v}")?,
+                FieldValue::Integer(v) => write!(f, "{v}i")?,
+                FieldValue::Boolean(v) => write!(f, "{v}")?,
+                FieldValue::String(v) => {
+                    f.write_char('"')?;
+                    write_escaped(f, v, &['"', '\\'])?;
+                    f.write_char('"')?;
+                }
+            }
+        }
+
+        if let Some(timestamp) = self.timestamp {
+            write!(f, " {timestamp}")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn write_escaped(f: &mut Formatter<'_>, value: &str, special: &[char]) -> std::fmt::Result {
+    for c in value.chars() {
+        // New lines separate points and can't be escaped, they are replaced by spaces. A
+        // trailing backslash would escape the separator that follows, so it is escaped too.
+        let c = if c == '\n' || c == '\r' { ' ' } else { c };
+        if special.contains(&c) {
+            f.write_char('\\')?;
+        }
+        f.write_char(c)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LinePoint;
+
+    #[test]
+    fn measurement_tags_and_fields_are_escaped() {
+        let point = LinePoint::new("hive sensors,v2")
+            .tag("device name", "hive 1,a=b")
+            .field("weight=raw", 10u32);
+
+        assert_eq!(
+            point.to_string(),
+            r"hive\ sensors\,v2,device\ name=hive\ 1\,a\=b weight\=raw=10i"
+        );
+    }
+
+    #[test]
+    fn backslashes_do_not_escape_the_separators() {
+        let point = LinePoint::new(r"hive\")
+            .tag(r"device\", r"hive-1\")
+            .tag("topic_prefix", "apiary-7")
+            .field(r"weight\", 1u32);
+
+        assert_eq!(
+            point.to_string(),
+            r"hive\\,device\\=hive-1\\,topic_prefix=apiary-7 weight\\=1i"
+        );
+    }
+
+    #[test]
+    fn fields_are_typed() {
+        let point = LinePoint::new("m")
+            .field("weight", 1200u32)
+            .field("temperature", 21.1f32)
+            .field("charging", true)
+            .field("firmware", r#"v1 "beta" \ 2"#.to_string());
+
+        assert_eq!(
+            point.to_string(),
+            r#"m weight=1200i,temperature=21.1,charging=true,firmware="v1 \"beta\" \\ 2""#
+        );
+    }
+
+    #[test]
+    fn tags_are_sorted_and_empty_tags_are_skipped() {
+        let point = LinePoint::new("m")
+            .tag("topic_prefix", "apiary-7")
+            .tag("device_id", "")
+            .tag("device_name", "hive-1")
+            .field("weight", 1u32)
+            .timestamp(1_684_000_000_000);
+
+        assert_eq!(
+            point.to_string(),
+            "m,device_name=hive-1,topic_prefix=apiary-7 weight=1i 1684000000000"
+        );
+    }
+
+    #[test]
+    fn non_finite_floats_are_skipped() {
+        let point = LinePoint::new("m")
+            .field("humidity", f32::NAN)
+            .field("weight", 1u32);
+
+        assert_eq!(point.to_string(), "m weight=1i");
+    }
+
+    #[test]
+    fn new_lines_do_not_break_the_point() {
+        let point = LinePoint::new("m")
+            .tag("device_name", "hive\n1")
+            .field("weight", 1u32);
+
+        assert_eq!(point.to_string(), r"m,device_name=hive\ 1 weight=1i");
+    }
+}
@@ -1,20 +1,24 @@
 use beesbuddy_bumblebee::application::Application;
+use beesbuddy_bumblebee::cli;
 use beesbuddy_bumblebee::configuration::get_configuration;
+use beesbuddy_bumblebee::health::DependencyHealth;
+use beesbuddy_bumblebee::metrics::Metrics;
+use beesbuddy_bumblebee::supervisor::Supervisor;
 use beesbuddy_bumblebee::telemetry::{get_subscriber, init_subscriber};
 use beesbuddy_bumblebee::workers::{
-    run_mqtt_worker_until_stopped, run_subscription_worker_until_stopped,
+    run_mqtt_worker_until_stopped, run_subscription_worker_until_stopped, MQTT_WORKER,
+    SUBSCRIPTIONS_WORKER,
 };
-use beesbuddy_bumblebee::{application, utils};
-use rumqttc::tokio_rustls::rustls;
-use rumqttc::tokio_rustls::rustls::ClientConfig;
-use rumqttc::{AsyncClient, MqttOptions, Transport};
+use beesbuddy_bumblebee::{application, mqtt, shutdown};
 use std::fmt::Debug;
-use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error(transparent)]
     App(#[from] application::Error),
+    #[error(transparent)]
+    Command(#[from] anyhow::Error),
 }
 
 #[tokio::main]
@@ -22,57 +26,97 @@ async fn main() -> Result<(), Error> {
     let subscriber = get_subscriber("bumblebee".into(), "info".into(), std::io::stdout);
     init_subscriber(subscriber);
 
-    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    // Every (re)started mqtt worker subscribes its own receiver.
+    let (tx, _) = tokio::sync::broadcast::channel(1024);
 
     let configuration = get_configuration().expect("Failed to read configuration.");
-    let mut mqtt_options = MqttOptions::new(
-        format!("beesbuddy-bumblebee-{}", uuid::Uuid::new_v4()),
-        configuration.clone().mqtt.host,
-        configuration.clone().mqtt.port,
-    );
-    mqtt_options.set_keep_alive(Duration::from_secs(5));
-    mqtt_options.set_credentials(
-        configuration.clone().mqtt.username,
-        configuration.clone().mqtt.password,
-    );
-    mqtt_options.set_clean_session(true);
 
-    if configuration.clone().mqtt.port == 8883 {
-        let mut root_cert_store = rustls::RootCertStore::empty();
-        for cert in rustls_native_certs::load_native_certs().expect("could not load platform certs")
-        {
-            root_cert_store
-                .add(&rustls::Certificate(cert.0))
-                .expect("unable to add certs");
-        }
-
-        let client_config = ClientConfig::builder()
-            .with_safe_defaults()
-            .with_root_certificates(root_cert_store)
-            .with_no_client_auth();
-
-        mqtt_options.set_transport(Transport::tls_with_config(client_config.into()));
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().is_some() {
+        cli::run_command(&configuration, args).await?;
+        return Ok(());
     }
 
-    let application = Application::build(configuration.clone()).await?;
-    let application_task = tokio::spawn(application.run_until_stopped());
+    let shutdown = CancellationToken::new();
+    let shutdown_deadline = configuration.application.shutdown_deadline();
+    tokio::spawn(shutdown::cancel_on_signal(shutdown.clone()));
+
+    let supervisor = Supervisor::new(configuration.supervisor.clone());
+    let health = DependencyHealth::new();
+    let metrics = Metrics::new();
 
-    let (mqtt_client, mqtt_event_loop) = AsyncClient::new(mqtt_options, 20);
-    let mqtt_worker_task = tokio::spawn(run_mqtt_worker_until_stopped(
+    let application = Application::build(
         configuration.clone(),
-        rx,
-        mqtt_client,
-        mqtt_event_loop,
-    ));
+        health.clone(),
+        supervisor.clone(),
+        metrics.clone(),
+    )
+    .await?;
+    let application_task = shutdown::spawn_until_shutdown(
+        "Web application",
+        application.run_until_stopped(shutdown.clone()),
+        shutdown.clone(),
+    );
 
-    let subscriptions_worker_task =
-        tokio::spawn(run_subscription_worker_until_stopped(configuration, tx));
+    let mqtt_worker_task = supervisor.spawn(
+        MQTT_WORKER,
+        {
+            let configuration = configuration.clone();
+            let tx = tx.clone();
+            let health = health.clone();
+            let metrics = metrics.clone();
+            let shutdown = shutdown.clone();
+            move || {
+                let configuration = configuration.clone();
+                let rx = tx.subscribe();
+                let health = health.clone();
+                let metrics = metrics.clone();
+                let shutdown = shutdown.clone();
+                async move {
+                    let (mqtt_client, mqtt_event_loop) = mqtt::client(&configuration.mqtt)?;
+                    run_mqtt_worker_until_stopped(
+                        configuration,
+                        rx,
+                        mqtt_client,
+                        mqtt_event_loop,
+                        health,
+                        metrics,
+                        shutdown,
+                    )
+                    .await
+                }
+            }
+        },
+        shutdown.clone(),
+    );
 
-    tokio::select! {
-        o = application_task => utils::report_exit("Web application", o),
-        o = mqtt_worker_task =>  utils::report_exit("Metrics/mqtt delivery worker", o),
-        o = subscriptions_worker_task =>  utils::report_exit("Table/subscriptions change listener", o),
-    }
+    let subscriptions_worker_task = supervisor.spawn(
+        SUBSCRIPTIONS_WORKER,
+        {
+            let shutdown = shutdown.clone();
+            move || {
+                run_subscription_worker_until_stopped(
+                    configuration.clone(),
+                    tx.clone(),
+                    health.clone(),
+                    metrics.clone(),
+                    shutdown.clone(),
+                )
+            }
+        },
+        shutdown.clone(),
+    );
+
+    shutdown::wait_for_tasks(
+        vec![
+            application_task,
+            mqtt_worker_task,
+            subscriptions_worker_task,
+        ],
+        shutdown,
+        shutdown_deadline,
+    )
+    .await;
 
     Ok(())
 }
@@ -0,0 +1,182 @@
+use crate::influxdb_client::InfluxDbWriteError;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts,
+    Registry, TextEncoder,
+};
+
+/// Prometheus metrics of the ingestion pipeline and the web server, exposed on `/metrics`.
+///
+/// Every metric is registered in the registry of the instance, clones share the same
+/// metrics so the workers and the web server can be handed their own copy.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    pub mqtt_messages_received: IntCounterVec,
+    pub mqtt_decode_failures: IntCounterVec,
+    pub influxdb_write_duration: Histogram,
+    pub influxdb_writes: IntCounterVec,
+    pub influxdb_rejected_points: IntCounter,
+    pub influxdb_spool_depth: IntGauge,
+    pub active_subscriptions: IntGauge,
+    pub subscriptions_listener_reconnects: IntCounter,
+    pub http_request_duration: HistogramVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("bumblebee".into()), None)
+            .expect("Failed to create the metrics registry");
+
+        let mqtt_messages_received = IntCounterVec::new(
+            Opts::new(
+                "mqtt_messages_received_total",
+                "Messages received from the mqtt broker per subscription topic prefix.",
+            ),
+            &["topic_prefix"],
+        )
+        .unwrap();
+        let mqtt_decode_failures = IntCounterVec::new(
+            Opts::new(
+                "mqtt_decode_failures_total",
+                "Mqtt payloads per subscription topic prefix that could not be decoded.",
+            ),
+            &["topic_prefix"],
+        )
+        .unwrap();
+        let influxdb_write_duration = Histogram::with_opts(HistogramOpts::new(
+            "influxdb_write_duration_seconds",
+            "Latency of the writes to influxdb.",
+        ))
+        .unwrap();
+        let influxdb_writes = IntCounterVec::new(
+            Opts::new(
+                "influxdb_writes_total",
+                "Writes to influxdb by outcome, either success, rejected or failure.",
+            ),
+            &["outcome"],
+        )
+        .unwrap();
+        let influxdb_rejected_points = IntCounter::new(
+            "influxdb_rejected_points_total",
+            "Points influxdb rejected for good, they are dropped.",
+        )
+        .unwrap();
+        let influxdb_spool_depth = IntGauge::new(
+            "influxdb_spool_depth",
+            "Points waiting in the spool to be written to influxdb.",
+        )
+        .unwrap();
+        let active_subscriptions = IntGauge::new(
+            "mqtt_active_subscriptions",
+            "Topics the mqtt client is subscribed to.",
+        )
+        .unwrap();
+        let subscriptions_listener_reconnects = IntCounter::new(
+            "subscriptions_listener_reconnects_total",
+            "Reconnections of the subscriptions changes listener.",
+        )
+        .unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Duration of the http requests per route.",
+            ),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+
+        registry
+            .register(Box::new(mqtt_messages_received.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(mqtt_decode_failures.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(influxdb_write_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(influxdb_writes.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(influxdb_rejected_points.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(influxdb_spool_depth.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(active_subscriptions.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(subscriptions_listener_reconnects.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(http_request_duration.clone()))
+            .unwrap();
+
+        Self {
+            registry,
+            mqtt_messages_received,
+            mqtt_decode_failures,
+            influxdb_write_duration,
+            influxdb_writes,
+            influxdb_rejected_points,
+            influxdb_spool_depth,
+            active_subscriptions,
+            subscriptions_listener_reconnects,
+            http_request_duration,
+        }
+    }
+
+    pub fn record_influxdb_write(&self, outcome: &Result<(), InfluxDbWriteError>) {
+        let outcome = match outcome {
+            Ok(_) => "success",
+            Err(InfluxDbWriteError::Rejected { .. }) => "rejected",
+            Err(InfluxDbWriteError::Unavailable(_)) => "failure",
+        };
+        self.influxdb_writes.with_label_values(&[outcome]).inc();
+    }
+
+    /// All the metrics in the prometheus text format.
+    pub fn encode(&self) -> Result<String, anyhow::Error> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Metrics;
+
+    #[test]
+    fn metrics_are_encoded_in_the_text_format() {
+        let metrics = Metrics::new();
+        metrics
+            .mqtt_messages_received
+            .with_label_values(&["apiary-1"])
+            .inc();
+        metrics.record_influxdb_write(&Err(anyhow::anyhow!("timeout").into()));
+
+        let text = metrics.encode().unwrap();
+
+        assert!(
+            text.contains(r#"bumblebee_mqtt_messages_received_total{topic_prefix="apiary-1"} 1"#)
+        );
+        assert!(text.contains(r#"bumblebee_influxdb_writes_total{outcome="failure"} 1"#));
+        assert!(text.contains("# TYPE bumblebee_influxdb_write_duration_seconds histogram"));
+    }
+
+    #[test]
+    fn clones_share_the_metrics() {
+        let metrics = Metrics::new();
+        metrics.clone().active_subscriptions.set(3);
+
+        assert_eq!(metrics.active_subscriptions.get(), 3);
+    }
+}
@@ -0,0 +1,489 @@
+use crate::configuration::{MqttProtocolVersion, MqttSettings, MqttTlsSettings, MqttTransport};
+use crate::mqtt_client::{MqttClient, MqttEventLoop};
+use anyhow::Context;
+use rumqttc::tokio_rustls::rustls::{self, ClientConfig};
+use rumqttc::v5;
+use rumqttc::v5::mqttbytes::v5::ConnectProperties;
+use rumqttc::{AsyncClient, MqttOptions, QoS, Transport};
+use std::fs::File;
+use std::io::BufReader;
+use uuid::Uuid;
+
+/// Creates an mqtt client of the configured protocol version and its event loop, the
+/// connection is only opened once the event loop is polled.
+pub fn client(settings: &MqttSettings) -> Result<(MqttClient, MqttEventLoop), anyhow::Error> {
+    match settings.protocol_version {
+        MqttProtocolVersion::V4 => {
+            let (client, event_loop) =
+                AsyncClient::new(options(settings)?, settings.request_channel_capacity);
+            Ok((
+                MqttClient::V4(client),
+                MqttEventLoop::V4(Box::new(event_loop)),
+            ))
+        }
+        MqttProtocolVersion::V5 => {
+            let (client, event_loop) =
+                v5::AsyncClient::new(options_v5(settings)?, settings.request_channel_capacity);
+            Ok((
+                MqttClient::V5(client),
+                MqttEventLoop::V5(Box::new(event_loop)),
+            ))
+        }
+    }
+}
+
+/// Connection options of the MQTT 3.1.1 client.
+pub fn options(settings: &MqttSettings) -> Result<MqttOptions, anyhow::Error> {
+    validate(settings)?;
+
+    let mut options = MqttOptions::new(
+        client_id(settings),
+        broker_address(settings)?,
+        settings.port,
+    );
+    options
+        .set_keep_alive(settings.keep_alive())
+        .set_credentials(settings.username.clone(), settings.password.clone())
+        .set_clean_session(settings.clean_session)
+        .set_inflight(settings.inflight)
+        .set_transport(transport(settings)?);
+
+    Ok(options)
+}
+
+/// Connection options of the MQTT 5 client.
+pub fn options_v5(settings: &MqttSettings) -> Result<v5::MqttOptions, anyhow::Error> {
+    validate(settings)?;
+
+    let mut options = v5::MqttOptions::new(
+        client_id(settings),
+        broker_address(settings)?,
+        settings.port,
+    );
+    // Unlike 3.1.1, MQTT 5 drops the session on disconnect unless it is given an expiry.
+    if !settings.clean_session {
+        let mut properties = ConnectProperties::new();
+        properties.session_expiry_interval = Some(settings.session_expiry_interval_seconds);
+        options.set_connect_properties(properties);
+    }
+    options
+        .set_keep_alive(settings.keep_alive())
+        .set_credentials(settings.username.clone(), settings.password.clone())
+        .set_clean_start(settings.clean_session)
+        .set_receive_maximum(Some(settings.inflight))
+        .set_topic_alias_max((settings.topic_alias_max > 0).then_some(settings.topic_alias_max))
+        .set_transport(transport(settings)?);
+
+    Ok(options)
+}
+
+fn validate(settings: &MqttSettings) -> Result<(), anyhow::Error> {
+    // Checked here as the mqtt options panic on these values.
+    anyhow::ensure!(
+        settings.keep_alive_seconds >= 5,
+        "The mqtt keep alive has to be at least 5 seconds."
+    );
+    anyhow::ensure!(
+        settings.inflight > 0,
+        "The mqtt inflight size has to be positive."
+    );
+    // The broker only resumes the session of the same client id.
+    anyhow::ensure!(
+        settings.clean_session || settings.client_id.is_some(),
+        "A persistent mqtt session requires a stable client id."
+    );
+    anyhow::ensure!(
+        !settings.shared_subscriptions
+            || (!settings.shared_subscription_group.is_empty()
+                && !settings.shared_subscription_group.contains(['/', '+', '#'])),
+        "{:?} is not a valid shared subscription group.",
+        settings.shared_subscription_group
+    );
+    Ok(())
+}
+
+fn client_id(settings: &MqttSettings) -> String {
+    settings
+        .client_id
+        .clone()
+        .unwrap_or_else(|| format!("beesbuddy-bumblebee-{}", Uuid::new_v4()))
+}
+
+/// The websocket transports connect to the whole url, host and port are not used.
+fn broker_address(settings: &MqttSettings) -> Result<String, anyhow::Error> {
+    match settings.transport {
+        MqttTransport::Tcp | MqttTransport::Tls => Ok(settings.host.clone()),
+        MqttTransport::Ws | MqttTransport::Wss => settings
+            .websocket_url
+            .clone()
+            .context("A websocket url is required by the websocket transports."),
+    }
+}
+
+fn transport(settings: &MqttSettings) -> Result<Transport, anyhow::Error> {
+    let transport = match settings.transport {
+        MqttTransport::Tcp => Transport::tcp(),
+        MqttTransport::Tls => Transport::tls_with_config(tls_config(&settings.tls)?.into()),
+        MqttTransport::Ws => Transport::ws(),
+        MqttTransport::Wss => Transport::wss_with_config(tls_config(&settings.tls)?.into()),
+    };
+    Ok(transport)
+}
+
+/// QoS the topics are subscribed with.
+pub fn qos(settings: &MqttSettings) -> Result<QoS, anyhow::Error> {
+    rumqttc::qos(settings.qos)
+        .map_err(|_| anyhow::anyhow!("{} is not a valid mqtt QoS.", settings.qos))
+}
+
+/// Group the topics are subscribed in when shared subscriptions are enabled.
+pub fn share_group(settings: &MqttSettings) -> Option<String> {
+    settings
+        .shared_subscriptions
+        .then(|| settings.shared_subscription_group.clone())
+}
+
+/// Filter `topic` is subscribed with, the publishes received through a shared
+/// subscription keep their own topic.
+pub fn subscription_filter(share_group: Option<&str>, topic: &str) -> String {
+    match share_group {
+        Some(group) => format!("$share/{group}/{topic}"),
+        None => topic.to_string(),
+    }
+}
+
+fn tls_config(settings: &MqttTlsSettings) -> Result<ClientConfig, anyhow::Error> {
+    let mut root_cert_store = rustls::RootCertStore::empty();
+    match &settings.ca_file {
+        Some(ca_file) => {
+            for cert in read_certificates(ca_file)? {
+                root_cert_store
+                    .add(&cert)
+                    .with_context(|| format!("Invalid CA certificate in {ca_file}"))?;
+            }
+        }
+        None => {
+            for cert in rustls_native_certs::load_native_certs()
+                .context("Failed to load the platform certificates")?
+            {
+                root_cert_store
+                    .add(&rustls::Certificate(cert.0))
+                    .context("Invalid platform certificate")?;
+            }
+        }
+    }
+
+    let builder = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(root_cert_store);
+
+    match (&settings.client_certificate_file, &settings.client_key_file) {
+        (Some(certificate_file), Some(key_file)) => builder
+            .with_single_cert(
+                read_certificates(certificate_file)?,
+                read_private_key(key_file)?,
+            )
+            .context("Invalid mqtt client certificate or key"),
+        (None, None) => Ok(builder.with_no_client_auth()),
+        _ => {
+            anyhow::bail!("Both the client certificate and key files are required for mutual TLS.")
+        }
+    }
+}
+
+fn read_certificates(path: &str) -> Result<Vec<rustls::Certificate>, anyhow::Error> {
+    let file = File::open(path).with_context(|| format!("Failed to open {path}"))?;
+    let certificates = rustls_pemfile::certs(&mut BufReader::new(file))
+        .with_context(|| format!("Failed to read the certificates of {path}"))?;
+    anyhow::ensure!(!certificates.is_empty(), "No certificate found in {path}");
+
+    Ok(certificates.into_iter().map(rustls::Certificate).collect())
+}
+
+fn read_private_key(path: &str) -> Result<rustls::PrivateKey, anyhow::Error> {
+    let file = File::open(path).with_context(|| format!("Failed to open {path}"))?;
+    let mut reader = BufReader::new(file);
+
+    loop {
+        match rustls_pemfile::read_one(&mut reader)
+            .with_context(|| format!("Failed to read the private key of {path}"))?
+        {
+            Some(rustls_pemfile::Item::RSAKey(key))
+            | Some(rustls_pemfile::Item::PKCS8Key(key))
+            | Some(rustls_pemfile::Item::ECKey(key)) => return Ok(rustls::PrivateKey(key)),
+            Some(_) => {}
+            None => anyhow::bail!("No private key found in {path}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{options, options_v5, qos, share_group, subscription_filter};
+    use crate::configuration::{MqttProtocolVersion, MqttSettings, MqttTlsSettings, MqttTransport};
+    use claims::{assert_err, assert_ok};
+    use rumqttc::{QoS, Transport};
+    use std::time::Duration;
+
+    fn fixture(name: &str) -> Option<String> {
+        Some(format!(
+            "{}/tests/fixtures/mqtt/{name}",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+    }
+
+    fn settings() -> MqttSettings {
+        MqttSettings {
+            port: 1883,
+            host: "broker".into(),
+            username: "bumblebee".into(),
+            password: "secret".into(),
+            reconciliation_interval_seconds: 300,
+            client_id: None,
+            keep_alive_seconds: 30,
+            clean_session: true,
+            qos: 1,
+            inflight: 10,
+            request_channel_capacity: 20,
+            transport: MqttTransport::Tcp,
+            websocket_url: None,
+            tls: MqttTlsSettings::default(),
+            shared_subscriptions: false,
+            shared_subscription_group: "bumblebee".into(),
+            protocol_version: MqttProtocolVersion::V4,
+            topic_alias_max: 10,
+            session_expiry_interval_seconds: 3600,
+            device_name_topic_level: 1,
+            discovery_mode: false,
+        }
+    }
+
+    #[test]
+    fn options_are_taken_from_the_settings() {
+        let settings = MqttSettings {
+            client_id: Some("bumblebee-1".into()),
+            clean_session: false,
+            ..settings()
+        };
+
+        let options = options(&settings).unwrap();
+
+        assert_eq!(options.client_id(), "bumblebee-1");
+        assert_eq!(options.broker_address(), ("broker".to_string(), 1883));
+        assert_eq!(options.keep_alive(), Duration::from_secs(30));
+        assert!(!options.clean_session());
+        assert_eq!(options.inflight(), 10);
+        assert_eq!(
+            options.credentials(),
+            Some(("bumblebee".to_string(), "secret".to_string()))
+        );
+        assert!(matches!(options.transport(), Transport::Tcp));
+    }
+
+    #[test]
+    fn a_random_client_id_is_generated_when_not_set() {
+        let first = options(&settings()).unwrap();
+        let second = options(&settings()).unwrap();
+
+        assert!(first.client_id().starts_with("beesbuddy-bumblebee-"));
+        assert_ne!(first.client_id(), second.client_id());
+    }
+
+    #[test]
+    fn tls_transport_accepts_a_custom_ca_and_a_client_certificate() {
+        let settings = MqttSettings {
+            transport: MqttTransport::Tls,
+            tls: MqttTlsSettings {
+                ca_file: fixture("ca.pem"),
+                client_certificate_file: fixture("client.pem"),
+                client_key_file: fixture("client.key"),
+            },
+            ..settings()
+        };
+
+        let options = options(&settings).unwrap();
+
+        assert!(matches!(options.transport(), Transport::Tls(_)));
+    }
+
+    #[test]
+    fn client_certificate_without_a_key_is_rejected() {
+        let settings = MqttSettings {
+            transport: MqttTransport::Tls,
+            tls: MqttTlsSettings {
+                ca_file: fixture("ca.pem"),
+                client_certificate_file: fixture("client.pem"),
+                client_key_file: None,
+            },
+            ..settings()
+        };
+
+        assert_err!(options(&settings));
+    }
+
+    #[test]
+    fn missing_ca_file_is_rejected() {
+        let settings = MqttSettings {
+            transport: MqttTransport::Tls,
+            tls: MqttTlsSettings {
+                ca_file: fixture("missing.pem"),
+                ..MqttTlsSettings::default()
+            },
+            ..settings()
+        };
+
+        assert_err!(options(&settings));
+    }
+
+    #[test]
+    fn websocket_transport_connects_to_the_url() {
+        let settings = MqttSettings {
+            transport: MqttTransport::Ws,
+            websocket_url: Some("ws://broker:8080/mqtt".into()),
+            ..settings()
+        };
+
+        let options = options(&settings).unwrap();
+
+        assert_eq!(options.broker_address().0, "ws://broker:8080/mqtt");
+        assert!(matches!(options.transport(), Transport::Ws));
+    }
+
+    #[test]
+    fn websocket_transport_requires_a_url() {
+        let settings = MqttSettings {
+            transport: MqttTransport::Wss,
+            ..settings()
+        };
+
+        assert_err!(options(&settings));
+    }
+
+    #[test]
+    fn persistent_session_requires_a_client_id() {
+        let settings = MqttSettings {
+            clean_session: false,
+            ..settings()
+        };
+
+        assert_err!(options(&settings));
+    }
+
+    #[test]
+    fn keep_alive_below_five_seconds_is_rejected() {
+        let settings = MqttSettings {
+            keep_alive_seconds: 1,
+            ..settings()
+        };
+
+        assert_err!(options(&settings));
+    }
+
+    #[test]
+    fn qos_has_to_be_0_1_or_2() {
+        assert_eq!(qos(&settings()).unwrap(), QoS::AtLeastOnce);
+        assert_ok!(qos(&MqttSettings {
+            qos: 2,
+            ..settings()
+        }));
+        assert_err!(qos(&MqttSettings {
+            qos: 3,
+            ..settings()
+        }));
+    }
+
+    #[test]
+    fn shared_subscriptions_are_prefixed_with_the_group() {
+        let settings = MqttSettings {
+            shared_subscriptions: true,
+            ..settings()
+        };
+
+        let group = share_group(&settings);
+
+        assert_eq!(
+            subscription_filter(group.as_deref(), "apiary-1/hive-1"),
+            "$share/bumblebee/apiary-1/hive-1"
+        );
+    }
+
+    #[test]
+    fn topics_are_subscribed_as_is_without_shared_subscriptions() {
+        let group = share_group(&settings());
+
+        assert_eq!(group, None);
+        assert_eq!(
+            subscription_filter(group.as_deref(), "apiary-1/hive-1"),
+            "apiary-1/hive-1"
+        );
+    }
+
+    #[test]
+    fn shared_subscription_group_cannot_contain_separators_or_wildcards() {
+        for group in ["", "bumble/bee", "bumble+", "#"] {
+            let settings = MqttSettings {
+                shared_subscriptions: true,
+                shared_subscription_group: group.into(),
+                ..settings()
+            };
+
+            assert_err!(options(&settings));
+        }
+    }
+
+    #[test]
+    fn v5_options_are_taken_from_the_settings() {
+        let settings = MqttSettings {
+            protocol_version: MqttProtocolVersion::V5,
+            ..settings()
+        };
+
+        let options = options_v5(&settings).unwrap();
+
+        assert!(options.client_id().starts_with("beesbuddy-bumblebee-"));
+        assert_eq!(options.broker_address(), ("broker".to_string(), 1883));
+        assert_eq!(options.keep_alive(), Duration::from_secs(30));
+        assert!(options.clean_start());
+        assert_eq!(options.receive_maximum(), Some(10));
+        assert_eq!(options.topic_alias_max(), Some(10));
+        assert_eq!(
+            options
+                .connect_properties()
+                .and_then(|properties| properties.session_expiry_interval),
+            None
+        );
+    }
+
+    #[test]
+    fn v5_persistent_sessions_expire_after_the_configured_interval() {
+        let settings = MqttSettings {
+            protocol_version: MqttProtocolVersion::V5,
+            client_id: Some("bumblebee-1".into()),
+            clean_session: false,
+            topic_alias_max: 0,
+            ..settings()
+        };
+
+        let options = options_v5(&settings).unwrap();
+
+        assert!(!options.clean_start());
+        assert_eq!(options.topic_alias_max(), None);
+        assert_eq!(
+            options
+                .connect_properties()
+                .and_then(|properties| properties.session_expiry_interval),
+            Some(3600)
+        );
+    }
+
+    #[test]
+    fn v5_options_are_validated_like_3_1_1() {
+        let settings = MqttSettings {
+            protocol_version: MqttProtocolVersion::V5,
+            clean_session: false,
+            ..settings()
+        };
+
+        assert_err!(options_v5(&settings));
+    }
+}
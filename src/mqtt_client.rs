@@ -0,0 +1,242 @@
+use rumqttc::v5;
+use rumqttc::v5::mqttbytes::v5::SubscribeReasonCode;
+use rumqttc::{Outgoing, QoS, SubscribeReasonCode as SubscribeReturnCode};
+
+/// Mqtt client of either protocol version.
+#[derive(Clone)]
+pub enum MqttClient {
+    V4(rumqttc::AsyncClient),
+    V5(v5::AsyncClient),
+}
+
+/// Event loop of the client, the events of both protocol versions are turned into
+/// [`MqttEvent`]s so the worker does not depend on the version.
+pub enum MqttEventLoop {
+    V4(Box<rumqttc::EventLoop>),
+    V5(Box<v5::EventLoop>),
+}
+
+#[derive(Debug)]
+pub enum MqttEvent {
+    /// The broker acknowledged the connection.
+    Connected {
+        session_present: bool,
+    },
+    Message(MqttMessage),
+    /// A subscribe request was sent to the broker with the packet id `pkid`.
+    SubscribeSent {
+        pkid: u16,
+    },
+    /// The broker accepted every filter of a subscribe request.
+    Subscribed {
+        pkid: u16,
+    },
+    /// The broker refused some of the filters of a subscribe request.
+    SubscribeFailed {
+        pkid: u16,
+        reasons: Vec<String>,
+    },
+    /// The disconnect request was sent to the broker.
+    Disconnected,
+    Other,
+}
+
+/// Publish received from the broker, topic aliases are already resolved.
+#[derive(Debug, Clone)]
+pub struct MqttMessage {
+    pub topic: String,
+    pub payload: Vec<u8>,
+    /// User properties of MQTT 5 publishes, always empty with MQTT 3.1.1.
+    pub user_properties: Vec<(String, String)>,
+}
+
+impl MqttClient {
+    pub fn try_subscribe(&self, filter: String, qos: QoS) -> Result<(), anyhow::Error> {
+        match self {
+            MqttClient::V4(client) => client.try_subscribe(filter, qos)?,
+            MqttClient::V5(client) => client.try_subscribe(filter, v5_qos(qos))?,
+        }
+        Ok(())
+    }
+
+    pub fn try_unsubscribe(&self, filter: String) -> Result<(), anyhow::Error> {
+        match self {
+            MqttClient::V4(client) => client.try_unsubscribe(filter)?,
+            MqttClient::V5(client) => client.try_unsubscribe(filter)?,
+        }
+        Ok(())
+    }
+
+    pub fn try_disconnect(&self) -> Result<(), anyhow::Error> {
+        match self {
+            MqttClient::V4(client) => client.try_disconnect()?,
+            MqttClient::V5(client) => client.try_disconnect()?,
+        }
+        Ok(())
+    }
+}
+
+impl MqttEventLoop {
+    /// Polls the connection for the next event, reconnecting when it was lost.
+    pub async fn poll(&mut self) -> Result<MqttEvent, anyhow::Error> {
+        match self {
+            MqttEventLoop::V4(event_loop) => Ok(v4_event(event_loop.poll().await?)),
+            MqttEventLoop::V5(event_loop) => Ok(v5_event(event_loop.poll().await?)),
+        }
+    }
+}
+
+fn v5_qos(qos: QoS) -> v5::mqttbytes::QoS {
+    match qos {
+        QoS::AtMostOnce => v5::mqttbytes::QoS::AtMostOnce,
+        QoS::AtLeastOnce => v5::mqttbytes::QoS::AtLeastOnce,
+        QoS::ExactlyOnce => v5::mqttbytes::QoS::ExactlyOnce,
+    }
+}
+
+fn v4_event(event: rumqttc::Event) -> MqttEvent {
+    use rumqttc::{Event, Incoming};
+
+    match event {
+        Event::Incoming(Incoming::ConnAck(connack)) => MqttEvent::Connected {
+            session_present: connack.session_present,
+        },
+        Event::Incoming(Incoming::Publish(publish)) => MqttEvent::Message(MqttMessage {
+            topic: publish.topic,
+            payload: publish.payload.to_vec(),
+            user_properties: Vec::new(),
+        }),
+        Event::Incoming(Incoming::SubAck(suback)) => {
+            let failures = suback
+                .return_codes
+                .iter()
+                .filter(|code| matches!(code, SubscribeReturnCode::Failure))
+                .count();
+            match failures {
+                0 => MqttEvent::Subscribed { pkid: suback.pkid },
+                failures => MqttEvent::SubscribeFailed {
+                    pkid: suback.pkid,
+                    reasons: vec!["Failure".to_string(); failures],
+                },
+            }
+        }
+        Event::Outgoing(Outgoing::Subscribe(pkid)) => MqttEvent::SubscribeSent { pkid },
+        Event::Outgoing(Outgoing::Disconnect) => MqttEvent::Disconnected,
+        _ => MqttEvent::Other,
+    }
+}
+
+fn v5_event(event: v5::Event) -> MqttEvent {
+    use v5::{Event, Incoming};
+
+    match event {
+        Event::Incoming(Incoming::ConnAck(connack)) => MqttEvent::Connected {
+            session_present: connack.session_present,
+        },
+        Event::Incoming(Incoming::Publish(publish)) => MqttEvent::Message(MqttMessage {
+            topic: String::from_utf8_lossy(&publish.topic).into_owned(),
+            payload: publish.payload.to_vec(),
+            user_properties: publish
+                .properties
+                .map(|properties| properties.user_properties)
+                .unwrap_or_default(),
+        }),
+        Event::Incoming(Incoming::SubAck(suback)) => {
+            let reasons: Vec<String> = suback
+                .return_codes
+                .iter()
+                .filter(|code| !matches!(code, SubscribeReasonCode::Success(_)))
+                .map(|code| format!("{code:?}"))
+                .collect();
+            match reasons.is_empty() {
+                true => MqttEvent::Subscribed { pkid: suback.pkid },
+                false => MqttEvent::SubscribeFailed {
+                    pkid: suback.pkid,
+                    reasons,
+                },
+            }
+        }
+        Event::Outgoing(Outgoing::Subscribe(pkid)) => MqttEvent::SubscribeSent { pkid },
+        Event::Outgoing(Outgoing::Disconnect) => MqttEvent::Disconnected,
+        _ => MqttEvent::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{v4_event, v5_event, MqttEvent};
+    use rumqttc::v5;
+    use rumqttc::v5::mqttbytes::v5::{PublishProperties, SubscribeReasonCode};
+    use rumqttc::{Outgoing, QoS, SubAck, SubscribeReasonCode as SubscribeReturnCode};
+
+    #[test]
+    fn v5_user_properties_are_kept_on_messages() {
+        let mut publish = v5::mqttbytes::v5::Publish::new(
+            "apiary-1/hive-1",
+            v5::mqttbytes::QoS::AtLeastOnce,
+            "{}",
+            None,
+        );
+        publish.properties = Some(PublishProperties {
+            user_properties: vec![("firmware_version".into(), "1.2.0".into())],
+            ..PublishProperties::default()
+        });
+
+        let event = v5_event(v5::Event::Incoming(v5::Incoming::Publish(publish)));
+
+        match event {
+            MqttEvent::Message(message) => {
+                assert_eq!(message.topic, "apiary-1/hive-1");
+                assert_eq!(
+                    message.user_properties,
+                    vec![("firmware_version".to_string(), "1.2.0".to_string())]
+                );
+            }
+            event => panic!("unexpected event {event:?}"),
+        }
+    }
+
+    #[test]
+    fn v5_subscribe_failures_carry_the_reason_codes() {
+        let suback = v5::mqttbytes::v5::SubAck {
+            pkid: 1,
+            return_codes: vec![
+                SubscribeReasonCode::Success(v5::mqttbytes::QoS::AtLeastOnce),
+                SubscribeReasonCode::NotAuthorized,
+            ],
+            properties: None,
+        };
+
+        let event = v5_event(v5::Event::Incoming(v5::Incoming::SubAck(suback)));
+
+        assert!(
+            matches!(event, MqttEvent::SubscribeFailed { pkid: 1, reasons } if reasons == vec!["NotAuthorized"])
+        );
+    }
+
+    #[test]
+    fn v4_subscribe_failures_are_reported() {
+        let suback = SubAck::new(
+            1,
+            vec![
+                SubscribeReturnCode::Success(QoS::AtLeastOnce),
+                SubscribeReturnCode::Failure,
+            ],
+        );
+
+        let event = v4_event(rumqttc::Event::Incoming(rumqttc::Incoming::SubAck(suback)));
+
+        assert!(
+            matches!(event, MqttEvent::SubscribeFailed { pkid: 1, reasons } if reasons.len() == 1)
+        );
+    }
+
+    #[test]
+    fn sent_subscribe_requests_carry_their_packet_id() {
+        let event = v4_event(rumqttc::Event::Outgoing(Outgoing::Subscribe(3)));
+        assert!(matches!(event, MqttEvent::SubscribeSent { pkid: 3 }));
+
+        let event = v5_event(v5::Event::Outgoing(Outgoing::Subscribe(4)));
+        assert!(matches!(event, MqttEvent::SubscribeSent { pkid: 4 }));
+    }
+}
@@ -0,0 +1,381 @@
+use super::registry::select_device_organization;
+use crate::authentication::UserAccess;
+use crate::domain::{Calibration, Id, NewCalibration};
+use crate::utils::{error_chain_fmt, escape_html, see_other};
+use actix_web::http::StatusCode;
+use actix_web::{http::header::ContentType, web, HttpResponse, ResponseError};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::convert::{TryFrom, TryInto};
+use std::fmt::Write;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    device_id: String,
+    tare_offset: String,
+    scale_factor: String,
+    temperature_coefficient: String,
+    reference_temperature: String,
+    valid_from: String,
+}
+
+impl TryFrom<FormData> for NewCalibration {
+    type Error = String;
+
+    fn try_from(value: FormData) -> Result<Self, Self::Error> {
+        let device_id = Id::parse(value.device_id)?;
+        let tare_offset = parse_number(&value.tare_offset, "tare offset")?;
+        let scale_factor = parse_number(&value.scale_factor, "scale factor")?;
+        let temperature_coefficient =
+            parse_number(&value.temperature_coefficient, "temperature coefficient")?;
+        let reference_temperature =
+            parse_number(&value.reference_temperature, "reference temperature")?;
+        let valid_from = match value.valid_from.trim() {
+            "" => Utc::now(),
+            valid_from => DateTime::parse_from_rfc3339(valid_from)
+                .map_err(|_| format!("{} is not a valid RFC3339 date.", valid_from))?
+                .with_timezone(&Utc),
+        };
+
+        Ok(Self {
+            device_id,
+            tare_offset,
+            scale_factor,
+            temperature_coefficient,
+            reference_temperature,
+            valid_from,
+        })
+    }
+}
+
+fn parse_number(s: &str, name: &str) -> Result<f64, String> {
+    s.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| format!("{} is not a valid {}.", s, name))
+}
+
+#[derive(serde::Deserialize)]
+pub struct EditQuery {
+    device_id: Option<Uuid>,
+}
+
+#[derive(thiserror::Error)]
+pub enum CalibrationError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("You are not allowed to calibrate the hive {0}.")]
+    Forbidden(Uuid),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for CalibrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for CalibrationError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            CalibrationError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            CalibrationError::Forbidden(_) => StatusCode::FORBIDDEN,
+            CalibrationError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+pub async fn get_view_admin_calibrations(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+    access: UserAccess,
+) -> Result<HttpResponse, CalibrationError> {
+    let calibrations = select_calibrations(&pool, &access)
+        .await
+        .context("Failed to retrieve device calibrations.")?;
+
+    let mut msg_html = String::new();
+
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", escape_html(m.content())).unwrap();
+    }
+
+    let mut calibrations_html = String::new();
+
+    for calibration in calibrations {
+        writeln!(
+            calibrations_html,
+            r#"<p><i>hive {} version {} valid from {}: tare offset {}, scale factor {}, temperature coefficient {} at {} °C</i> <a href="/admin/calibrations/edit?device_id={}">edit</a></p>"#,
+            calibration.device_id,
+            calibration.version,
+            calibration.valid_from,
+            calibration.tare_offset,
+            calibration.scale_factor,
+            calibration.temperature_coefficient,
+            calibration.reference_temperature,
+            calibration.device_id
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>View calibrations</title>
+</head>
+<body>
+    {msg_html}
+    <a href="/admin/calibrations/edit">Calibrate a hive</a>
+    <p>Calibration history:</p>
+    {calibrations_html}
+</body>
+</html>"#
+        )))
+}
+
+pub async fn get_edit_admin_calibration(
+    query: web::Query<EditQuery>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+    access: UserAccess,
+) -> Result<HttpResponse, CalibrationError> {
+    let current = match query.device_id {
+        Some(device_id) => {
+            if !can_calibrate(&pool, &access, device_id).await? {
+                return Err(CalibrationError::Forbidden(device_id));
+            }
+            select_current_calibration(&pool, device_id, Utc::now())
+                .await
+                .context("Failed to retrieve the current device calibration.")?
+        }
+        None => None,
+    };
+
+    let mut msg_html = String::new();
+
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", escape_html(m.content())).unwrap();
+    }
+
+    let device_id = query.device_id.map(|id| id.to_string()).unwrap_or_default();
+    let (tare_offset, scale_factor, temperature_coefficient, reference_temperature) = match &current
+    {
+        Some(c) => (
+            c.tare_offset,
+            c.scale_factor,
+            c.temperature_coefficient,
+            c.reference_temperature,
+        ),
+        None => (0.0, 1.0, 0.0, 20.0),
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Calibrate a hive</title>
+</head>
+<body>
+    {msg_html}
+    <p>Save a new calibration version</p>
+    <form action="/admin/calibrations/edit" method="post">
+        <div style="margin-bottom: 5px">
+             <label>Hive id:<br>
+                <input
+                    type="text"
+                    placeholder="Enter hive id"
+                    name="device_id"
+                    value="{device_id}"
+                >
+            </label>
+        </div>
+        <div style="margin-bottom: 5px">
+             <label>Tare offset:<br>
+                <input type="text" name="tare_offset" value="{tare_offset}">
+            </label>
+        </div>
+        <div style="margin-bottom: 5px">
+             <label>Scale factor (kg per raw unit):<br>
+                <input type="text" name="scale_factor" value="{scale_factor}">
+            </label>
+        </div>
+        <div style="margin-bottom: 5px">
+             <label>Temperature coefficient (kg per °C):<br>
+                <input type="text" name="temperature_coefficient" value="{temperature_coefficient}">
+            </label>
+        </div>
+        <div style="margin-bottom: 5px">
+             <label>Reference temperature (°C):<br>
+                <input type="text" name="reference_temperature" value="{reference_temperature}">
+            </label>
+        </div>
+        <div style="margin-bottom: 5px">
+             <label>Valid from (RFC3339, empty for now):<br>
+                <input type="text" placeholder="2023-06-01T00:00:00Z" name="valid_from">
+            </label>
+        </div>
+        <div>
+            <button type="submit">Save</button>
+        </div>
+    </form>
+</body>
+</html>"#,
+        )))
+}
+
+#[tracing::instrument(
+    name = "Adding a new calibration version",
+    skip(form, pool, access),
+    fields(device_id = %form.device_id)
+)]
+pub async fn post_edit_admin_calibration(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    access: UserAccess,
+) -> Result<HttpResponse, CalibrationError> {
+    let new_calibration: NewCalibration = form
+        .0
+        .try_into()
+        .map_err(CalibrationError::ValidationError)?;
+    let device_id = *new_calibration.device_id.as_ref();
+    if !can_calibrate(&pool, &access, device_id).await? {
+        return Err(CalibrationError::Forbidden(device_id));
+    }
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let version = insert_calibration(&mut transaction, &new_calibration)
+        .await
+        .context("Failed to insert new calibration in the database.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a new calibration.")?;
+
+    FlashMessage::info(format!("Calibration version {version} has been saved.")).send();
+    Ok(see_other("/admin/calibrations/view"))
+}
+
+/// Only superadmins can calibrate devices that are not registered.
+async fn can_calibrate(
+    pool: &PgPool,
+    access: &UserAccess,
+    device_id: Uuid,
+) -> Result<bool, CalibrationError> {
+    if access.is_superadmin() {
+        return Ok(true);
+    }
+    let organization_id = select_device_organization(pool, device_id)
+        .await
+        .context("Failed to retrieve the apiary of the device.")?;
+    Ok(organization_id.is_some_and(|id| access.can_edit(id)))
+}
+
+/// Calibrations of the devices registered in the organizations the user can view.
+#[tracing::instrument(name = "Select all calibrations from the database", skip(pool))]
+pub async fn select_calibrations(
+    pool: &PgPool,
+    access: &UserAccess,
+) -> Result<Vec<Calibration>, sqlx::Error> {
+    sqlx::query_as!(
+        Calibration,
+        r#"
+    SELECT device_id, version, tare_offset, scale_factor, temperature_coefficient, reference_temperature, valid_from
+        FROM device_calibrations
+        WHERE $1 OR device_id IN (SELECT id FROM devices WHERE organization_id = ANY($2))
+        ORDER BY device_id, version DESC
+    "#,
+        access.is_superadmin(),
+        &access.organization_ids()
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// The calibration of a device with the highest version that is valid at `at`.
+#[tracing::instrument(name = "Select the current calibration of a device", skip(pool))]
+pub async fn select_current_calibration(
+    pool: &PgPool,
+    device_id: Uuid,
+    at: DateTime<Utc>,
+) -> Result<Option<Calibration>, sqlx::Error> {
+    sqlx::query_as!(
+        Calibration,
+        r#"
+    SELECT device_id, version, tare_offset, scale_factor, temperature_coefficient, reference_temperature, valid_from
+        FROM device_calibrations
+        WHERE device_id = $1 AND valid_from <= $2
+        ORDER BY version DESC
+        LIMIT 1
+    "#,
+        device_id,
+        at
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+#[tracing::instrument(
+    name = "Insert a new calibration version in the database",
+    skip(new_calibration, transaction)
+)]
+pub async fn insert_calibration(
+    transaction: &mut Transaction<'_, Postgres>,
+    new_calibration: &NewCalibration,
+) -> Result<i32, sqlx::Error> {
+    // Concurrent calibrations of a device would read the same latest version, the lock is
+    // held until the transaction ends. The device may have no row to lock with FOR UPDATE.
+    sqlx::query!(
+        r#"
+    SELECT pg_advisory_xact_lock(hashtext($1::uuid::text)) IS NULL AS "locked"
+        "#,
+        new_calibration.device_id.as_ref()
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+
+    let version = sqlx::query_scalar!(
+        r#"
+    SELECT COALESCE(MAX(version), 0) + 1 AS "version!" FROM device_calibrations WHERE device_id = $1
+        "#,
+        new_calibration.device_id.as_ref()
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+
+    sqlx::query!(
+        r#"
+    INSERT INTO device_calibrations (id, device_id, version, tare_offset, scale_factor, temperature_coefficient, reference_temperature, valid_from, created_at)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+        Uuid::new_v4(),
+        new_calibration.device_id.as_ref(),
+        version,
+        new_calibration.tare_offset,
+        new_calibration.scale_factor,
+        new_calibration.temperature_coefficient,
+        new_calibration.reference_temperature,
+        new_calibration.valid_from,
+        Utc::now()
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    Ok(version)
+}
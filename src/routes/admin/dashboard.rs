@@ -13,7 +13,17 @@ pub async fn get_admin_dashboard() -> Result<HttpResponse, actix_web::Error> {
     <p>Welcome!</p>
     <p>Available actions:</p>
     <ol>
+        <li><a href="/admin/organizations/view">View apiaries</a></li>
+        <li><a href="/admin/devices/view">View hives</a></li>
         <li><a href="/admin/subscriptions/topics/view">View topics</a></li>
+        <li><a href="/admin/quarantine/readings/view">View quarantined readings</a></li>
+        <li><a href="/admin/calibrations/view">View calibrations</a></li>
+        <li><a href="/admin/devices/pending/view">View pending devices</a></li>
+        <li>
+            <form name="logoutForm" action="/admin/logout" method="post">
+                <input type="submit" value="Logout">
+            </form>
+        </li>
     </ol>
 </body>
 </html>"#
@@ -0,0 +1,266 @@
+use super::registry::{insert_device, organization_exists};
+use super::subscriptions::insert_subscriber_topic;
+use crate::authentication::UserAccess;
+use crate::domain::{
+    DeviceNamePattern, NewDevice, NewSubscriberTopic, PayloadFormat, RegistryName, TopicPrefix,
+    ViewPendingDevice,
+};
+use crate::utils::{error_chain_fmt, escape_html, see_other};
+use actix_web::http::StatusCode;
+use actix_web::{http::header::ContentType, web, HttpResponse, ResponseError};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::fmt::Write;
+use uuid::Uuid;
+
+#[derive(thiserror::Error)]
+pub enum PendingDeviceError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("No pending device with id {0}.")]
+    NotFound(Uuid),
+    #[error("You are not allowed to manage the devices of the apiary {0}.")]
+    Forbidden(Uuid),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for PendingDeviceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for PendingDeviceError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            PendingDeviceError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            PendingDeviceError::NotFound(_) => StatusCode::NOT_FOUND,
+            PendingDeviceError::Forbidden(_) => StatusCode::FORBIDDEN,
+            PendingDeviceError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+pub async fn get_view_admin_pending_devices(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+    access: UserAccess,
+) -> Result<HttpResponse, PendingDeviceError> {
+    let devices = select_pending_devices(&pool, &access)
+        .await
+        .context("Failed to retrieve the pending devices.")?;
+
+    let mut msg_html = String::new();
+
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", escape_html(m.content())).unwrap();
+    }
+
+    let mut devices_html = String::new();
+
+    for device in devices {
+        let actions = match device.status.as_str() {
+            "pending" => format!(
+                r#"<form action="/admin/devices/pending/{0}/approve" method="post" style="display: inline"><button type="submit">Approve</button></form>
+<form action="/admin/devices/pending/{0}/reject" method="post" style="display: inline"><button type="submit">Reject</button></form>"#,
+                device.id
+            ),
+            status => status.to_string(),
+        };
+        writeln!(
+            devices_html,
+            "<p><i>topic: {}/{}, for apiary {}, {} messages from {} to {}</i> {}</p>",
+            escape_html(&device.topic_prefix),
+            escape_html(&device.device_name),
+            device.organization_id,
+            device.messages,
+            device.first_seen_at,
+            device.last_seen_at,
+            actions
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Pending devices</title>
+</head>
+<body>
+    {msg_html}
+    <p>Discovered devices:</p>
+    {devices_html}
+</body>
+</html>"#
+        )))
+}
+
+/// Registers a pending device as a new hive of its apiary and subscribes it with the json
+/// payload format.
+#[tracing::instrument(name = "Approving a pending device", skip(pool, access))]
+pub async fn post_approve_admin_pending_device(
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    access: UserAccess,
+) -> Result<HttpResponse, PendingDeviceError> {
+    let id = path.into_inner();
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let device = select_editable_pending_device(&mut transaction, id, &access).await?;
+    if !organization_exists(&mut transaction, device.organization_id)
+        .await
+        .context("Failed to look up the apiary of the pending device.")?
+    {
+        return Err(PendingDeviceError::ValidationError(format!(
+            "The apiary {} is not registered.",
+            device.organization_id
+        )));
+    }
+
+    let new_device = NewDevice {
+        organization_id: device.organization_id.into(),
+        name: RegistryName::parse(device.device_name.clone())
+            .map_err(PendingDeviceError::ValidationError)?,
+        location: None,
+        description: Some(format!(
+            "Discovered on the topic {}/{}",
+            device.topic_prefix, device.device_name
+        )),
+    };
+    let device_id = insert_device(&mut transaction, &new_device)
+        .await
+        .context("Failed to register the pending device.")?;
+    let new_subscriber = NewSubscriberTopic {
+        organization_id: device.organization_id.into(),
+        device_id: Some(device_id.into()),
+        device_name: DeviceNamePattern::parse(device.device_name.clone())
+            .map_err(PendingDeviceError::ValidationError)?,
+        topic_prefix: TopicPrefix::parse(device.topic_prefix.clone())
+            .map_err(PendingDeviceError::ValidationError)?,
+        payload_format: PayloadFormat::Json,
+    };
+    insert_subscriber_topic(&mut transaction, &new_subscriber)
+        .await
+        .context("Failed to insert the subscriber of the pending device.")?;
+    delete_pending_device(&mut transaction, id)
+        .await
+        .context("Failed to delete the approved pending device.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to approve a pending device.")?;
+
+    FlashMessage::info(format!(
+        "Device {}/{} has been added to subscribe list with hive id {}.",
+        device.topic_prefix, device.device_name, device_id
+    ))
+    .send();
+    Ok(see_other("/admin/devices/pending/view"))
+}
+
+/// Rejected devices are kept so their publishes are not recorded as pending again.
+#[tracing::instrument(name = "Rejecting a pending device", skip(pool, access))]
+pub async fn post_reject_admin_pending_device(
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    access: UserAccess,
+) -> Result<HttpResponse, PendingDeviceError> {
+    let id = path.into_inner();
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    select_editable_pending_device(&mut transaction, id, &access).await?;
+    sqlx::query!(
+        "UPDATE pending_devices SET status = 'rejected' WHERE id = $1",
+        id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to reject the pending device.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to reject a pending device.")?;
+
+    FlashMessage::info("The device has been rejected.").send();
+    Ok(see_other("/admin/devices/pending/view"))
+}
+
+/// Pending devices of the organizations the user can view.
+#[tracing::instrument(name = "Select all pending devices from the database", skip(pool))]
+pub async fn select_pending_devices(
+    pool: &PgPool,
+    access: &UserAccess,
+) -> Result<Vec<ViewPendingDevice>, sqlx::Error> {
+    sqlx::query_as!(
+        ViewPendingDevice,
+        r#"
+    SELECT id, organization_id, topic_prefix, device_name, status, messages, first_seen_at, last_seen_at
+        FROM pending_devices
+        WHERE $1 OR organization_id = ANY($2)
+        ORDER BY status, last_seen_at DESC
+    "#,
+        access.is_superadmin(),
+        &access.organization_ids()
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Locks a device that is still pending, devices of other organizations are reported as
+/// missing so their ids are not disclosed.
+async fn select_editable_pending_device(
+    transaction: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+    access: &UserAccess,
+) -> Result<ViewPendingDevice, PendingDeviceError> {
+    let device = select_pending_device_for_update(transaction, id)
+        .await
+        .context("Failed to retrieve the pending device.")?
+        .filter(|device| device.status == "pending" && access.can_view(device.organization_id))
+        .ok_or(PendingDeviceError::NotFound(id))?;
+    if !access.can_edit(device.organization_id) {
+        return Err(PendingDeviceError::Forbidden(device.organization_id));
+    }
+    Ok(device)
+}
+
+#[tracing::instrument(name = "Select a pending device for update", skip(transaction))]
+async fn select_pending_device_for_update(
+    transaction: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+) -> Result<Option<ViewPendingDevice>, sqlx::Error> {
+    sqlx::query_as!(
+        ViewPendingDevice,
+        r#"
+    SELECT id, organization_id, topic_prefix, device_name, status, messages, first_seen_at, last_seen_at
+        FROM pending_devices
+        WHERE id = $1
+        FOR UPDATE
+    "#,
+        id
+    )
+    .fetch_optional(transaction)
+    .await
+}
+
+#[tracing::instrument(name = "Delete a pending device", skip(transaction))]
+async fn delete_pending_device(
+    transaction: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM pending_devices WHERE id = $1", id)
+        .execute(transaction)
+        .await?;
+    Ok(())
+}
@@ -1,7 +1,34 @@
+mod calibrations;
 mod dashboard;
+mod devices;
+mod logout;
+mod quarantine;
+mod registry;
 mod subscriptions;
 
+pub use calibrations::{
+    get_edit_admin_calibration, get_view_admin_calibrations, post_edit_admin_calibration,
+};
 pub use dashboard::get_admin_dashboard;
+pub use devices::{
+    get_view_admin_pending_devices, post_approve_admin_pending_device,
+    post_reject_admin_pending_device,
+};
+pub use logout::log_out;
+pub use quarantine::get_view_admin_quarantined_readings;
+pub use registry::{
+    get_view_admin_devices, get_view_admin_organizations, post_create_admin_device,
+    post_create_admin_organization,
+};
 pub use subscriptions::get_view_admin_subscriptions_topics;
 pub use subscriptions::post_create_admin_subscriptions_topics;
 pub use subscriptions::get_create_admin_subscriptions_topics;
+pub use subscriptions::{
+    get_delete_admin_subscriptions_topic, get_edit_admin_subscriptions_topic,
+    post_delete_admin_subscriptions_topic, post_edit_admin_subscriptions_topic,
+};
+pub use subscriptions::{
+    authorize_subscriber, delete_subscriber_topic, insert_subscriber_topic,
+    select_editable_subscriber_topic, select_visible_subscriber_topic, update_subscriber_topic,
+    TopicSubscribeError,
+};
@@ -0,0 +1,71 @@
+use crate::authentication::UserAccess;
+use crate::domain::ViewQuarantinedReading;
+use crate::utils::{e500, escape_html};
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+/// Quarantined readings are not attributed to an organization, so only superadmins see them.
+pub async fn get_view_admin_quarantined_readings(
+    pool: web::Data<PgPool>,
+    access: UserAccess,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !access.is_superadmin() {
+        return Err(actix_web::error::ErrorForbidden(
+            "Only superadmins can view the quarantined readings.",
+        ));
+    }
+    let readings = select_quarantined_readings(&pool, 100)
+        .await
+        .map_err(e500)?;
+
+    let mut readings_html = String::new();
+
+    for reading in readings {
+        writeln!(
+            readings_html,
+            "<p><i>{} from topic {} (device {}): {}</i><br><code>{}</code></p>",
+            reading.received_at,
+            escape_html(&reading.topic),
+            escape_html(reading.device_name.as_deref().unwrap_or("unknown")),
+            escape_html(&reading.reason),
+            escape_html(&reading.line_point)
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Quarantined readings</title>
+</head>
+<body>
+    <p>Quarantined readings:</p>
+    {readings_html}
+</body>
+</html>"#
+        )))
+}
+
+#[tracing::instrument(name = "Select latest quarantined readings from the database", skip(pool))]
+pub async fn select_quarantined_readings(
+    pool: &PgPool,
+    limit: i64,
+) -> Result<Vec<ViewQuarantinedReading>, sqlx::Error> {
+    sqlx::query_as!(
+        ViewQuarantinedReading,
+        r#"
+    SELECT topic, device_name, line_point, reason, received_at FROM quarantined_readings
+        ORDER BY created_at DESC
+        LIMIT $1
+    "#,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,455 @@
+use crate::authentication::UserAccess;
+use crate::domain::{
+    optional_text, Id, NewDevice, NewOrganization, RegistryName, ViewDevice, ViewOrganization,
+};
+use crate::utils::{error_chain_fmt, escape_html, see_other};
+use actix_web::http::StatusCode;
+use actix_web::{http::header::ContentType, web, HttpResponse, ResponseError};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::convert::{TryFrom, TryInto};
+use std::fmt::Write;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct OrganizationFormData {
+    name: String,
+    location: String,
+    description: String,
+}
+
+impl TryFrom<OrganizationFormData> for NewOrganization {
+    type Error = String;
+
+    fn try_from(value: OrganizationFormData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            name: RegistryName::parse(value.name)?,
+            location: optional_text(value.location),
+            description: optional_text(value.description),
+        })
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeviceFormData {
+    organization_id: String,
+    name: String,
+    location: String,
+    description: String,
+}
+
+impl TryFrom<DeviceFormData> for NewDevice {
+    type Error = String;
+
+    fn try_from(value: DeviceFormData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            organization_id: Id::parse(value.organization_id)?,
+            name: RegistryName::parse(value.name)?,
+            location: optional_text(value.location),
+            description: optional_text(value.description),
+        })
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum RegistryError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for RegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for RegistryError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RegistryError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            RegistryError::Forbidden(_) => StatusCode::FORBIDDEN,
+            RegistryError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+const REGISTRY_FIELDS_HTML: &str = r#"<div style="margin-bottom: 5px">
+            <label>Name:<br>
+                <input type="text" placeholder="Enter name" name="name">
+            </label>
+        </div>
+        <div style="margin-bottom: 5px">
+            <label>Location:<br>
+                <input type="text" placeholder="Enter location" name="location">
+            </label>
+        </div>
+        <div style="margin-bottom: 5px">
+            <label>Description:<br>
+                <input type="text" placeholder="Enter description" name="description">
+            </label>
+        </div>"#;
+
+pub async fn get_view_admin_organizations(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+    access: UserAccess,
+) -> Result<HttpResponse, RegistryError> {
+    let organizations = select_organizations(&pool, &access)
+        .await
+        .context("Failed to retrieve the apiaries.")?;
+
+    let mut msg_html = String::new();
+
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", escape_html(m.content())).unwrap();
+    }
+
+    let mut organizations_html = String::new();
+
+    for organization in organizations {
+        writeln!(
+            organizations_html,
+            "<p><i>{} ({}), location: {}, {}</i></p>",
+            escape_html(&organization.name),
+            organization.id,
+            escape_html(organization.location.as_deref().unwrap_or("unknown")),
+            escape_html(organization.description.as_deref().unwrap_or_default())
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Apiaries</title>
+</head>
+<body>
+    {msg_html}
+    <p>Registered apiaries:</p>
+    {organizations_html}
+    <p>Register an apiary</p>
+    <form action="/admin/organizations/create" method="post">
+        {REGISTRY_FIELDS_HTML}
+        <div>
+            <button type="submit">Register</button>
+        </div>
+    </form>
+</body>
+</html>"#
+        )))
+}
+
+/// Apiaries define who can access what, so only superadmins register them.
+#[tracing::instrument(
+name = "Registering a new apiary",
+skip(form, pool, access),
+fields(
+name = % form.name,
+)
+)]
+pub async fn post_create_admin_organization(
+    form: web::Form<OrganizationFormData>,
+    pool: web::Data<PgPool>,
+    access: UserAccess,
+) -> Result<HttpResponse, RegistryError> {
+    if !access.is_superadmin() {
+        return Err(RegistryError::Forbidden(
+            "Only superadmins can register apiaries.".into(),
+        ));
+    }
+    let new_organization: NewOrganization =
+        form.0.try_into().map_err(RegistryError::ValidationError)?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    insert_organization(&mut transaction, &new_organization)
+        .await
+        .context("Failed to insert new apiary in the database.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a new apiary.")?;
+
+    FlashMessage::info(format!(
+        "The apiary {} has been registered.",
+        new_organization.name.as_ref()
+    ))
+    .send();
+    Ok(see_other("/admin/organizations/view"))
+}
+
+pub async fn get_view_admin_devices(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+    access: UserAccess,
+) -> Result<HttpResponse, RegistryError> {
+    let devices = select_devices(&pool, &access)
+        .await
+        .context("Failed to retrieve the hives.")?;
+    let organizations = select_organizations(&pool, &access)
+        .await
+        .context("Failed to retrieve the apiaries.")?;
+
+    let mut msg_html = String::new();
+
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", escape_html(m.content())).unwrap();
+    }
+
+    let mut devices_html = String::new();
+
+    for device in devices {
+        writeln!(
+            devices_html,
+            "<p><i>{} ({}) in apiary {}, location: {}, {}</i></p>",
+            escape_html(&device.name),
+            device.id,
+            escape_html(&device.organization_name),
+            escape_html(device.location.as_deref().unwrap_or("unknown")),
+            escape_html(device.description.as_deref().unwrap_or_default())
+        )
+        .unwrap();
+    }
+
+    let organizations_html = organization_options_html(
+        organizations
+            .iter()
+            .filter(|organization| access.can_edit(organization.id)),
+        None,
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Hives</title>
+</head>
+<body>
+    {msg_html}
+    <p>Registered hives:</p>
+    {devices_html}
+    <p>Register a hive</p>
+    <form action="/admin/devices/create" method="post">
+        <div style="margin-bottom: 5px">
+            <label>Apiary:<br>
+                <select name="organization_id">
+                    {organizations_html}
+                </select>
+            </label>
+        </div>
+        {REGISTRY_FIELDS_HTML}
+        <div>
+            <button type="submit">Register</button>
+        </div>
+    </form>
+</body>
+</html>"#
+        )))
+}
+
+#[tracing::instrument(
+name = "Registering a new hive",
+skip(form, pool, access),
+fields(
+organization_id = % form.organization_id,
+name = % form.name,
+)
+)]
+pub async fn post_create_admin_device(
+    form: web::Form<DeviceFormData>,
+    pool: web::Data<PgPool>,
+    access: UserAccess,
+) -> Result<HttpResponse, RegistryError> {
+    let new_device: NewDevice = form.0.try_into().map_err(RegistryError::ValidationError)?;
+    let organization_id = *new_device.organization_id.as_ref();
+    if !access.can_edit(organization_id) {
+        return Err(RegistryError::Forbidden(format!(
+            "You are not allowed to register hives in the apiary {organization_id}."
+        )));
+    }
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    if !organization_exists(&mut transaction, organization_id)
+        .await
+        .context("Failed to look up the apiary.")?
+    {
+        return Err(RegistryError::ValidationError(format!(
+            "The apiary {organization_id} is not registered."
+        )));
+    }
+    insert_device(&mut transaction, &new_device)
+        .await
+        .context("Failed to insert new hive in the database.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a new hive.")?;
+
+    FlashMessage::info(format!(
+        "The hive {} has been registered.",
+        new_device.name.as_ref()
+    ))
+    .send();
+    Ok(see_other("/admin/devices/view"))
+}
+
+/// Options of an html select of apiaries with `selected` preselected.
+pub fn organization_options_html<'a>(
+    organizations: impl Iterator<Item = &'a ViewOrganization>,
+    selected: Option<Uuid>,
+) -> String {
+    let mut options_html = String::new();
+    for organization in organizations {
+        writeln!(
+            options_html,
+            r#"<option value="{}"{}>{}</option>"#,
+            organization.id,
+            if selected == Some(organization.id) {
+                " selected"
+            } else {
+                ""
+            },
+            escape_html(&organization.name)
+        )
+        .unwrap();
+    }
+    options_html
+}
+
+/// Apiaries the user can view, ordered by name.
+#[tracing::instrument(name = "Select all apiaries from the database", skip(pool))]
+pub async fn select_organizations(
+    pool: &PgPool,
+    access: &UserAccess,
+) -> Result<Vec<ViewOrganization>, sqlx::Error> {
+    sqlx::query_as!(
+        ViewOrganization,
+        r#"
+    SELECT id, name, location, description FROM organizations
+        WHERE $1 OR id = ANY($2)
+        ORDER BY name
+    "#,
+        access.is_superadmin(),
+        &access.organization_ids()
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Hives of the apiaries the user can view, ordered by apiary and hive name.
+#[tracing::instrument(name = "Select all hives from the database", skip(pool))]
+pub async fn select_devices(
+    pool: &PgPool,
+    access: &UserAccess,
+) -> Result<Vec<ViewDevice>, sqlx::Error> {
+    sqlx::query_as!(
+        ViewDevice,
+        r#"
+    SELECT d.id, d.organization_id, o.name AS organization_name, d.name, d.location, d.description
+        FROM devices d
+        JOIN organizations o ON o.id = d.organization_id
+        WHERE $1 OR d.organization_id = ANY($2)
+        ORDER BY o.name, d.name
+    "#,
+        access.is_superadmin(),
+        &access.organization_ids()
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Check that an apiary is registered", skip(transaction))]
+pub async fn organization_exists(
+    transaction: &mut Transaction<'_, Postgres>,
+    organization_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM organizations WHERE id = $1) AS "exists!""#,
+        organization_id
+    )
+    .fetch_one(&mut *transaction)
+    .await
+}
+
+/// The apiary a hive is registered in.
+#[tracing::instrument(name = "Select the apiary of a hive", skip(pool))]
+pub async fn select_device_organization(
+    pool: &PgPool,
+    device_id: Uuid,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT organization_id FROM devices WHERE id = $1",
+        device_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+#[tracing::instrument(
+    name = "Insert a new apiary in the database",
+    skip(new_organization, transaction)
+)]
+pub async fn insert_organization(
+    transaction: &mut Transaction<'_, Postgres>,
+    new_organization: &NewOrganization,
+) -> Result<Uuid, sqlx::Error> {
+    let organization_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+    INSERT INTO organizations (id, name, location, description, created_at, updated_at)
+    VALUES ($1, $2, $3, $4, $5, $5)
+            "#,
+        organization_id,
+        new_organization.name.as_ref(),
+        new_organization.location,
+        new_organization.description,
+        Utc::now()
+    )
+    .execute(transaction)
+    .await?;
+    Ok(organization_id)
+}
+
+#[tracing::instrument(
+    name = "Insert a new hive in the database",
+    skip(new_device, transaction)
+)]
+pub async fn insert_device(
+    transaction: &mut Transaction<'_, Postgres>,
+    new_device: &NewDevice,
+) -> Result<Uuid, sqlx::Error> {
+    let device_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+    INSERT INTO devices (id, organization_id, name, location, description, created_at, updated_at)
+    VALUES ($1, $2, $3, $4, $5, $6, $6)
+            "#,
+        device_id,
+        new_device.organization_id.as_ref(),
+        new_device.name.as_ref(),
+        new_device.location,
+        new_device.description,
+        Utc::now()
+    )
+    .execute(transaction)
+    .await?;
+    Ok(device_id)
+}
@@ -2,4 +2,13 @@ mod topics;
 
 pub use topics::get_view_admin_subscriptions_topics;
 pub use topics::post_create_admin_subscriptions_topics;
-pub use topics::get_create_admin_subscriptions_topics;
\ No newline at end of file
+pub use topics::get_create_admin_subscriptions_topics;
+pub use topics::{
+    get_delete_admin_subscriptions_topic, get_edit_admin_subscriptions_topic,
+    post_delete_admin_subscriptions_topic, post_edit_admin_subscriptions_topic,
+};
+pub use topics::insert_subscriber_topic;
+pub use topics::{
+    authorize_subscriber, delete_subscriber_topic, select_editable_subscriber_topic,
+    select_visible_subscriber_topic, update_subscriber_topic, TopicSubscribeError,
+};
\ No newline at end of file
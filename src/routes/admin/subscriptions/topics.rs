@@ -1,5 +1,12 @@
-use crate::domain::{Id, NewSubscriberTopic, ViewSubscriberTopic};
-use crate::utils::{error_chain_fmt, see_other};
+use crate::authentication::UserAccess;
+use crate::domain::{
+    DeviceNamePattern, Id, NewSubscriberTopic, PayloadFormat, TopicPrefix, ViewDevice,
+    ViewOrganization, ViewSubscriberTopic,
+};
+use crate::routes::admin::registry::{
+    organization_options_html, select_device_organization, select_devices, select_organizations,
+};
+use crate::utils::{error_chain_fmt, escape_html, see_other};
 use actix_web::http::StatusCode;
 use actix_web::{http::header::ContentType, web, HttpResponse, ResponseError};
 use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
@@ -16,30 +23,45 @@ pub struct FormData {
     organization_id: String,
     device_name: String,
     topic_prefix: String,
+    payload_format: String,
 }
 
 impl TryFrom<FormData> for NewSubscriberTopic {
     type Error = String;
 
     fn try_from(value: FormData) -> Result<Self, Self::Error> {
-        let device_id = Id::parse(value.device_id)?;
+        let device_id = match value.device_id.trim() {
+            "" => None,
+            device_id => Some(Id::parse(device_id.to_string())?),
+        };
         let organization_id = Id::parse(value.organization_id)?;
-        let device_name = value.device_name;
-        let topic_prefix = value.topic_prefix;
+        let device_name = DeviceNamePattern::parse(value.device_name)?;
+        let topic_prefix = TopicPrefix::parse(value.topic_prefix)?;
+        let payload_format = PayloadFormat::parse(value.payload_format)?;
 
         Ok(Self {
             organization_id,
             device_id,
             device_name,
             topic_prefix,
+            payload_format,
         })
     }
 }
 
+/// Unique constraint of `subscriptions_topics` on the topic prefix and device name.
+const UNIQUE_TOPIC_CONSTRAINT: &str = "subscriptions_topics_topic_key";
+
 #[derive(thiserror::Error)]
 pub enum TopicSubscribeError {
     #[error("{0}")]
     ValidationError(String),
+    #[error("You are not allowed to manage the subscriptions of the apiary {0}.")]
+    Forbidden(Uuid),
+    #[error("No subscription topic with id {0}.")]
+    NotFound(Uuid),
+    #[error("The topic {0} is already subscribed.")]
+    DuplicateTopic(String),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -54,23 +76,91 @@ impl ResponseError for TopicSubscribeError {
     fn status_code(&self) -> StatusCode {
         match self {
             TopicSubscribeError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            TopicSubscribeError::Forbidden(_) => StatusCode::FORBIDDEN,
+            TopicSubscribeError::NotFound(_) => StatusCode::NOT_FOUND,
+            TopicSubscribeError::DuplicateTopic(_) => StatusCode::CONFLICT,
             TopicSubscribeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 }
 
+impl TopicSubscribeError {
+    /// Stable identifier of the error for the api clients.
+    pub fn code(&self) -> &'static str {
+        match self {
+            TopicSubscribeError::ValidationError(_) => "validation_error",
+            TopicSubscribeError::Forbidden(_) => "forbidden",
+            TopicSubscribeError::NotFound(_) => "not_found",
+            TopicSubscribeError::DuplicateTopic(_) => "duplicate_topic",
+            TopicSubscribeError::UnexpectedError(_) => "unexpected_error",
+        }
+    }
+
+    /// Reports the violation of the unique topic constraint as a duplicate topic.
+    pub fn from_store(
+        e: sqlx::Error,
+        new_subscriber: &NewSubscriberTopic,
+        context: &'static str,
+    ) -> TopicSubscribeError {
+        match &e {
+            sqlx::Error::Database(db_error)
+                if db_error.constraint() == Some(UNIQUE_TOPIC_CONSTRAINT) =>
+            {
+                TopicSubscribeError::DuplicateTopic(new_subscriber.topic())
+            }
+            _ => TopicSubscribeError::UnexpectedError(anyhow::Error::new(e).context(context)),
+        }
+    }
+}
+
+/// Sends a duplicate topic back to the form it was submitted from, other errors are returned.
+fn flash_duplicate_topic(
+    e: TopicSubscribeError,
+    form_location: &str,
+) -> Result<HttpResponse, TopicSubscribeError> {
+    match e {
+        TopicSubscribeError::DuplicateTopic(_) => {
+            FlashMessage::error(e.to_string()).send();
+            Ok(see_other(form_location))
+        }
+        e => Err(e),
+    }
+}
+
 pub async fn get_view_admin_subscriptions_topics(
     pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+    access: UserAccess,
 ) -> Result<HttpResponse, TopicSubscribeError> {
-    let topics = select_subscribers_topics(&pool).await.unwrap();
+    let topics = select_subscribers_topics(&pool, &access)
+        .await
+        .context("Failed to retrieve the subscriber topics.")?;
+
+    let mut msg_html = String::new();
+
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", escape_html(m.content())).unwrap();
+    }
 
     let mut topics_html = String::new();
 
     for topic in topics {
+        let actions = if access.can_edit(topic.organization_id) {
+            format!(
+                r#" <a href="/admin/subscriptions/topics/{0}/edit">edit</a> <a href="/admin/subscriptions/topics/{0}/delete">delete</a>"#,
+                topic.id
+            )
+        } else {
+            String::new()
+        };
         writeln!(
             topics_html,
-            "<p><i>topic: {}/{}, for apiary {} and hive {} </i></p>",
-            topic.topic_prefix, topic.device_name, topic.organization_id, topic.device_id
+            "<p><i>topic: {}, for apiary {}{}, payload format {} </i>{}</p>",
+            escape_html(&topic.topic()),
+            topic.organization_id,
+            hive_html(&topic),
+            topic.payload_format,
+            actions
         )
         .unwrap();
     }
@@ -86,6 +176,7 @@ pub async fn get_view_admin_subscriptions_topics(
     <title>View subscriptions</title>
 </head>
 <body>
+    {msg_html}
     <a href="/admin/subscriptions/topics/create">Create a new topic</a>
     <p>Available topics:</p>
     {topics_html}
@@ -94,35 +185,71 @@ pub async fn get_view_admin_subscriptions_topics(
         )))
 }
 
-pub async fn get_create_admin_subscriptions_topics(
-    flash_messages: IncomingFlashMessages,
-) -> Result<HttpResponse, actix_web::Error> {
-    let mut msg_html = String::new();
+/// Form of a subscription, `current` fills in the values of an existing subscription and only
+/// the apiaries and hives the user can edit are offered.
+fn topic_form_html(
+    action: &str,
+    submit: &str,
+    organizations: &[ViewOrganization],
+    devices: &[ViewDevice],
+    access: &UserAccess,
+    current: Option<&ViewSubscriberTopic>,
+) -> String {
+    let organizations_html = organization_options_html(
+        organizations
+            .iter()
+            .filter(|organization| access.can_edit(organization.id)),
+        current.map(|topic| topic.organization_id),
+    );
 
-    for m in flash_messages.iter() {
-        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+    let mut devices_html = String::new();
+
+    for device in devices
+        .iter()
+        .filter(|device| access.can_edit(device.organization_id))
+    {
+        writeln!(
+            devices_html,
+            r#"<option value="{}"{}>{} / {}</option>"#,
+            device.id,
+            selected(current.and_then(|topic| topic.device_id) == Some(device.id)),
+            escape_html(&device.organization_name),
+            escape_html(&device.name)
+        )
+        .unwrap();
     }
 
-    Ok(HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(format!(
-            r#"
-<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta http-equiv="content-type" content="text/html; charset=utf-8">
-    <title>Create a subscription</title>
-</head>
-<body>
-    {msg_html}
-    <p>Create subscription for topic</p>
-    <form action="/admin/subscriptions/topics/create" method="post">
+    writeln!(
+        devices_html,
+        r#"<option value=""{}>No hive, for + or # patterns</option>"#,
+        selected(current.is_some_and(|topic| topic.device_id.is_none()))
+    )
+    .unwrap();
+
+    let mut payload_formats_html = String::new();
+
+    for format in PayloadFormat::ALL {
+        writeln!(
+            payload_formats_html,
+            r#"<option value="{0}"{1}>{0}</option>"#,
+            format.as_str(),
+            selected(current.map(|topic| topic.payload_format.as_str()) == Some(format.as_str()))
+        )
+        .unwrap();
+    }
+
+    let topic_prefix = escape_html(current.map_or("", |topic| &topic.topic_prefix));
+    let device_name = escape_html(current.map_or("", |topic| &topic.device_name));
+
+    format!(
+        r#"<form action="{action}" method="post">
         <div style="margin-bottom: 5px">
              <label>Topic prefix:<br>
                 <input
                     type="text"
                     placeholder="Enter topic prefix"
                     name="topic_prefix"
+                    value="{topic_prefix}"
                 >
             </label>
         </div>
@@ -130,41 +257,181 @@ pub async fn get_create_admin_subscriptions_topics(
              <label>Device name:<br>
                 <input
                     type="text"
-                    placeholder="Enter device name"
+                    placeholder="Enter device name, or + or # for all devices"
                     name="device_name"
+                    value="{device_name}"
                 >
             </label>
         </div>
         <div style="margin-bottom: 5px">
-            <label>Apiary id:<br>
-                <input
-                    type="text"
-                    placeholder="Enter apiary id"
-                    name="organization_id"
-                >
+            <label>Apiary:<br>
+                <select name="organization_id">
+                    {organizations_html}
+                </select>
             </label>
         </div>
         <div style="margin-bottom: 5px">
-             <label>Hive id:<br>
-                <input
-                    type="text"
-                    placeholder="Enter hive id"
-                    name="device_id"
-                >
+             <label>Hive:<br>
+                <select name="device_id">
+                    {devices_html}
+                </select>
+            </label>
+        </div>
+        <div style="margin-bottom: 5px">
+             <label>Payload format:<br>
+                <select name="payload_format">
+                    {payload_formats_html}
+                </select>
             </label>
         </div>
         <div>
-            <button type="submit">Subscribe</button>
+            <button type="submit">{submit}</button>
         </div>
-    </form>
+    </form>"#
+    )
+}
+
+/// The hive of a subscription, wildcard patterns cover all the hives of the prefix.
+fn hive_html(topic: &ViewSubscriberTopic) -> String {
+    match topic.device_id {
+        Some(device_id) => format!(" and hive {device_id}"),
+        None => " and all its hives".to_string(),
+    }
+}
+
+fn selected(is_selected: bool) -> &'static str {
+    if is_selected {
+        " selected"
+    } else {
+        ""
+    }
+}
+
+pub async fn get_create_admin_subscriptions_topics(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+    access: UserAccess,
+) -> Result<HttpResponse, TopicSubscribeError> {
+    let organizations = select_organizations(&pool, &access)
+        .await
+        .context("Failed to retrieve the apiaries.")?;
+    let devices = select_devices(&pool, &access)
+        .await
+        .context("Failed to retrieve the hives.")?;
+
+    let mut msg_html = String::new();
+
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", escape_html(m.content())).unwrap();
+    }
+
+    let form_html = topic_form_html(
+        "/admin/subscriptions/topics/create",
+        "Subscribe",
+        &organizations,
+        &devices,
+        &access,
+        None,
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Create a subscription</title>
+</head>
+<body>
+    {msg_html}
+    <p>Create subscription for topic</p>
+    {form_html}
 </body>
 </html>"#,
         )))
 }
 
+/// Checks that the user can edit the apiary and that the hive is registered in it, only
+/// a wildcard pattern can go without a hive.
+pub async fn authorize_subscriber(
+    new_subscriber: &NewSubscriberTopic,
+    pool: &PgPool,
+    access: &UserAccess,
+) -> Result<(), TopicSubscribeError> {
+    let organization_id = *new_subscriber.organization_id.as_ref();
+    if !access.can_edit(organization_id) {
+        return Err(TopicSubscribeError::Forbidden(organization_id));
+    }
+    let device_id = match &new_subscriber.device_id {
+        Some(device_id) => *device_id.as_ref(),
+        None if new_subscriber.is_wildcard() => return Ok(()),
+        None => {
+            return Err(TopicSubscribeError::ValidationError(format!(
+                "The subscription of {} needs a hive.",
+                new_subscriber.topic()
+            )))
+        }
+    };
+    match select_device_organization(pool, device_id)
+        .await
+        .context("Failed to look up the hive.")?
+    {
+        Some(id) if id == organization_id => Ok(()),
+        Some(_) => Err(TopicSubscribeError::ValidationError(format!(
+            "The hive {device_id} is not registered in the apiary {organization_id}."
+        ))),
+        None => Err(TopicSubscribeError::ValidationError(format!(
+            "The hive {device_id} is not registered."
+        ))),
+    }
+}
+
+/// Parses the form and checks that the user can edit the apiary and that the hive is
+/// registered in it.
+async fn validate_subscriber(
+    form: FormData,
+    pool: &PgPool,
+    access: &UserAccess,
+) -> Result<NewSubscriberTopic, TopicSubscribeError> {
+    let new_subscriber: NewSubscriberTopic = form
+        .try_into()
+        .map_err(TopicSubscribeError::ValidationError)?;
+    authorize_subscriber(&new_subscriber, pool, access).await?;
+    Ok(new_subscriber)
+}
+
+/// The subscription topic `id` if the user can view it, topics of other organizations are
+/// reported as missing so their ids are not disclosed.
+pub async fn select_visible_subscriber_topic(
+    pool: &PgPool,
+    id: Uuid,
+    access: &UserAccess,
+) -> Result<ViewSubscriberTopic, TopicSubscribeError> {
+    select_subscriber_topic(pool, id)
+        .await
+        .context("Failed to retrieve the subscription topic.")?
+        .filter(|topic| access.can_view(topic.organization_id))
+        .ok_or(TopicSubscribeError::NotFound(id))
+}
+
+/// The subscription topic `id` if the user can edit it.
+pub async fn select_editable_subscriber_topic(
+    pool: &PgPool,
+    id: Uuid,
+    access: &UserAccess,
+) -> Result<ViewSubscriberTopic, TopicSubscribeError> {
+    let topic = select_visible_subscriber_topic(pool, id, access).await?;
+    if !access.can_edit(topic.organization_id) {
+        return Err(TopicSubscribeError::Forbidden(topic.organization_id));
+    }
+    Ok(topic)
+}
+
 #[tracing::instrument(
 name = "Adding a new subscriber",
-skip(form, pool),
+skip(form, pool, access),
 fields(
 organization_id = % form.organization_id,
 device_id = % form.device_id,
@@ -175,21 +442,22 @@ topic_prefix = % form.topic_prefix,
 pub async fn post_create_admin_subscriptions_topics(
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
+    access: UserAccess,
 ) -> Result<HttpResponse, TopicSubscribeError> {
-    let new_subscriber: NewSubscriberTopic = form
-        .0
-        .try_into()
-        .map_err(TopicSubscribeError::ValidationError)?;
-
-    println!("{:?}", new_subscriber);
+    let new_subscriber = validate_subscriber(form.0, &pool, &access).await?;
 
     let mut transaction = pool
         .begin()
         .await
         .context("Failed to acquire a Postgres connection from the pool")?;
-    insert_subscriber_topic(&mut transaction, &new_subscriber)
-        .await
-        .context("Failed to insert new subscriber in the database.")?;
+    if let Err(e) = insert_subscriber_topic(&mut transaction, &new_subscriber).await {
+        let e = TopicSubscribeError::from_store(
+            e,
+            &new_subscriber,
+            "Failed to insert new subscriber in the database.",
+        );
+        return flash_duplicate_topic(e, "/admin/subscriptions/topics/create");
+    }
     transaction
         .commit()
         .await
@@ -199,22 +467,184 @@ pub async fn post_create_admin_subscriptions_topics(
     Ok(see_other("/admin/subscriptions/topics/view"))
 }
 
+pub async fn get_edit_admin_subscriptions_topic(
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+    access: UserAccess,
+) -> Result<HttpResponse, TopicSubscribeError> {
+    let id = path.into_inner();
+    let topic = select_editable_subscriber_topic(&pool, id, &access).await?;
+    let organizations = select_organizations(&pool, &access)
+        .await
+        .context("Failed to retrieve the apiaries.")?;
+    let devices = select_devices(&pool, &access)
+        .await
+        .context("Failed to retrieve the hives.")?;
+
+    let mut msg_html = String::new();
+
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", escape_html(m.content())).unwrap();
+    }
+
+    let form_html = topic_form_html(
+        &format!("/admin/subscriptions/topics/{id}/edit"),
+        "Save",
+        &organizations,
+        &devices,
+        &access,
+        Some(&topic),
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Edit a subscription</title>
+</head>
+<body>
+    {msg_html}
+    <p>Edit subscription for topic {}</p>
+    {form_html}
+    <a href="/admin/subscriptions/topics/view">Cancel</a>
+</body>
+</html>"#,
+            escape_html(&topic.topic())
+        )))
+}
+
+#[tracing::instrument(
+name = "Editing a subscriber",
+skip(form, pool, access),
+fields(
+organization_id = % form.organization_id,
+device_id = % form.device_id,
+device_name = % form.device_name,
+topic_prefix = % form.topic_prefix,
+)
+)]
+pub async fn post_edit_admin_subscriptions_topic(
+    path: web::Path<Uuid>,
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    access: UserAccess,
+) -> Result<HttpResponse, TopicSubscribeError> {
+    let id = path.into_inner();
+    let topic = select_editable_subscriber_topic(&pool, id, &access).await?;
+    let new_subscriber = validate_subscriber(form.0, &pool, &access).await?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    match update_subscriber_topic(&mut transaction, id, &new_subscriber).await {
+        Ok(true) => {}
+        Ok(false) => return Err(TopicSubscribeError::NotFound(id)),
+        Err(e) => {
+            let e = TopicSubscribeError::from_store(
+                e,
+                &new_subscriber,
+                "Failed to update the subscriber in the database.",
+            );
+            return flash_duplicate_topic(e, &format!("/admin/subscriptions/topics/{id}/edit"));
+        }
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to update a subscriber.")?;
+
+    FlashMessage::info(format!(
+        "The topic {} has been updated to {}.",
+        topic.topic(),
+        new_subscriber.topic()
+    ))
+    .send();
+    Ok(see_other("/admin/subscriptions/topics/view"))
+}
+
+pub async fn get_delete_admin_subscriptions_topic(
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    access: UserAccess,
+) -> Result<HttpResponse, TopicSubscribeError> {
+    let id = path.into_inner();
+    let topic = select_editable_subscriber_topic(&pool, id, &access).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"
+<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Delete a subscription</title>
+</head>
+<body>
+    <p>Delete the subscription of topic {} for apiary {}{}? The worker unsubscribes from the topic.</p>
+    <form action="/admin/subscriptions/topics/{id}/delete" method="post">
+        <button type="submit">Delete</button>
+    </form>
+    <a href="/admin/subscriptions/topics/view">Cancel</a>
+</body>
+</html>"#,
+            escape_html(&topic.topic()),
+            topic.organization_id,
+            hive_html(&topic)
+        )))
+}
+
+#[tracing::instrument(name = "Deleting a subscriber", skip(pool, access))]
+pub async fn post_delete_admin_subscriptions_topic(
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    access: UserAccess,
+) -> Result<HttpResponse, TopicSubscribeError> {
+    let id = path.into_inner();
+    let topic = select_editable_subscriber_topic(&pool, id, &access).await?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    if !delete_subscriber_topic(&mut transaction, id)
+        .await
+        .context("Failed to delete the subscriber from the database.")?
+    {
+        return Err(TopicSubscribeError::NotFound(id));
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to delete a subscriber.")?;
+
+    FlashMessage::info(format!("The topic {} has been deleted.", topic.topic())).send();
+    Ok(see_other("/admin/subscriptions/topics/view"))
+}
+
+/// Subscribers of the organizations the user can view.
 #[tracing::instrument(name = "Select all subscribers from the database", skip(pool))]
 pub async fn select_subscribers_topics(
     pool: &PgPool,
+    access: &UserAccess,
 ) -> Result<Vec<ViewSubscriberTopic>, sqlx::Error> {
-    let subscribers = sqlx::query_as!(
+    sqlx::query_as!(
         ViewSubscriberTopic,
         r#"
-    SELECT organization_id, device_id, device_name, topic_prefix FROM subscriptions_topics
-    "#
+    SELECT id, organization_id, device_id, device_name, topic_prefix, payload_format FROM subscriptions_topics
+        WHERE $1 OR organization_id = ANY($2)
+    "#,
+        access.is_superadmin(),
+        &access.organization_ids()
     )
     .fetch_all(pool)
     .await
-    .context("Failed to performed a query to retrieve stored credentials.")
-    .unwrap();
-
-    Ok(subscribers)
 }
 
 #[tracing::instrument(
@@ -228,14 +658,15 @@ pub async fn insert_subscriber_topic(
     let subscriber_id = Uuid::new_v4();
     sqlx::query!(
         r#"
-    INSERT INTO subscriptions_topics (id, organization_id, device_id, device_name, topic_prefix, created_at, updated_at)
-    VALUES ($1, $2, $3, $4, $5, $6, $7)
+    INSERT INTO subscriptions_topics (id, organization_id, device_id, device_name, topic_prefix, payload_format, created_at, updated_at)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
             "#,
         subscriber_id,
         new_subscriber.organization_id.as_ref(),
-        new_subscriber.device_id.as_ref(),
-        new_subscriber.device_name,
-        new_subscriber.topic_prefix,
+        new_subscriber.device_id.as_ref().map(|id| *id.as_ref()),
+        new_subscriber.device_name.as_ref(),
+        new_subscriber.topic_prefix.as_ref(),
+        new_subscriber.payload_format.as_str(),
         Utc::now(),
         Utc::now()
     )
@@ -243,3 +674,64 @@ pub async fn insert_subscriber_topic(
     .await?;
     Ok(subscriber_id)
 }
+
+#[tracing::instrument(name = "Select a subscriber topic from the database", skip(pool))]
+pub async fn select_subscriber_topic(
+    pool: &PgPool,
+    id: Uuid,
+) -> Result<Option<ViewSubscriberTopic>, sqlx::Error> {
+    sqlx::query_as!(
+        ViewSubscriberTopic,
+        r#"
+    SELECT id, organization_id, device_id, device_name, topic_prefix, payload_format FROM subscriptions_topics
+        WHERE id = $1
+    "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Returns whether the subscriber topic exists, the trigger tells the worker the old topic.
+#[tracing::instrument(
+    name = "Update a subscriber topic in the database",
+    skip(new_subscriber, transaction)
+)]
+pub async fn update_subscriber_topic(
+    transaction: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+    new_subscriber: &NewSubscriberTopic,
+) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query!(
+        r#"
+    UPDATE subscriptions_topics
+        SET organization_id = $2, device_id = $3, device_name = $4, topic_prefix = $5, payload_format = $6, updated_at = $7
+        WHERE id = $1
+            "#,
+        id,
+        new_subscriber.organization_id.as_ref(),
+        new_subscriber.device_id.as_ref().map(|id| *id.as_ref()),
+        new_subscriber.device_name.as_ref(),
+        new_subscriber.topic_prefix.as_ref(),
+        new_subscriber.payload_format.as_str(),
+        Utc::now()
+    )
+    .execute(transaction)
+    .await?;
+    Ok(updated.rows_affected() == 1)
+}
+
+/// Returns whether the subscriber topic existed.
+#[tracing::instrument(
+    name = "Delete a subscriber topic from the database",
+    skip(transaction)
+)]
+pub async fn delete_subscriber_topic(
+    transaction: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let deleted = sqlx::query!("DELETE FROM subscriptions_topics WHERE id = $1", id)
+        .execute(transaction)
+        .await?;
+    Ok(deleted.rows_affected() == 1)
+}
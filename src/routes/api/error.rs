@@ -0,0 +1,112 @@
+use crate::routes::TopicSubscribeError;
+use crate::utils::error_chain_fmt;
+use actix_web::http::{header, StatusCode};
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+use utoipa::ToSchema;
+
+/// Error object of the api responses.
+#[derive(serde::Serialize, ToSchema)]
+pub struct ErrorBody {
+    pub error: ErrorDetail,
+}
+
+#[derive(serde::Serialize, ToSchema)]
+pub struct ErrorDetail {
+    /// Stable identifier of the error, like `not_found` or `duplicate_topic`.
+    #[schema(example = "duplicate_topic")]
+    pub code: String,
+    /// Human readable description of the error.
+    pub message: String,
+}
+
+#[derive(thiserror::Error)]
+pub enum ApiError {
+    #[error(transparent)]
+    Topic(#[from] TopicSubscribeError),
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("A valid api token is required.")]
+    Unauthorized(#[source] anyhow::Error),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ApiError {
+    fn code(&self) -> &'static str {
+        match self {
+            ApiError::Topic(e) => e.code(),
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::UnexpectedError(_) => "unexpected_error",
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::Topic(e) => e.status_code(),
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// The causes of the unexpected errors are only logged, they may expose internals.
+    fn error_response(&self) -> HttpResponse {
+        let message = match self {
+            ApiError::Topic(TopicSubscribeError::UnexpectedError(_))
+            | ApiError::UnexpectedError(_) => "Something went wrong.".to_string(),
+            e => e.to_string(),
+        };
+        let mut response = HttpResponse::build(self.status_code());
+        if let ApiError::Unauthorized(_) = self {
+            response.insert_header((header::WWW_AUTHENTICATE, "Bearer"));
+        }
+        response.json(ErrorBody {
+            error: ErrorDetail {
+                code: self.code().to_string(),
+                message,
+            },
+        })
+    }
+}
+
+/// Error handler of the json, query and path extractors of the api, so malformed requests
+/// get an error object too.
+pub fn reject_malformed_api_request<E: std::fmt::Display>(
+    e: E,
+    _: &HttpRequest,
+) -> actix_web::Error {
+    ApiError::BadRequest(e.to_string()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ApiError;
+    use crate::routes::TopicSubscribeError;
+    use actix_web::body::to_bytes;
+    use actix_web::http::StatusCode;
+    use actix_web::ResponseError;
+
+    #[actix_web::test]
+    async fn unexpected_errors_do_not_expose_their_cause() {
+        let e = ApiError::Topic(TopicSubscribeError::UnexpectedError(anyhow::anyhow!(
+            "password authentication failed for user postgres"
+        )));
+
+        let response = e.error_response();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["code"], "unexpected_error");
+        assert_eq!(body["error"]["message"], "Something went wrong.");
+    }
+}
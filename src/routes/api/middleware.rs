@@ -0,0 +1,46 @@
+use crate::authentication::{validate_api_token, AuthError, UserId};
+use crate::routes::ApiError;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header;
+use actix_web::{web, HttpMessage};
+use actix_web_lab::middleware::Next;
+use anyhow::Context;
+use secrecy::Secret;
+use sqlx::PgPool;
+
+/// Rejects the api requests without a valid `Authorization: Bearer <token>` header, the user
+/// of the token is then available as [`UserId`] like a logged in user.
+pub async fn reject_invalid_api_tokens(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let token = bearer_token(&req).map_err(ApiError::Unauthorized)?;
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .cloned()
+        .context("The database pool is not configured.")
+        .map_err(ApiError::UnexpectedError)?;
+
+    match validate_api_token(token, &pool).await {
+        Ok(user_id) => {
+            req.extensions_mut().insert(UserId::from(user_id));
+            next.call(req).await
+        }
+        Err(AuthError::InvalidCredentials(e)) => Err(ApiError::Unauthorized(e).into()),
+        Err(AuthError::UnexpectedError(e)) => Err(ApiError::UnexpectedError(e).into()),
+    }
+}
+
+fn bearer_token(req: &ServiceRequest) -> Result<Secret<String>, anyhow::Error> {
+    let header_value = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .context("The 'Authorization' header was missing.")?
+        .to_str()
+        .context("The 'Authorization' header was not a valid UTF8 string.")?;
+    let token = header_value
+        .strip_prefix("Bearer ")
+        .context("The authorization scheme was not 'Bearer'.")?;
+    Ok(Secret::new(token.trim().to_string()))
+}
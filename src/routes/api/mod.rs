@@ -0,0 +1,12 @@
+mod error;
+mod middleware;
+mod openapi;
+mod topics;
+
+pub use error::{reject_malformed_api_request, ApiError, ErrorBody, ErrorDetail};
+pub use middleware::reject_invalid_api_tokens;
+pub use openapi::{get_api_openapi, ApiDoc};
+pub use topics::{
+    delete_api_subscriptions_topic, get_api_subscriptions_topic, get_api_subscriptions_topics,
+    post_api_subscriptions_topics, put_api_subscriptions_topic,
+};
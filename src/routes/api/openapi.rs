@@ -0,0 +1,38 @@
+use crate::routes::api::error::{ErrorBody, ErrorDetail};
+use crate::routes::api::topics::{self, TopicPage, TopicRequest, TopicResponse};
+use actix_web::web;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+/// OpenAPI description of the `/api/v1` endpoints, generated from the handlers.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Bumblebee API", description = "Management of the subscription topics."),
+    paths(
+        topics::get_api_subscriptions_topics,
+        topics::get_api_subscriptions_topic,
+        topics::post_api_subscriptions_topics,
+        topics::put_api_subscriptions_topic,
+        topics::delete_api_subscriptions_topic,
+    ),
+    components(schemas(TopicRequest, TopicResponse, TopicPage, ErrorBody, ErrorDetail)),
+    modifiers(&ApiTokenSecurity),
+    tags((name = "subscriptions", description = "Topics the worker subscribes to"))
+)]
+pub struct ApiDoc;
+
+struct ApiTokenSecurity;
+
+impl Modify for ApiTokenSecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_token",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+pub async fn get_api_openapi() -> web::Json<utoipa::openapi::OpenApi> {
+    web::Json(ApiDoc::openapi())
+}
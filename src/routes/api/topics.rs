@@ -0,0 +1,361 @@
+use crate::authentication::UserAccess;
+use crate::domain::{
+    DeviceNamePattern, NewSubscriberTopic, PayloadFormat, TopicPrefix, ViewSubscriberTopic,
+};
+use crate::routes::{
+    authorize_subscriber, delete_subscriber_topic, insert_subscriber_topic,
+    select_editable_subscriber_topic, select_visible_subscriber_topic, update_subscriber_topic,
+    ApiError, TopicSubscribeError,
+};
+use actix_web::http::header;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use std::convert::{TryFrom, TryInto};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+const DEFAULT_PER_PAGE: u32 = 50;
+const MAX_PER_PAGE: u32 = 100;
+
+#[derive(serde::Deserialize, ToSchema)]
+pub struct TopicRequest {
+    /// Apiary of the subscription.
+    organization_id: Uuid,
+    /// Hive of the subscription, it has to be registered in the apiary. Only a wildcard
+    /// pattern covering all the hives of the prefix can leave it out.
+    device_id: Option<Uuid>,
+    /// Name of the device or a pattern of mqtt wildcards, like `+` or `#`.
+    #[schema(example = "hive-1")]
+    device_name: String,
+    /// Plain topic without wildcards.
+    #[schema(example = "beesbuddy/apiary-7")]
+    topic_prefix: String,
+    /// One of `json`, `cbor`, `csv` or `binary`.
+    #[schema(example = "json")]
+    payload_format: String,
+}
+
+impl TryFrom<TopicRequest> for NewSubscriberTopic {
+    type Error = String;
+
+    fn try_from(value: TopicRequest) -> Result<Self, Self::Error> {
+        let device_name = DeviceNamePattern::parse(value.device_name)?;
+        let topic_prefix = TopicPrefix::parse(value.topic_prefix)?;
+        let payload_format = PayloadFormat::parse(value.payload_format)?;
+
+        Ok(Self {
+            organization_id: value.organization_id.into(),
+            device_id: value.device_id.map(Into::into),
+            device_name,
+            topic_prefix,
+            payload_format,
+        })
+    }
+}
+
+#[derive(serde::Serialize, ToSchema)]
+pub struct TopicResponse {
+    id: Uuid,
+    organization_id: Uuid,
+    /// Empty for a wildcard pattern covering all the hives of the prefix.
+    device_id: Option<Uuid>,
+    #[schema(example = "hive-1")]
+    device_name: String,
+    #[schema(example = "beesbuddy/apiary-7")]
+    topic_prefix: String,
+    /// Topic filter the worker subscribes with.
+    #[schema(example = "beesbuddy/apiary-7/hive-1")]
+    topic: String,
+    #[schema(example = "json")]
+    payload_format: String,
+}
+
+impl From<ViewSubscriberTopic> for TopicResponse {
+    fn from(value: ViewSubscriberTopic) -> Self {
+        Self {
+            topic: value.topic(),
+            id: value.id,
+            organization_id: value.organization_id,
+            device_id: value.device_id,
+            device_name: value.device_name,
+            topic_prefix: value.topic_prefix,
+            payload_format: value.payload_format,
+        }
+    }
+}
+
+#[derive(serde::Serialize, ToSchema)]
+pub struct TopicPage {
+    items: Vec<TopicResponse>,
+    page: u32,
+    per_page: u32,
+    /// Number of topics matching the filter on all the pages.
+    total: i64,
+}
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TopicsQuery {
+    /// Only the topics of this apiary.
+    organization_id: Option<Uuid>,
+    /// Page number, starting at 1.
+    #[param(minimum = 1, default = 1)]
+    page: Option<u32>,
+    /// Topics per page.
+    #[param(minimum = 1, maximum = 100, default = 50)]
+    per_page: Option<u32>,
+}
+
+/// Subscription topics of the apiaries of the user, oldest first.
+#[utoipa::path(
+    get,
+    path = "/api/v1/subscriptions/topics",
+    tag = "subscriptions",
+    params(TopicsQuery),
+    responses(
+        (status = 200, description = "A page of subscription topics", body = TopicPage),
+        (status = 400, description = "Invalid page", body = ErrorBody),
+        (status = 401, description = "Missing or invalid api token", body = ErrorBody),
+    ),
+    security(("api_token" = []))
+)]
+pub async fn get_api_subscriptions_topics(
+    query: web::Query<TopicsQuery>,
+    pool: web::Data<PgPool>,
+    access: UserAccess,
+) -> Result<web::Json<TopicPage>, ApiError> {
+    let page = query.page.unwrap_or(1);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PER_PAGE);
+    if page == 0 || !(1..=MAX_PER_PAGE).contains(&per_page) {
+        return Err(ApiError::BadRequest(format!(
+            "The page starts at 1 and has between 1 and {MAX_PER_PAGE} topics."
+        )));
+    }
+
+    let (topics, total) =
+        select_subscribers_topics_page(&pool, &access, query.organization_id, page, per_page)
+            .await
+            .context("Failed to retrieve the subscription topics.")?;
+
+    Ok(web::Json(TopicPage {
+        items: topics.into_iter().map(TopicResponse::from).collect(),
+        page,
+        per_page,
+        total,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/subscriptions/topics/{id}",
+    tag = "subscriptions",
+    params(("id" = Uuid, Path, description = "Id of the subscription topic")),
+    responses(
+        (status = 200, description = "The subscription topic", body = TopicResponse),
+        (status = 401, description = "Missing or invalid api token", body = ErrorBody),
+        (status = 404, description = "No visible subscription topic with this id", body = ErrorBody),
+    ),
+    security(("api_token" = []))
+)]
+pub async fn get_api_subscriptions_topic(
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    access: UserAccess,
+) -> Result<web::Json<TopicResponse>, ApiError> {
+    let topic = select_visible_subscriber_topic(&pool, path.into_inner(), &access).await?;
+    Ok(web::Json(topic.into()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/subscriptions/topics",
+    tag = "subscriptions",
+    request_body = TopicRequest,
+    responses(
+        (status = 201, description = "The subscription topic has been created", body = TopicResponse,
+            headers(("Location" = String, description = "Url of the new subscription topic"))),
+        (status = 400, description = "Invalid subscription topic", body = ErrorBody),
+        (status = 401, description = "Missing or invalid api token", body = ErrorBody),
+        (status = 403, description = "The user cannot edit the apiary", body = ErrorBody),
+        (status = 409, description = "The topic is already subscribed", body = ErrorBody),
+    ),
+    security(("api_token" = []))
+)]
+#[tracing::instrument(
+    name = "Adding a new subscriber with the api",
+    skip(body, pool, access)
+)]
+pub async fn post_api_subscriptions_topics(
+    body: web::Json<TopicRequest>,
+    pool: web::Data<PgPool>,
+    access: UserAccess,
+) -> Result<HttpResponse, ApiError> {
+    let new_subscriber: NewSubscriberTopic = body
+        .into_inner()
+        .try_into()
+        .map_err(TopicSubscribeError::ValidationError)?;
+    authorize_subscriber(&new_subscriber, &pool, &access).await?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let id = insert_subscriber_topic(&mut transaction, &new_subscriber)
+        .await
+        .map_err(|e| {
+            TopicSubscribeError::from_store(
+                e,
+                &new_subscriber,
+                "Failed to insert new subscriber in the database.",
+            )
+        })?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a new subscriber.")?;
+
+    let topic = select_visible_subscriber_topic(&pool, id, &access).await?;
+    Ok(HttpResponse::Created()
+        .insert_header((
+            header::LOCATION,
+            format!("/api/v1/subscriptions/topics/{id}"),
+        ))
+        .json(TopicResponse::from(topic)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/subscriptions/topics/{id}",
+    tag = "subscriptions",
+    params(("id" = Uuid, Path, description = "Id of the subscription topic")),
+    request_body = TopicRequest,
+    responses(
+        (status = 200, description = "The subscription topic has been updated", body = TopicResponse),
+        (status = 400, description = "Invalid subscription topic", body = ErrorBody),
+        (status = 401, description = "Missing or invalid api token", body = ErrorBody),
+        (status = 403, description = "The user cannot edit the apiary", body = ErrorBody),
+        (status = 404, description = "No visible subscription topic with this id", body = ErrorBody),
+        (status = 409, description = "The topic is already subscribed", body = ErrorBody),
+    ),
+    security(("api_token" = []))
+)]
+#[tracing::instrument(name = "Updating a subscriber with the api", skip(body, pool, access))]
+pub async fn put_api_subscriptions_topic(
+    path: web::Path<Uuid>,
+    body: web::Json<TopicRequest>,
+    pool: web::Data<PgPool>,
+    access: UserAccess,
+) -> Result<web::Json<TopicResponse>, ApiError> {
+    let id = path.into_inner();
+    select_editable_subscriber_topic(&pool, id, &access).await?;
+    let new_subscriber: NewSubscriberTopic = body
+        .into_inner()
+        .try_into()
+        .map_err(TopicSubscribeError::ValidationError)?;
+    authorize_subscriber(&new_subscriber, &pool, &access).await?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let updated = update_subscriber_topic(&mut transaction, id, &new_subscriber)
+        .await
+        .map_err(|e| {
+            TopicSubscribeError::from_store(
+                e,
+                &new_subscriber,
+                "Failed to update the subscriber in the database.",
+            )
+        })?;
+    if !updated {
+        return Err(TopicSubscribeError::NotFound(id).into());
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to update a subscriber.")?;
+
+    let topic = select_visible_subscriber_topic(&pool, id, &access).await?;
+    Ok(web::Json(topic.into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/subscriptions/topics/{id}",
+    tag = "subscriptions",
+    params(("id" = Uuid, Path, description = "Id of the subscription topic")),
+    responses(
+        (status = 204, description = "The subscription topic has been deleted"),
+        (status = 401, description = "Missing or invalid api token", body = ErrorBody),
+        (status = 403, description = "The user cannot edit the apiary", body = ErrorBody),
+        (status = 404, description = "No visible subscription topic with this id", body = ErrorBody),
+    ),
+    security(("api_token" = []))
+)]
+#[tracing::instrument(name = "Deleting a subscriber with the api", skip(pool, access))]
+pub async fn delete_api_subscriptions_topic(
+    path: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    access: UserAccess,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    select_editable_subscriber_topic(&pool, id, &access).await?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    if !delete_subscriber_topic(&mut transaction, id)
+        .await
+        .context("Failed to delete the subscriber from the database.")?
+    {
+        return Err(TopicSubscribeError::NotFound(id).into());
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to delete a subscriber.")?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// A page of the subscribers of the organizations the user can view, with the number of
+/// subscribers on all the pages.
+#[tracing::instrument(name = "Select a page of subscribers from the database", skip(pool))]
+async fn select_subscribers_topics_page(
+    pool: &PgPool,
+    access: &UserAccess,
+    organization_id: Option<Uuid>,
+    page: u32,
+    per_page: u32,
+) -> Result<(Vec<ViewSubscriberTopic>, i64), sqlx::Error> {
+    let topics = sqlx::query_as!(
+        ViewSubscriberTopic,
+        r#"
+    SELECT id, organization_id, device_id, device_name, topic_prefix, payload_format FROM subscriptions_topics
+        WHERE ($1 OR organization_id = ANY($2)) AND ($3::uuid IS NULL OR organization_id = $3)
+        ORDER BY created_at, id
+        LIMIT $4 OFFSET $5
+    "#,
+        access.is_superadmin(),
+        &access.organization_ids(),
+        organization_id,
+        i64::from(per_page),
+        i64::from(page - 1) * i64::from(per_page)
+    )
+    .fetch_all(pool)
+    .await?;
+    let total = sqlx::query_scalar!(
+        r#"
+    SELECT COUNT(*) AS "total!" FROM subscriptions_topics
+        WHERE ($1 OR organization_id = ANY($2)) AND ($3::uuid IS NULL OR organization_id = $3)
+    "#,
+        access.is_superadmin(),
+        &access.organization_ids(),
+        organization_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok((topics, total))
+}
@@ -1,6 +1,98 @@
-use actix_web::HttpResponse;
+use crate::health::DependencyHealth;
+use crate::supervisor::{Supervisor, WorkerStatus};
+use crate::workers::SUBSCRIPTIONS_WORKER;
+use actix_web::{web, HttpResponse};
+use serde_json::{json, Value};
+use sqlx::PgPool;
 
 #[tracing::instrument(name = "Health check")]
 pub async fn health_check() -> HttpResponse {
     HttpResponse::Ok().finish()
 }
+
+/// Readiness of the service with the status of every dependency.
+///
+/// Postgres and the mqtt broker are critical: without them no reading can be ingested.
+/// Influxdb outages are covered by the spool and missed subscriptions notifications by
+/// the reconciliation, so those only degrade the service.
+#[tracing::instrument(name = "Readiness check", skip(pool, health, supervisor))]
+pub async fn health_ready(
+    pool: web::Data<PgPool>,
+    health: web::Data<DependencyHealth>,
+    supervisor: web::Data<Supervisor>,
+) -> HttpResponse {
+    let database = match pool.acquire().await {
+        Ok(_) => dependency(true, true, json!({})),
+        Err(err) => dependency(false, true, json!({ "error": err.to_string() })),
+    };
+
+    let mqtt = dependency(health.mqtt_connected(), true, json!({}));
+
+    let writes = health.influxdb_writes();
+    let influxdb = dependency(
+        !writes.is_failing(),
+        false,
+        json!({
+            "last_success_at": writes.last_success_at.map(|at| at.to_rfc3339()),
+            "last_failure_at": writes.last_failure_at.map(|at| at.to_rfc3339()),
+            "last_error": writes.last_error,
+        }),
+    );
+
+    let subscriptions_listener = dependency(
+        health.subscriptions_listener_connected(),
+        false,
+        json!({ "worker": supervisor.status(SUBSCRIPTIONS_WORKER).map(|s| worker(&s)) }),
+    );
+
+    let dependencies = [
+        ("database", database),
+        ("mqtt", mqtt),
+        ("influxdb", influxdb),
+        ("subscriptions_listener", subscriptions_listener),
+    ];
+    let ready = dependencies
+        .iter()
+        .all(|(_, d)| d["status"] == "up" || d["critical"] == false);
+
+    let workers: serde_json::Map<String, Value> = supervisor
+        .statuses()
+        .iter()
+        .map(|(name, status)| (name.to_string(), worker(status)))
+        .collect();
+
+    let body = json!({
+        "status": if ready { "ready" } else { "not_ready" },
+        "dependencies": dependencies
+            .into_iter()
+            .map(|(name, d)| (name.to_string(), d))
+            .collect::<serde_json::Map<String, Value>>(),
+        "workers": workers,
+    });
+
+    if ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+fn dependency(up: bool, critical: bool, details: Value) -> Value {
+    let mut dependency = json!({
+        "status": if up { "up" } else { "down" },
+        "critical": critical,
+    });
+    if let (Some(dependency), Value::Object(details)) = (dependency.as_object_mut(), details) {
+        dependency.extend(details);
+    }
+    dependency
+}
+
+fn worker(status: &WorkerStatus) -> Value {
+    json!({
+        "state": status.state.as_str(),
+        "restarts": status.restarts,
+        "last_error": status.last_error,
+        "last_error_at": status.last_error_at.map(|at| at.to_rfc3339()),
+    })
+}
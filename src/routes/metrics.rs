@@ -0,0 +1,44 @@
+use crate::metrics::Metrics;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::{web, HttpResponse};
+use actix_web_lab::middleware::Next;
+use std::time::Instant;
+
+/// Metrics in the prometheus text format.
+pub async fn get_metrics(metrics: web::Data<Metrics>) -> HttpResponse {
+    match metrics.encode() {
+        Ok(body) => HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4; charset=utf-8")
+            .body(body),
+        Err(err) => {
+            tracing::error!("Error during metrics encoding = {err:?}");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Records the duration of every request under its route pattern, requests that match
+/// no route are grouped so that random paths do not create new series.
+pub async fn record_request_duration(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let started_at = Instant::now();
+    let method = req.method().to_string();
+
+    let response = next.call(req).await?;
+
+    if let Some(metrics) = response.request().app_data::<web::Data<Metrics>>() {
+        let route = response
+            .request()
+            .match_pattern()
+            .unwrap_or_else(|| "unmatched".to_string());
+        metrics
+            .http_request_duration
+            .with_label_values(&[&method, &route, response.status().as_str()])
+            .observe(started_at.elapsed().as_secs_f64());
+    }
+
+    Ok(response)
+}
@@ -1,7 +1,13 @@
 mod health_check;
 mod home;
+mod login;
+mod metrics;
 mod admin;
+mod api;
 
 pub use health_check::*;
 pub use home::*;
+pub use login::*;
+pub use metrics::*;
 pub use admin::*;
+pub use api::*;
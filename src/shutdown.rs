@@ -0,0 +1,72 @@
+use std::future::Future;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// Cancels `shutdown` on the first SIGTERM or SIGINT.
+pub async fn cancel_on_signal(shutdown: CancellationToken) -> Result<(), std::io::Error> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut sigterm = signal(SignalKind::terminate())?;
+        tokio::select! {
+            _ = sigterm.recv() => info!("Received SIGTERM"),
+            o = tokio::signal::ctrl_c() => { o?; info!("Received SIGINT") },
+            _ = shutdown.cancelled() => return Ok(()),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        tokio::select! {
+            o = tokio::signal::ctrl_c() => { o?; info!("Received SIGINT") },
+            _ = shutdown.cancelled() => return Ok(()),
+        }
+    }
+
+    shutdown.cancel();
+    Ok(())
+}
+
+/// Spawns a task that cancels `shutdown` when it exits, so that one task stopping
+/// stops all the others as well.
+pub fn spawn_until_shutdown<F, E>(
+    task_name: &'static str,
+    task: F,
+    shutdown: CancellationToken,
+) -> JoinHandle<()>
+where
+    F: Future<Output = Result<(), E>> + Send + 'static,
+    E: std::fmt::Debug + std::fmt::Display + Send + 'static,
+{
+    let handle = tokio::spawn(task);
+    tokio::spawn(async move {
+        crate::utils::report_exit(task_name, handle.await);
+        shutdown.cancel();
+    })
+}
+
+/// Waits for all the tasks to finish, giving them `deadline` to drain their work once
+/// `shutdown` is cancelled.
+pub async fn wait_for_tasks(
+    tasks: Vec<JoinHandle<()>>,
+    shutdown: CancellationToken,
+    deadline: Duration,
+) {
+    let all_tasks = async {
+        for task in tasks {
+            if let Err(err) = task.await {
+                warn!("Task failed to complete = {err:?}");
+            }
+        }
+    };
+
+    tokio::select! {
+        _ = all_tasks => info!("All tasks have exited"),
+        _ = async {
+            shutdown.cancelled().await;
+            tokio::time::sleep(deadline).await;
+        } => warn!("Tasks did not exit within the {deadline:?} shutdown deadline"),
+    }
+}
@@ -0,0 +1,265 @@
+use crate::configuration::SupervisorSettings;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, VecDeque};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkerState {
+    Running,
+    BackingOff,
+    /// The restart budget is exhausted, the worker is not restarted anymore.
+    Failed,
+    Stopped,
+}
+
+impl WorkerState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WorkerState::Running => "running",
+            WorkerState::BackingOff => "backing_off",
+            WorkerState::Failed => "failed",
+            WorkerState::Stopped => "stopped",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkerStatus {
+    pub state: WorkerState,
+    pub restarts: u32,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+}
+
+/// Runs workers and restarts them with an exponential backoff when they fail.
+///
+/// A worker is given up on once it was restarted `max_restarts` times within the restart
+/// window, the state of every worker is kept for health checks.
+#[derive(Clone)]
+pub struct Supervisor {
+    settings: SupervisorSettings,
+    workers: Arc<Mutex<BTreeMap<&'static str, WorkerStatus>>>,
+}
+
+impl Supervisor {
+    pub fn new(settings: SupervisorSettings) -> Self {
+        Self {
+            settings,
+            workers: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    pub fn status(&self, name: &str) -> Option<WorkerStatus> {
+        self.workers.lock().unwrap().get(name).cloned()
+    }
+
+    /// Statuses of all the workers by name.
+    pub fn statuses(&self) -> BTreeMap<&'static str, WorkerStatus> {
+        self.workers.lock().unwrap().clone()
+    }
+
+    /// Spawns the worker built by `make_worker` and builds a new one whenever it exits
+    /// before `shutdown` is cancelled.
+    pub fn spawn<F, Fut>(
+        &self,
+        name: &'static str,
+        make_worker: F,
+        shutdown: CancellationToken,
+    ) -> JoinHandle<()>
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), anyhow::Error>> + Send + 'static,
+    {
+        self.workers.lock().unwrap().insert(
+            name,
+            WorkerStatus {
+                state: WorkerState::Running,
+                restarts: 0,
+                last_error: None,
+                last_error_at: None,
+            },
+        );
+
+        tokio::spawn(self.clone().supervise(name, make_worker, shutdown))
+    }
+
+    async fn supervise<F, Fut>(
+        self,
+        name: &'static str,
+        mut make_worker: F,
+        shutdown: CancellationToken,
+    ) where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), anyhow::Error>> + Send + 'static,
+    {
+        let mut restarts_in_window = VecDeque::new();
+        let mut consecutive_failures = 0;
+
+        loop {
+            self.update(name, |status| status.state = WorkerState::Running);
+            let started_at = Instant::now();
+            // The worker runs in its own task so a panic is handled as a failure.
+            let outcome = tokio::spawn(make_worker()).await;
+
+            if shutdown.is_cancelled() {
+                crate::utils::report_exit(name, outcome);
+                self.update(name, |status| status.state = WorkerState::Stopped);
+                return;
+            }
+
+            let error = match outcome {
+                Ok(Ok(())) => "exited unexpectedly".to_string(),
+                Ok(Err(e)) => format!("{e:?}"),
+                Err(e) => format!("failed to complete: {e}"),
+            };
+            error!(error.cause_chain = %error, "{} failed", name);
+
+            let now = Instant::now();
+            restarts_in_window.retain(|restarted_at: &Instant| {
+                now.duration_since(*restarted_at) < self.settings.restart_window()
+            });
+            if restarts_in_window.len() >= self.settings.max_restarts {
+                error!(
+                    "{} was restarted {} times within {:?}, giving up",
+                    name,
+                    restarts_in_window.len(),
+                    self.settings.restart_window()
+                );
+                self.update(name, |status| {
+                    status.state = WorkerState::Failed;
+                    status.last_error = Some(error.clone());
+                    status.last_error_at = Some(Utc::now());
+                });
+                return;
+            }
+
+            // A worker that ran for a while is not failing in a loop, start over.
+            if started_at.elapsed() >= self.settings.max_backoff() {
+                consecutive_failures = 0;
+            }
+            consecutive_failures += 1;
+            let delay = backoff(&self.settings, consecutive_failures);
+            warn!("Restarting {} in {:?}", name, delay);
+            self.update(name, |status| {
+                status.state = WorkerState::BackingOff;
+                status.last_error = Some(error.clone());
+                status.last_error_at = Some(Utc::now());
+            });
+
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = shutdown.cancelled() => {
+                    self.update(name, |status| status.state = WorkerState::Stopped);
+                    return;
+                }
+            }
+
+            restarts_in_window.push_back(Instant::now());
+            self.update(name, |status| status.restarts += 1);
+            info!("Restarting {}", name);
+        }
+    }
+
+    fn update(&self, name: &'static str, f: impl FnOnce(&mut WorkerStatus)) {
+        if let Some(status) = self.workers.lock().unwrap().get_mut(name) {
+            f(status);
+        }
+    }
+}
+
+/// Delay before the restart that follows `consecutive_failures` failures in a row.
+fn backoff(settings: &SupervisorSettings, consecutive_failures: u32) -> Duration {
+    let factor = 2u32.saturating_pow(consecutive_failures.saturating_sub(1));
+    std::cmp::min(
+        settings.initial_backoff().saturating_mul(factor),
+        settings.max_backoff(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backoff, Supervisor, WorkerState};
+    use crate::configuration::SupervisorSettings;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio_util::sync::CancellationToken;
+
+    fn settings() -> SupervisorSettings {
+        SupervisorSettings {
+            initial_backoff_milliseconds: 1,
+            max_backoff_milliseconds: 8,
+            max_restarts: 3,
+            restart_window_seconds: 60,
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let delays: Vec<u128> = (1..=5)
+            .map(|failures| backoff(&settings(), failures).as_millis())
+            .collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 8]);
+    }
+
+    #[tokio::test]
+    async fn failing_worker_is_restarted_until_the_budget_is_exhausted() {
+        let supervisor = Supervisor::new(settings());
+        let starts = Arc::new(AtomicU32::new(0));
+
+        let worker_starts = starts.clone();
+        supervisor
+            .spawn(
+                "worker",
+                move || {
+                    worker_starts.fetch_add(1, Ordering::SeqCst);
+                    async { Err(anyhow::anyhow!("broken")) }
+                },
+                CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+
+        let status = supervisor.status("worker").unwrap();
+        assert_eq!(status.state, WorkerState::Failed);
+        assert_eq!(status.restarts, 3);
+        assert!(status.last_error.unwrap().contains("broken"));
+        assert_eq!(starts.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn worker_is_stopped_on_shutdown() {
+        let supervisor = Supervisor::new(settings());
+        let shutdown = CancellationToken::new();
+
+        let worker_shutdown = shutdown.clone();
+        let handle = supervisor.spawn(
+            "worker",
+            move || {
+                let shutdown = worker_shutdown.clone();
+                async move {
+                    shutdown.cancelled().await;
+                    Ok(())
+                }
+            },
+            shutdown.clone(),
+        );
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(
+            supervisor.status("worker").unwrap().state,
+            WorkerState::Running
+        );
+
+        shutdown.cancel();
+        handle.await.unwrap();
+
+        let status = supervisor.status("worker").unwrap();
+        assert_eq!(status.state, WorkerState::Stopped);
+        assert_eq!(status.restarts, 0);
+    }
+}
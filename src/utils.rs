@@ -26,6 +26,15 @@ pub fn see_other(location: &str) -> HttpResponse {
         .finish()
 }
 
+// Escape text coming from devices before it is interpolated into an html page.
+pub fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
 pub fn error_chain_fmt(
     e: &impl std::error::Error,
     f: &mut std::fmt::Formatter<'_>,
@@ -1,5 +1,9 @@
+pub mod mqtt_session;
 pub mod mqtt_worker;
 pub mod subscriptions_worker;
+pub mod topic_registry;
 
+pub use mqtt_session::*;
 pub use mqtt_worker::*;
 pub use subscriptions_worker::*;
+pub use topic_registry::*;
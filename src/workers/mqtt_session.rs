@@ -0,0 +1,63 @@
+use chrono::Utc;
+use sqlx::PgPool;
+
+/// Topics subscribed in a persistent mqtt session.
+///
+/// The broker keeps the subscriptions of a session that is not clean across
+/// reconnections, they are stored under the client id so that only the topics that
+/// changed in the meantime have to be subscribed again.
+#[derive(Clone)]
+pub struct MqttSession {
+    pool: PgPool,
+    client_id: String,
+}
+
+impl MqttSession {
+    pub fn new(pool: PgPool, client_id: String) -> Self {
+        Self { pool, client_id }
+    }
+
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    /// Topics the broker holds for the session.
+    #[tracing::instrument(name = "Select mqtt session topics", skip(self), fields(client_id = %self.client_id))]
+    pub async fn topics(&self) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"
+        SELECT topic FROM mqtt_session_topics WHERE client_id = $1 ORDER BY topic
+        "#,
+            self.client_id
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Replaces the topics of the session.
+    #[tracing::instrument(name = "Store mqtt session topics", skip(self, topics), fields(client_id = %self.client_id))]
+    pub async fn store(&self, topics: &[String]) -> Result<(), sqlx::Error> {
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"DELETE FROM mqtt_session_topics WHERE client_id = $1"#,
+            self.client_id
+        )
+        .execute(&mut transaction)
+        .await?;
+
+        sqlx::query!(
+            r#"
+    INSERT INTO mqtt_session_topics (client_id, topic, updated_at)
+    SELECT $1, topic, $3 FROM UNNEST($2::text[]) AS topic
+            "#,
+            self.client_id,
+            topics,
+            Utc::now()
+        )
+        .execute(&mut transaction)
+        .await?;
+
+        transaction.commit().await
+    }
+}
@@ -1,101 +1,317 @@
 use crate::application::get_connection_pool;
-use crate::configuration::Settings;
-use crate::domain::HiveData;
+use crate::configuration::{InfluxDbSpoolSettings, Settings};
+use crate::domain::{Calibration, PayloadFormat, ReadingValidator, ViewSubscriberTopic};
+use crate::health::DependencyHealth;
+use crate::influxdb_batch_writer::InfluxDbBatchWriter;
 use crate::influxdb_client::InfluxDbClient;
+use crate::influxdb_spool::InfluxDbSpool;
+use crate::line_protocol::LinePoint;
+use crate::metrics::Metrics;
+use crate::mqtt;
+use crate::mqtt_client::{MqttClient, MqttEvent, MqttEventLoop, MqttMessage};
 use crate::utils;
-use crate::workers::{ActionType, SubscriptionTopicsNotificationPayload};
+use crate::workers::{
+    ActionType, MqttSession, SubscriptionTopicsNotificationPayload, SubscriptionsEvent,
+    TopicRegistry,
+};
+use chrono::{DateTime, Utc};
 use log::warn;
-use rumqttc::{AsyncClient, Event, EventLoop, Incoming, QoS};
+use rumqttc::QoS;
 use sqlx::PgPool;
+use std::collections::HashMap;
 use std::time::Duration;
-use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
 use tracing::{error, info};
+use uuid::Uuid;
+
+/// Name the worker is supervised and reported under.
+pub const MQTT_WORKER: &str = "Metrics/mqtt delivery worker";
+
+/// How the topics are subscribed.
+#[derive(Clone)]
+struct SubscriptionOptions {
+    qos: QoS,
+    /// Set when the broker keeps the subscriptions across connections.
+    session: Option<MqttSession>,
+    share_group: Option<String>,
+    /// Also subscribes the prefix of every subscription to find unknown devices.
+    discovery: bool,
+}
+
+impl SubscriptionOptions {
+    /// Filter `topic` is subscribed and unsubscribed with.
+    fn filter(&self, topic: &str) -> String {
+        mqtt::subscription_filter(self.share_group.as_deref(), topic)
+    }
+
+    /// Topics a subscription row is subscribed with, the discovery topic is counted once
+    /// per row of the prefix like any other topic.
+    ///
+    /// Brokers like mosquitto deliver a publish once per matching subscription, so the
+    /// single level device names covered by the discovery topic are not subscribed again.
+    fn topics(&self, topic_prefix: &str, device_name: &str) -> Vec<String> {
+        let discovery_topic = format!("{topic_prefix}/+");
+        if !self.discovery {
+            return vec![format!("{topic_prefix}/{device_name}")];
+        }
+        if device_name.contains(['/', '#']) {
+            return vec![format!("{topic_prefix}/{device_name}"), discovery_topic];
+        }
+        vec![discovery_topic]
+    }
+}
 
 pub async fn run_mqtt_worker_until_stopped(
     configuration: Settings,
-    rx: UnboundedReceiver<SubscriptionTopicsNotificationPayload>,
-    mqtt_client: AsyncClient,
-    mqtt_event_loop: EventLoop,
+    rx: broadcast::Receiver<SubscriptionsEvent>,
+    mqtt_client: MqttClient,
+    mqtt_event_loop: MqttEventLoop,
+    health: DependencyHealth,
+    metrics: Metrics,
+    shutdown: CancellationToken,
 ) -> Result<(), anyhow::Error> {
-    let connection_pool = get_connection_pool(&configuration.database);
-    let influxdb_client = configuration.influxdb.client();
-    mqtt_worker_loop(
-        connection_pool,
+    let outcome = mqtt_worker_loop(
         rx,
         mqtt_client,
         mqtt_event_loop,
-        influxdb_client,
+        configuration,
+        health.clone(),
+        metrics,
+        shutdown,
     )
-    .await
+    .await;
+    health.set_mqtt_connected(false);
+    outcome
 }
 
 #[tracing::instrument(
     name = "Mqtt worker loop",
-    skip(db_pool, rx, client, event_loop, influxdb_client)
+    skip(rx, client, event_loop, configuration, health, metrics, shutdown)
 )]
 async fn mqtt_worker_loop(
-    db_pool: PgPool,
-    rx: UnboundedReceiver<SubscriptionTopicsNotificationPayload>,
-    client: AsyncClient,
-    event_loop: EventLoop,
-    influxdb_client: InfluxDbClient,
+    rx: broadcast::Receiver<SubscriptionsEvent>,
+    client: MqttClient,
+    event_loop: MqttEventLoop,
+    configuration: Settings,
+    health: DependencyHealth,
+    metrics: Metrics,
+    shutdown: CancellationToken,
 ) -> Result<(), anyhow::Error> {
-    setup_initial_subscribers(db_pool.clone(), client.clone())
-        .await
-        .unwrap();
+    let db_pool = get_connection_pool(&configuration.database);
+    let influxdb_settings = configuration.influxdb;
+    let reconciliation_interval = configuration.mqtt.reconciliation_interval();
+    let options = SubscriptionOptions {
+        qos: mqtt::qos(&configuration.mqtt)?,
+        session: match (
+            &configuration.mqtt.client_id,
+            configuration.mqtt.clean_session,
+        ) {
+            (Some(client_id), false) => Some(MqttSession::new(db_pool.clone(), client_id.clone())),
+            _ => None,
+        },
+        share_group: mqtt::share_group(&configuration.mqtt),
+        discovery: configuration.mqtt.discovery_mode,
+    };
 
-    let notification_receiver = tokio::spawn(run_message_processor(
-        db_pool.clone(),
+    // Stops the message processor when any of the tasks below exits on its own.
+    let stop = shutdown.child_token();
+    let topic_registry = TopicRegistry::new().with_metrics(&metrics);
+    // The topics of a persistent session are subscribed once the broker tells whether it
+    // still holds the session.
+    if options.session.is_none() {
+        setup_initial_subscribers(
+            db_pool.clone(),
+            client.clone(),
+            topic_registry.clone(),
+            &options,
+        )
+        .await?;
+    }
+
+    let spool = InfluxDbSpool::new(db_pool.clone(), influxdb_settings.spool.max_points)
+        .with_metrics(&metrics);
+    let spool_settings = influxdb_settings.spool.clone();
+    let batch_settings = influxdb_settings.batch.clone();
+    let influxdb_client = influxdb_settings
+        .client()
+        .with_health(health.clone())
+        .with_metrics(metrics.clone());
+    let (influxdb_writer, batch_writer) =
+        InfluxDbBatchWriter::spawn(influxdb_client.clone(), spool.clone(), batch_settings);
+
+    let processor = PublishProcessor {
+        db_pool: db_pool.clone(),
+        influxdb_writer,
+        validator: ReadingValidator::new(configuration.validation),
+        metrics,
+        device_name_topic_level: configuration.mqtt.device_name_topic_level,
+        discovery: configuration.mqtt.discovery_mode,
+    };
+    let mut notification_receiver = tokio::spawn(run_message_processor(
+        processor,
         client.clone(),
         event_loop,
+        topic_registry.clone(),
+        options.clone(),
+        health,
+        stop.clone(),
+    ));
+    let mut subscriptions_change_listener = tokio::spawn(run_subscriptions_change_listener(
+        db_pool.clone(),
+        rx,
+        client.clone(),
+        topic_registry,
+        reconciliation_interval,
+        options,
+    ));
+    let mut spool_replayer = tokio::spawn(run_spool_replayer(
+        spool,
         influxdb_client,
+        spool_settings,
     ));
-    let subscriptions_change_listener =
-        tokio::spawn(run_subscriptions_change_listener(rx, client.clone()));
 
-    tokio::select! {
-        o = notification_receiver => utils::report_exit("Notification receiver", o),
-        o = subscriptions_change_listener =>  utils::report_exit("Subscriptions change listener", o),
+    let mut batch_writer = batch_writer;
+
+    // The message processor exits on shutdown, the other tasks only when they fail.
+    let batch_writer_exited = tokio::select! {
+        o = &mut notification_receiver => {
+            utils::report_exit("Notification receiver", o);
+            false
+        }
+        o = &mut subscriptions_change_listener => {
+            utils::report_exit("Subscriptions change listener", o);
+            false
+        }
+        o = &mut batch_writer => {
+            utils::report_exit("Influxdb batch writer", o);
+            true
+        }
+        o = &mut spool_replayer => {
+            utils::report_exit("Influxdb spool replayer", o);
+            false
+        }
+    };
+
+    // Once the message processor has sent the mqtt DISCONNECT it drops its influxdb
+    // writer, which makes the batch writer flush the pending points and exit.
+    stop.cancel();
+    subscriptions_change_listener.abort();
+    spool_replayer.abort();
+    if !notification_receiver.is_finished() {
+        utils::report_exit("Notification receiver", notification_receiver.await);
+    }
+    if !batch_writer_exited {
+        utils::report_exit("Influxdb batch writer", batch_writer.await);
     }
 
+    db_pool.close().await;
+    info!("Mqtt worker has stopped");
     Ok(())
 }
 
 #[tracing::instrument(
     name = "Processing mqtt message",
-    skip(db_pool, mqtt_client, mqtt_event_loop, influxdb_client)
+    skip(
+        processor,
+        mqtt_client,
+        mqtt_event_loop,
+        topic_registry,
+        options,
+        health,
+        shutdown
+    )
 )]
 async fn run_message_processor(
-    db_pool: PgPool,
-    mqtt_client: AsyncClient,
-    mut mqtt_event_loop: EventLoop,
-    influxdb_client: InfluxDbClient,
+    mut processor: PublishProcessor,
+    mqtt_client: MqttClient,
+    mut mqtt_event_loop: MqttEventLoop,
+    topic_registry: TopicRegistry,
+    options: SubscriptionOptions,
+    health: DependencyHealth,
+    shutdown: CancellationToken,
 ) -> Result<(), anyhow::Error> {
+    let db_pool = processor.db_pool.clone();
+    let mut disconnecting = false;
+
     loop {
-        let event = mqtt_event_loop.poll().await;
+        let event = tokio::select! {
+            event = mqtt_event_loop.poll() => event,
+            _ = shutdown.cancelled(), if !disconnecting => {
+                info!("Disconnecting from the mqtt broker");
+                disconnecting = true;
+                if let Err(err) = mqtt_client.try_disconnect() {
+                    error!("Error on disconnecting from the mqtt broker = {err:?}");
+                    return Ok(());
+                }
+                continue;
+            }
+        };
 
         match &event {
             Ok(notification) => match notification {
-                Event::Incoming(incoming) => {
-                    if let Incoming::Publish(publish) = incoming {
-                        match HiveData::try_from(publish.payload.to_vec()) {
-                            Ok(data) => {
-                                let _ = influxdb_client
-                                    .write(data.format_line_point().as_str())
-                                    .await;
-                            }
-                            Err(err) => {
-                                error!("Error during raw payload reading = {err:?}");
-                            }
-                        }
+                MqttEvent::Message(message) => processor.process(message).await?,
+                MqttEvent::Connected { session_present } => {
+                    info!("Connected to the mqtt broker");
+                    health.set_mqtt_connected(true);
+                    topic_registry.forget_sent_subscriptions();
+                    if options.session.is_some() {
+                        restore_session_subscriptions(
+                            &db_pool,
+                            &mqtt_client,
+                            &topic_registry,
+                            &options,
+                            *session_present,
+                        )
+                        .await
+                        .unwrap_or_else(|err| {
+                            error!("Error during mqtt session restore = {err:?}")
+                        });
                     }
                 }
-                Event::Outgoing(_) => {}
+                MqttEvent::SubscribeSent { pkid } => topic_registry.subscription_sent(*pkid),
+                // Only the topics the broker acknowledged are held by the session.
+                MqttEvent::Subscribed { pkid } => {
+                    if let Some(topic) = topic_registry.subscription_acknowledged(*pkid) {
+                        info!("The mqtt broker acknowledged subscription: {topic}");
+                        store_session_topics(&options, &topic_registry).await;
+                    }
+                }
+                MqttEvent::SubscribeFailed { pkid, reasons } => {
+                    let topic = topic_registry.subscription_refused(*pkid);
+                    error!("The mqtt broker refused subscription {topic:?} = {reasons:?}");
+                    store_session_topics(&options, &topic_registry).await;
+                }
+                MqttEvent::Disconnected => {
+                    info!("Disconnected from the mqtt broker");
+                    return Ok(());
+                }
+                MqttEvent::Other => {}
             },
+            Err(error) if disconnecting => {
+                health.set_mqtt_connected(false);
+                warn!("Connection lost while disconnecting from the mqtt broker = {error:?}");
+                return Ok(());
+            }
             Err(error) => {
+                health.set_mqtt_connected(false);
                 error!("Error during message receive = {error:?}");
-                tokio::time::sleep(Duration::from_secs(60)).await;
-                match setup_initial_subscribers(db_pool.clone(), mqtt_client.clone()).await {
+                tokio::select! {
+                    _ = tokio::time::sleep(Duration::from_secs(60)) => {}
+                    _ = shutdown.cancelled() => return Ok(()),
+                }
+                // A persistent session is restored on the next acknowledged connection.
+                if options.session.is_some() {
+                    continue;
+                }
+                match setup_initial_subscribers(
+                    db_pool.clone(),
+                    mqtt_client.clone(),
+                    topic_registry.clone(),
+                    &options,
+                )
+                .await
+                {
                     Ok(_) => info!("Initialized subscribers again"),
                     Err(error) => error!("Error during subscribers initializing = {error:?}"),
                 }
@@ -104,51 +320,628 @@ async fn run_message_processor(
     }
 }
 
-#[tracing::instrument(name = "Receiving subscriptions changes", skip(rx, client))]
+/// Turns incoming publishes into influxdb points.
+struct PublishProcessor {
+    db_pool: PgPool,
+    influxdb_writer: InfluxDbBatchWriter,
+    validator: ReadingValidator,
+    metrics: Metrics,
+    device_name_topic_level: usize,
+    discovery: bool,
+}
+
+impl PublishProcessor {
+    async fn process(&mut self, publish: &MqttMessage) -> Result<(), anyhow::Error> {
+        let received_at = Utc::now();
+
+        let subscription = match select_subscription_by_topic(&self.db_pool, &publish.topic).await
+        {
+            Ok(subscription) => subscription,
+            Err(err) => {
+                error!("Error during subscription lookup = {err:?}");
+                None
+            }
+        };
+
+        // The topics of wildcard subscriptions are unbounded, the metrics are labelled with
+        // the prefix of the subscription instead.
+        let topic_prefix = subscription.as_ref().map_or("unmatched", |subscription| {
+            subscription.topic_prefix.as_str()
+        });
+        self.metrics
+            .mqtt_messages_received
+            .with_label_values(&[topic_prefix])
+            .inc();
+
+        if subscription.is_none() && self.discovery {
+            self.discover_device(publish).await;
+            return Ok(());
+        }
+
+        let payload_format = match &subscription {
+            Some(subscription) => PayloadFormat::parse(subscription.payload_format.clone())
+                .unwrap_or_else(|err| {
+                    warn!("{err} Falling back to json for topic {}", publish.topic);
+                    PayloadFormat::Json
+                }),
+            None => {
+                warn!("No subscription found for topic {}", publish.topic);
+                PayloadFormat::Json
+            }
+        };
+
+        let mut reading = match payload_format.decoder().decode(&publish.payload) {
+            Ok(reading) => reading,
+            Err(err) => {
+                let decode_failures = self
+                    .metrics
+                    .mqtt_decode_failures
+                    .with_label_values(&[topic_prefix]);
+                decode_failures.inc();
+                error!(
+                    topic = %publish.topic,
+                    decode_errors = decode_failures.get(),
+                    "Error during {} payload decoding = {err:?}",
+                    payload_format.as_str()
+                );
+                return Ok(());
+            }
+        };
+
+        match &subscription {
+            Some(subscription) if subscription.is_wildcard() => {
+                if let Some(device_name) =
+                    publish.topic.split('/').nth(self.device_name_topic_level)
+                {
+                    reading
+                        .device_name
+                        .get_or_insert_with(|| device_name.to_string());
+                }
+            }
+            Some(subscription) => {
+                reading
+                    .device_name
+                    .get_or_insert_with(|| subscription.device_name.clone());
+            }
+            None => {}
+        }
+
+        // A wildcard subscription covers many devices, each publishing on its own topic.
+        let device_id = subscription
+            .as_ref()
+            .filter(|subscription| !subscription.is_wildcard())
+            .and_then(|subscription| subscription.device_id);
+        let device = match device_id {
+            Some(device_id) => device_id.to_string(),
+            None => publish.topic.clone(),
+        };
+
+        let taken_at = reading.timestamp.unwrap_or(received_at);
+        if let Err(reason) = self.validator.validate(&device, &reading, taken_at) {
+            warn!("Reading from {} is quarantined: {reason}", publish.topic);
+            let point = tag_user_properties(
+                tag_subscription(reading.line_point(received_at), subscription.as_ref()),
+                &publish.user_properties,
+            );
+            insert_quarantined_reading(
+                &self.db_pool,
+                &publish.topic,
+                reading.device_name.as_deref(),
+                &point.to_string(),
+                &reason,
+                received_at,
+            )
+            .await
+            .unwrap_or_else(|err| error!("Error during reading quarantine = {err:?}"));
+            return Ok(());
+        }
+
+        if let Some(device_id) = device_id {
+            match select_calibration_at(&self.db_pool, device_id, taken_at).await {
+                Ok(Some(calibration)) => calibration.apply(&mut reading),
+                Ok(None) => {}
+                Err(err) => error!("Error during calibration lookup = {err:?}"),
+            }
+        }
+
+        let point = tag_user_properties(
+            tag_subscription(reading.line_point(received_at), subscription.as_ref()),
+            &publish.user_properties,
+        );
+        self.influxdb_writer.write(point.to_string()).await
+    }
+
+    /// Records a device publishing a valid payload under the prefix of an organization as
+    /// pending, its readings are ignored until an admin approves it. The payload is decoded
+    /// with the format of the prefix.
+    async fn discover_device(&self, publish: &MqttMessage) {
+        let Some((topic_prefix, device_name)) = publish.topic.rsplit_once('/') else {
+            return;
+        };
+        let payload_format = match select_prefix_payload_format(&self.db_pool, topic_prefix).await {
+            Ok(Some(payload_format)) => {
+                PayloadFormat::parse(payload_format).unwrap_or_else(|err| {
+                    warn!("{err} Falling back to json for topic {}", publish.topic);
+                    PayloadFormat::Json
+                })
+            }
+            Ok(None) => {
+                warn!("No organization uses the topic prefix {topic_prefix}");
+                return;
+            }
+            Err(err) => {
+                error!("Error during topic prefix lookup = {err:?}");
+                return;
+            }
+        };
+        if let Err(err) = payload_format.decoder().decode(&publish.payload) {
+            warn!("Ignoring unknown device {}: {err}", publish.topic);
+            return;
+        }
+
+        match upsert_pending_device(&self.db_pool, topic_prefix, device_name, Utc::now()).await {
+            Ok(true) => info!("Device {} is pending approval", publish.topic),
+            Ok(false) => warn!("No organization uses the topic prefix {topic_prefix}"),
+            Err(err) => error!("Error during pending device upsert = {err:?}"),
+        }
+    }
+}
+
+/// A wildcard subscription does not identify a single device, so its points are only
+/// tagged with the organization and the prefix.
+fn tag_subscription(point: LinePoint, subscription: Option<&ViewSubscriberTopic>) -> LinePoint {
+    match subscription {
+        Some(subscription) => match subscription.device_id {
+            Some(device_id) if !subscription.is_wildcard() => point
+                .tag("organization_id", subscription.organization_id.to_string())
+                .tag("device_id", device_id.to_string())
+                .tag("topic_prefix", subscription.topic_prefix.as_str()),
+            _ => point
+                .tag("organization_id", subscription.organization_id.to_string())
+                .tag("topic_prefix", subscription.topic_prefix.as_str()),
+        },
+        None => point,
+    }
+}
+
+/// Adds the MQTT 5 user properties of a publish, like the firmware version, as tags.
+/// They cannot override the tags identifying the device.
+fn tag_user_properties(point: LinePoint, user_properties: &[(String, String)]) -> LinePoint {
+    const RESERVED_TAGS: [&str; 4] = [
+        "device_name",
+        "organization_id",
+        "device_id",
+        "topic_prefix",
+    ];
+
+    user_properties.iter().fold(point, |point, (key, value)| {
+        if RESERVED_TAGS.contains(&key.as_str()) || point.has_tag(key) {
+            warn!("Ignoring the user property {key} as it is already a tag");
+            return point;
+        }
+        point.tag(key, value)
+    })
+}
+
+/// The latest calibration version of a device that was valid when the reading was taken,
+/// so replayed readings are calibrated the same way as live ones.
+#[tracing::instrument(name = "Select calibration of a device", skip(pool))]
+async fn select_calibration_at(
+    pool: &PgPool,
+    device_id: Uuid,
+    taken_at: DateTime<Utc>,
+) -> Result<Option<Calibration>, sqlx::Error> {
+    sqlx::query_as!(
+        Calibration,
+        r#"
+        SELECT device_id, version, tare_offset, scale_factor, temperature_coefficient, reference_temperature, valid_from
+            FROM device_calibrations
+            WHERE device_id = $1 AND valid_from <= $2
+            ORDER BY version DESC
+            LIMIT 1
+        "#,
+        device_id,
+        taken_at
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+#[tracing::instrument(
+    name = "Insert a quarantined reading in the database",
+    skip(pool, line_point)
+)]
+async fn insert_quarantined_reading(
+    pool: &PgPool,
+    topic: &str,
+    device_name: Option<&str>,
+    line_point: &str,
+    reason: &str,
+    received_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+    INSERT INTO quarantined_readings (id, topic, device_name, line_point, reason, received_at, created_at)
+    VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        Uuid::new_v4(),
+        topic,
+        device_name,
+        line_point,
+        reason,
+        received_at,
+        Utc::now()
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Payload format of the oldest subscription of the prefix, the one `upsert_pending_device`
+/// takes the organization from.
+#[tracing::instrument(name = "Select payload format of a topic prefix", skip(pool))]
+async fn select_prefix_payload_format(
+    pool: &PgPool,
+    topic_prefix: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+    SELECT payload_format FROM subscriptions_topics
+        WHERE topic_prefix = $1
+        ORDER BY created_at
+        LIMIT 1
+            "#,
+        topic_prefix
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Returns whether the device was recorded, which needs a subscription of the prefix to
+/// know the organization of the device.
+#[tracing::instrument(name = "Upsert a pending device", skip(pool))]
+async fn upsert_pending_device(
+    pool: &PgPool,
+    topic_prefix: &str,
+    device_name: &str,
+    seen_at: DateTime<Utc>,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+    INSERT INTO pending_devices (id, organization_id, topic_prefix, device_name, first_seen_at, last_seen_at)
+    SELECT $1, organization_id, topic_prefix, $3, $4, $4
+        FROM subscriptions_topics
+        WHERE topic_prefix = $2
+        ORDER BY created_at
+        LIMIT 1
+    ON CONFLICT (topic_prefix, device_name) DO UPDATE
+        SET messages = pending_devices.messages + 1, last_seen_at = EXCLUDED.last_seen_at
+            "#,
+        Uuid::new_v4(),
+        topic_prefix,
+        device_name,
+        seen_at
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[tracing::instrument(
+    name = "Replaying influxdb spool",
+    skip(spool, influxdb_client, settings)
+)]
+async fn run_spool_replayer(
+    spool: InfluxDbSpool,
+    influxdb_client: InfluxDbClient,
+    settings: InfluxDbSpoolSettings,
+) -> Result<(), anyhow::Error> {
+    if let Err(err) = spool.refresh_depth().await {
+        warn!("Not able to read influxdb spool depth = {err:?}");
+    }
+
+    let mut backoff = settings.replay_interval();
+
+    loop {
+        tokio::time::sleep(backoff).await;
+
+        if spool.is_empty() {
+            continue;
+        }
+
+        match replay_spooled_points(&spool, &influxdb_client, settings.replay_batch_size).await {
+            Ok(_) => backoff = settings.replay_interval(),
+            Err(err) => {
+                backoff = std::cmp::min(backoff * 2, settings.max_backoff());
+                warn!(
+                    "Error during influxdb spool replay, next attempt in {backoff:?} = {err:?}"
+                );
+            }
+        }
+    }
+}
+
+async fn replay_spooled_points(
+    spool: &InfluxDbSpool,
+    influxdb_client: &InfluxDbClient,
+    batch_size: i64,
+) -> Result<(), anyhow::Error> {
+    loop {
+        let points = spool.oldest(batch_size).await?;
+        if points.is_empty() {
+            return Ok(());
+        }
+
+        let payload: Vec<&str> = points.iter().map(|point| point.payload.as_str()).collect();
+        influxdb_client.write_lines(&payload).await?;
+        spool.remove(&points).await?;
+    }
+}
+
+#[tracing::instrument(
+    name = "Receiving subscriptions changes",
+    skip(db_pool, rx, client, topic_registry, options)
+)]
 async fn run_subscriptions_change_listener(
-    mut rx: UnboundedReceiver<SubscriptionTopicsNotificationPayload>,
-    client: AsyncClient,
+    db_pool: PgPool,
+    mut rx: broadcast::Receiver<SubscriptionsEvent>,
+    client: MqttClient,
+    topic_registry: TopicRegistry,
+    reconciliation_interval: Duration,
+    options: SubscriptionOptions,
 ) -> Result<(), anyhow::Error> {
+    let mut reconciliation = tokio::time::interval(reconciliation_interval);
+    reconciliation.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    // The first tick completes immediately, the subscriptions were just set up.
+    reconciliation.tick().await;
+
     loop {
-        let payload = rx.recv().await.unwrap();
-        info!("received data {:?}", payload);
-
-        match payload.action_type {
-            ActionType::INSERT => {
-                match client.try_subscribe(
-                    format!("{}/{}", payload.topic_prefix, payload.device_name),
-                    QoS::AtLeastOnce,
-                ) {
-                    Ok(_) => info!(
-                        "added subscription: {}/{}",
-                        payload.topic_prefix, payload.device_name
-                    ),
-                    Err(err) => {
-                        error!("error on adding subscription: {err:?}")
-                    }
+        let reconcile = tokio::select! {
+            event = rx.recv() => match event {
+                Ok(SubscriptionsEvent::Changed(payload)) => {
+                    apply_subscription_change(&client, &topic_registry, payload, &options);
+                    store_session_topics(&options, &topic_registry).await;
+                    false
+                }
+                Ok(SubscriptionsEvent::ListenerReconnected) => {
+                    info!("Subscriptions listener reconnected, reconciling subscriptions");
+                    reconciliation.reset();
+                    true
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("Missed {missed} subscriptions changes, reconciling subscriptions");
+                    reconciliation.reset();
+                    true
                 }
+                Err(broadcast::error::RecvError::Closed) => {
+                    anyhow::bail!("Subscriptions events channel is closed")
+                }
+            },
+            _ = reconciliation.tick() => true,
+        };
+
+        if reconcile {
+            if let Err(err) =
+                reconcile_subscriptions(&db_pool, &client, &topic_registry, &options).await
+            {
+                error!("Error during subscriptions reconciliation = {err:?}");
             }
-            ActionType::DELETE => {
-                match client
-                    .try_unsubscribe(format!("{}/{}", payload.topic_prefix, payload.device_name))
-                {
-                    Ok(_) => info!(
-                        "removed subscription: {}/{}",
-                        payload.topic_prefix, payload.device_name
-                    ),
-                    Err(err) => {
-                        error!("error on removing subscription: {err:?}")
-                    }
+        }
+    }
+}
+
+fn apply_subscription_change(
+    client: &MqttClient,
+    topic_registry: &TopicRegistry,
+    payload: SubscriptionTopicsNotificationPayload,
+    options: &SubscriptionOptions,
+) {
+    info!("received data {:?}", payload);
+
+    let topics = options.topics(&payload.topic_prefix, &payload.device_name);
+    let old_topics = match (&payload.old_topic_prefix, &payload.old_device_name) {
+        (Some(topic_prefix), Some(device_name)) => Some(options.topics(topic_prefix, device_name)),
+        _ => None,
+    };
+
+    match payload.action_type {
+        ActionType::INSERT => {
+            for topic in &topics {
+                subscribe_topic(client, topic_registry, topic, options);
+            }
+        }
+        ActionType::UPDATE => match old_topics {
+            Some(old_topics) if old_topics != topics => {
+                // Topics shared by the old and the new values, like the discovery topic of
+                // an unchanged prefix, stay subscribed.
+                for topic in old_topics.iter().filter(|topic| !topics.contains(topic)) {
+                    unsubscribe_topic(client, topic_registry, topic, options);
+                }
+                for topic in topics.iter().filter(|topic| !old_topics.contains(topic)) {
+                    subscribe_topic(client, topic_registry, topic, options);
                 }
             }
-            _ => {
-                info!("not supported action");
+            Some(_) => info!("topic {} has not changed", payload.topic()),
+            None => warn!("update of {} without the old topic", payload.topic()),
+        },
+        ActionType::DELETE => {
+            for topic in old_topics.unwrap_or(topics) {
+                unsubscribe_topic(client, topic_registry, &topic, options);
             }
-        };
+        }
+    };
+}
+
+/// Subscribes the topics of the database that are missing in the registry and
+/// unsubscribes the ones that are not in the database anymore.
+#[tracing::instrument(
+    name = "Reconciling subscriptions",
+    skip(pool, client, topic_registry, options)
+)]
+async fn reconcile_subscriptions(
+    pool: &PgPool,
+    client: &MqttClient,
+    topic_registry: &TopicRegistry,
+    options: &SubscriptionOptions,
+) -> Result<(), anyhow::Error> {
+    let mut desired = HashMap::new();
+    for row in sqlx::query!(
+        r#"
+        SELECT topic_prefix, device_name
+            FROM subscriptions_topics
+        "#,
+    )
+    .fetch_all(pool)
+    .await?
+    {
+        for topic in options.topics(&row.topic_prefix, &row.device_name) {
+            *desired.entry(topic).or_insert(0) += 1;
+        }
+    }
+
+    let diff = topic_registry.reconcile(desired);
+
+    for topic in diff.missing {
+        match topic_registry.request_subscription(&topic, || {
+            client.try_subscribe(options.filter(&topic), options.qos)
+        }) {
+            Ok(_) => info!("reconciliation added missing subscription: {topic}"),
+            Err(err) => {
+                topic_registry.forget(&topic);
+                error!("error on adding missing subscription {topic}: {err:?}")
+            }
+        }
+    }
+    for topic in diff.stale {
+        match client.try_unsubscribe(options.filter(&topic)) {
+            Ok(_) => info!("reconciliation removed stale subscription: {topic}"),
+            Err(err) => error!("error on removing stale subscription {topic}: {err:?}"),
+        }
+    }
+
+    store_session_topics(options, topic_registry).await;
+    Ok(())
+}
+
+/// Subscribes the topics of a persistent session once connected, the topics the broker
+/// still holds for the session are not subscribed again.
+#[tracing::instrument(
+    name = "Restoring mqtt session subscriptions",
+    skip(pool, client, topic_registry, options)
+)]
+async fn restore_session_subscriptions(
+    pool: &PgPool,
+    client: &MqttClient,
+    topic_registry: &TopicRegistry,
+    options: &SubscriptionOptions,
+    session_present: bool,
+) -> Result<(), anyhow::Error> {
+    let session = match (&options.session, session_present) {
+        (Some(session), true) => session,
+        _ => {
+            info!("The broker holds no session, subscribing all topics");
+            return setup_initial_subscribers(
+                pool.clone(),
+                client.clone(),
+                topic_registry.clone(),
+                options,
+            )
+            .await;
+        }
+    };
+
+    let held_topics = session.topics().await?;
+    info!(
+        "The broker holds {} topics for the session of {}",
+        held_topics.len(),
+        session.client_id()
+    );
+    topic_registry.reconcile(held_topics.into_iter().map(|topic| (topic, 1)).collect());
+    // Only the topics that changed while disconnected are subscribed or unsubscribed.
+    reconcile_subscriptions(pool, client, topic_registry, options).await
+}
+
+/// Stores the topics the broker acknowledged as the topics of the session.
+async fn store_session_topics(options: &SubscriptionOptions, topic_registry: &TopicRegistry) {
+    if let Some(session) = &options.session {
+        if let Err(err) = session.store(&topic_registry.acknowledged_topics()).await {
+            error!("Error during mqtt session topics store = {err:?}");
+        }
+    }
+}
+
+/// Subscribes `topic` unless another subscription already did.
+fn subscribe_topic(
+    client: &MqttClient,
+    topic_registry: &TopicRegistry,
+    topic: &str,
+    options: &SubscriptionOptions,
+) {
+    if !topic_registry.add(topic) {
+        info!("topic {topic} is already subscribed");
+        return;
+    }
+
+    match topic_registry.request_subscription(topic, || {
+        client.try_subscribe(options.filter(topic), options.qos)
+    }) {
+        Ok(_) => info!("added subscription: {topic}"),
+        Err(err) => {
+            topic_registry.remove(topic);
+            error!("error on adding subscription: {err:?}")
+        }
     }
 }
 
-async fn setup_initial_subscribers(pool: PgPool, client: AsyncClient) -> Result<(), anyhow::Error> {
+/// Unsubscribes `topic` once no other subscription uses it.
+fn unsubscribe_topic(
+    client: &MqttClient,
+    topic_registry: &TopicRegistry,
+    topic: &str,
+    options: &SubscriptionOptions,
+) {
+    if !topic_registry.remove(topic) {
+        info!("topic {topic} is still subscribed");
+        return;
+    }
+
+    match client.try_unsubscribe(options.filter(topic)) {
+        Ok(_) => info!("removed subscription: {topic}"),
+        Err(err) => {
+            topic_registry.add(topic);
+            error!("error on removing subscription: {err:?}")
+        }
+    }
+}
+
+/// Device names may be wildcard patterns, so the subscriptions of the topic prefix are
+/// matched against the topic here.
+#[tracing::instrument(name = "Select subscription for a topic", skip(pool))]
+async fn select_subscription_by_topic(
+    pool: &PgPool,
+    topic: &str,
+) -> Result<Option<ViewSubscriberTopic>, sqlx::Error> {
+    let subscriptions = sqlx::query_as!(
+        ViewSubscriberTopic,
+        r#"
+        SELECT id, organization_id, device_id, device_name, topic_prefix, payload_format
+            FROM subscriptions_topics
+            WHERE starts_with($1, topic_prefix || '/')
+        "#,
+        topic
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(ViewSubscriberTopic::best_match(subscriptions, topic))
+}
+
+async fn setup_initial_subscribers(
+    pool: PgPool,
+    client: MqttClient,
+    topic_registry: TopicRegistry,
+    options: &SubscriptionOptions,
+) -> Result<(), anyhow::Error> {
     let mut transaction = pool.begin().await?;
 
     match sqlx::query!(
@@ -161,16 +954,11 @@ async fn setup_initial_subscribers(pool: PgPool, client: AsyncClient) -> Result<
     .await
     {
         Ok(subscriptions) => {
+            // The broker dropped the subscriptions with the session, so start over.
+            topic_registry.clear();
             for subscription in subscriptions {
-                match client.try_subscribe(
-                    format!("{}/{}", subscription.topic_prefix, subscription.device_name),
-                    QoS::AtLeastOnce,
-                ) {
-                    Ok(_) => info!(
-                        "added subscription: {}/{}",
-                        subscription.topic_prefix, subscription.device_name
-                    ),
-                    Err(err) => warn!("error on adding subscription: {err:?}"),
+                for topic in options.topics(&subscription.topic_prefix, &subscription.device_name) {
+                    subscribe_topic(&client, &topic_registry, &topic, options);
                 }
             }
         }
@@ -179,5 +967,47 @@ async fn setup_initial_subscribers(pool: PgPool, client: AsyncClient) -> Result<
         }
     }
 
+    store_session_topics(options, &topic_registry).await;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::SubscriptionOptions;
+    use rumqttc::QoS;
+
+    fn options(discovery: bool) -> SubscriptionOptions {
+        SubscriptionOptions {
+            qos: QoS::AtLeastOnce,
+            session: None,
+            share_group: None,
+            discovery,
+        }
+    }
+
+    #[test]
+    fn device_topics_are_subscribed_without_discovery() {
+        assert_eq!(
+            options(false).topics("apiary-7", "hive-1"),
+            vec!["apiary-7/hive-1"]
+        );
+    }
+
+    #[test]
+    fn discovery_topic_covers_the_single_level_device_names() {
+        for device_name in ["hive-1", "+"] {
+            assert_eq!(
+                options(true).topics("apiary-7", device_name),
+                vec!["apiary-7/+"]
+            );
+        }
+    }
+
+    #[test]
+    fn multi_level_device_names_are_subscribed_next_to_the_discovery_topic() {
+        assert_eq!(
+            options(true).topics("apiary-7", "hive-1/data"),
+            vec!["apiary-7/hive-1/data", "apiary-7/+"]
+        );
+    }
+}
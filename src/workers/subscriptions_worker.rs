@@ -1,52 +1,132 @@
 use crate::application::get_connection_pool;
 use crate::configuration::Settings;
+use crate::health::DependencyHealth;
+use crate::metrics::Metrics;
+use anyhow::Context;
 use serde::Deserialize;
 use sqlx::postgres::PgListener;
 use sqlx::PgPool;
 use std::fmt::Debug;
-use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
 use uuid::Uuid;
 
-#[derive(Deserialize, Debug)]
+/// Name the worker is supervised and reported under.
+pub const SUBSCRIPTIONS_WORKER: &str = "Table/subscriptions change listener";
+
+#[derive(Deserialize, Debug, Clone)]
 pub enum ActionType {
     INSERT,
     UPDATE,
     DELETE,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Debug, Clone)]
+pub enum SubscriptionsEvent {
+    Changed(SubscriptionTopicsNotificationPayload),
+    /// The listener (re)connected and may have missed notifications.
+    ListenerReconnected,
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct SubscriptionTopicsNotificationPayload {
     pub table: String,
     pub action_type: ActionType,
     pub organization_id: Uuid,
-    pub device_id: Uuid,
+    pub device_id: Option<Uuid>,
     pub device_name: String,
     pub topic_prefix: String,
+    // Values before the change, only sent for UPDATE and DELETE.
+    pub old_device_name: Option<String>,
+    pub old_topic_prefix: Option<String>,
+}
+
+impl SubscriptionTopicsNotificationPayload {
+    pub fn topic(&self) -> String {
+        format!("{}/{}", self.topic_prefix, self.device_name)
+    }
+
+    pub fn old_topic(&self) -> Option<String> {
+        match (&self.old_topic_prefix, &self.old_device_name) {
+            (Some(topic_prefix), Some(device_name)) => {
+                Some(format!("{topic_prefix}/{device_name}"))
+            }
+            _ => None,
+        }
+    }
 }
 
-#[tracing::instrument(name = "Subscription worker loop", skip(configuration, tx))]
+#[tracing::instrument(
+    name = "Subscription worker loop",
+    skip(configuration, tx, health, metrics, shutdown)
+)]
 pub async fn run_subscription_worker_until_stopped(
     configuration: Settings,
-    tx: UnboundedSender<SubscriptionTopicsNotificationPayload>,
+    tx: broadcast::Sender<SubscriptionsEvent>,
+    health: DependencyHealth,
+    metrics: Metrics,
+    shutdown: CancellationToken,
 ) -> Result<(), anyhow::Error> {
     let connection_pool = get_connection_pool(&configuration.database);
-    subscription_worker_loop(&connection_pool, tx).await
+    let outcome = subscription_worker_loop(&connection_pool, tx, &health, &metrics, shutdown).await;
+    health.set_subscriptions_listener_connected(false);
+    connection_pool.close().await;
+    outcome
 }
 
 async fn subscription_worker_loop(
     db_pool: &PgPool,
-    tx: UnboundedSender<SubscriptionTopicsNotificationPayload>,
+    tx: broadcast::Sender<SubscriptionsEvent>,
+    health: &DependencyHealth,
+    metrics: &Metrics,
+    shutdown: CancellationToken,
 ) -> Result<(), anyhow::Error> {
-    let mut listener = PgListener::connect_with(db_pool).await.unwrap();
+    let mut listener = PgListener::connect_with(db_pool)
+        .await
+        .context("Failed to connect the subscriptions listener")?;
     listener.listen_all(vec!["subscriptions_topics"]).await?;
+    health.set_subscriptions_listener_connected(true);
+    // The supervisor restarts the loop after errors, the notifications sent meanwhile
+    // are missed as well as on a reconnect.
+    send_event(&tx, SubscriptionsEvent::ListenerReconnected);
 
     loop {
-        while let Some(notification) = listener.try_recv().await? {
-            let raw_payload = notification.payload().to_owned();
-            let payload =
-                serde_json::from_str::<SubscriptionTopicsNotificationPayload>(&raw_payload)
-                    .unwrap();
-            tx.send(payload).unwrap();
+        let notification = tokio::select! {
+            notification = listener.try_recv() => notification?,
+            _ = shutdown.cancelled() => break,
+        };
+
+        match notification {
+            Some(notification) => {
+                let raw_payload = notification.payload();
+                match serde_json::from_str::<SubscriptionTopicsNotificationPayload>(raw_payload) {
+                    Ok(payload) => send_event(&tx, SubscriptionsEvent::Changed(payload)),
+                    Err(err) => warn!("Invalid subscriptions notification {raw_payload} = {err:?}"),
+                }
+            }
+            None => {
+                // The connection was lost, reconnect right away so the reconciliation
+                // only runs once new notifications are received again.
+                warn!("Subscriptions listener lost its connection, reconnecting");
+                health.set_subscriptions_listener_connected(false);
+                listener.listen_all(vec!["subscriptions_topics"]).await?;
+                health.set_subscriptions_listener_connected(true);
+                metrics.subscriptions_listener_reconnects.inc();
+                send_event(&tx, SubscriptionsEvent::ListenerReconnected);
+            }
         }
     }
+
+    drop(listener);
+    info!("Subscriptions listener is closed");
+    Ok(())
+}
+
+// Nobody listens while the mqtt worker is restarting, it reconciles its subscriptions
+// with the database once it is running again.
+fn send_event(tx: &broadcast::Sender<SubscriptionsEvent>, event: SubscriptionsEvent) {
+    if tx.send(event).is_err() {
+        warn!("No mqtt worker is receiving subscriptions changes");
+    }
 }
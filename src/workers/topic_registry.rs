@@ -0,0 +1,314 @@
+use crate::metrics::Metrics;
+use prometheus::IntGauge;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Topics to subscribe and unsubscribe to match the database.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TopicsDiff {
+    pub missing: Vec<String>,
+    pub stale: Vec<String>,
+}
+
+/// Topics the mqtt client is subscribed to.
+///
+/// A topic is unique per subscription row, but in discovery mode the rows of an apiary
+/// share its `{prefix}/+` topic, so every topic is counted and only subscribed for the
+/// first row and unsubscribed with the last one.
+#[derive(Clone, Default, Debug)]
+pub struct TopicRegistry {
+    topics: Arc<Mutex<HashMap<String, usize>>>,
+    pending: Arc<Mutex<PendingSubscriptions>>,
+    active_subscriptions: Option<IntGauge>,
+}
+
+/// Subscribe requests waiting for the broker to acknowledge them.
+///
+/// The event loop only reports the packet id of a sent request, every request subscribes
+/// a single topic and is sent in the order it was queued in.
+#[derive(Debug, Default)]
+struct PendingSubscriptions {
+    queued: VecDeque<String>,
+    sent: HashMap<u16, String>,
+}
+
+impl PendingSubscriptions {
+    fn contains(&self, topic: &str) -> bool {
+        self.queued.iter().any(|queued| queued == topic)
+            || self.sent.values().any(|sent| sent == topic)
+    }
+}
+
+impl TopicRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps the number of subscribed topics in the active subscriptions gauge.
+    pub fn with_metrics(mut self, metrics: &Metrics) -> Self {
+        self.active_subscriptions = Some(metrics.active_subscriptions.clone());
+        self
+    }
+
+    /// Registers a subscription for `topic`, returns `true` when the topic is new and
+    /// has to be subscribed.
+    pub fn add(&self, topic: &str) -> bool {
+        let mut topics = self.topics.lock().unwrap();
+        let count = topics.entry(topic.to_string()).or_default();
+        *count += 1;
+        let added = *count == 1;
+        self.update_gauge(&topics);
+        added
+    }
+
+    /// Unregisters a subscription for `topic`, returns `true` when it was the last one
+    /// and the topic has to be unsubscribed.
+    pub fn remove(&self, topic: &str) -> bool {
+        let mut topics = self.topics.lock().unwrap();
+        match topics.get_mut(topic) {
+            Some(count) if *count > 1 => {
+                *count -= 1;
+                false
+            }
+            Some(_) => {
+                topics.remove(topic);
+                self.update_gauge(&topics);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn contains(&self, topic: &str) -> bool {
+        self.topics.lock().unwrap().contains_key(topic)
+    }
+
+    /// Active topics in alphabetical order.
+    pub fn topics(&self) -> Vec<String> {
+        let mut topics: Vec<String> = self.topics.lock().unwrap().keys().cloned().collect();
+        topics.sort();
+        topics
+    }
+
+    /// Topics the broker acknowledged the subscription of, in alphabetical order.
+    pub fn acknowledged_topics(&self) -> Vec<String> {
+        let topics = self.topics();
+        let pending = self.pending.lock().unwrap();
+        topics
+            .into_iter()
+            .filter(|topic| !pending.contains(topic))
+            .collect()
+    }
+
+    /// Queues the subscribe request of `topic` with `subscribe` to match its packet id once
+    /// sent, the lock keeps the requests of concurrent callers in the queue order.
+    pub fn request_subscription<E>(
+        &self,
+        topic: &str,
+        subscribe: impl FnOnce() -> Result<(), E>,
+    ) -> Result<(), E> {
+        let mut pending = self.pending.lock().unwrap();
+        subscribe()?;
+        pending.queued.push_back(topic.to_string());
+        Ok(())
+    }
+
+    /// Records the packet id of the oldest queued subscribe request, sent by the event loop.
+    pub fn subscription_sent(&self, pkid: u16) {
+        let mut pending = self.pending.lock().unwrap();
+        if let Some(topic) = pending.queued.pop_front() {
+            pending.sent.insert(pkid, topic);
+        }
+    }
+
+    /// Returns the topic of the subscribe request the broker acknowledged.
+    pub fn subscription_acknowledged(&self, pkid: u16) -> Option<String> {
+        self.pending.lock().unwrap().sent.remove(&pkid)
+    }
+
+    /// Returns the topic of the subscribe request the broker refused, it is not subscribed
+    /// anymore.
+    pub fn subscription_refused(&self, pkid: u16) -> Option<String> {
+        let topic = self.pending.lock().unwrap().sent.remove(&pkid)?;
+        self.forget(&topic);
+        Some(topic)
+    }
+
+    /// The broker does not answer the requests sent over a previous connection.
+    pub fn forget_sent_subscriptions(&self) {
+        self.pending.lock().unwrap().sent.clear();
+    }
+
+    pub fn clear(&self) {
+        let mut topics = self.topics.lock().unwrap();
+        topics.clear();
+        self.update_gauge(&topics);
+    }
+
+    /// Drops `topic` whatever the number of subscriptions registered for it.
+    pub fn forget(&self, topic: &str) {
+        let mut topics = self.topics.lock().unwrap();
+        topics.remove(topic);
+        self.update_gauge(&topics);
+    }
+
+    /// Replaces the registered subscriptions with the `desired` number of subscriptions
+    /// per topic and returns the topics that have to be subscribed or unsubscribed.
+    pub fn reconcile(&self, desired: HashMap<String, usize>) -> TopicsDiff {
+        let mut topics = self.topics.lock().unwrap();
+
+        let mut missing: Vec<String> = desired
+            .keys()
+            .filter(|topic| !topics.contains_key(*topic))
+            .cloned()
+            .collect();
+        let mut stale: Vec<String> = topics
+            .keys()
+            .filter(|topic| !desired.contains_key(*topic))
+            .cloned()
+            .collect();
+        missing.sort();
+        stale.sort();
+
+        *topics = desired;
+        self.update_gauge(&topics);
+        TopicsDiff { missing, stale }
+    }
+
+    fn update_gauge(&self, topics: &HashMap<String, usize>) {
+        if let Some(gauge) = &self.active_subscriptions {
+            gauge.set(topics.len() as i64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TopicRegistry, TopicsDiff};
+    use crate::metrics::Metrics;
+    use std::collections::HashMap;
+
+    #[test]
+    fn topic_is_subscribed_once_for_several_subscriptions() {
+        let registry = TopicRegistry::new();
+
+        assert!(registry.add("apiary-1/hive-1"));
+        assert!(!registry.add("apiary-1/hive-1"));
+        assert!(!registry.remove("apiary-1/hive-1"));
+        assert!(registry.contains("apiary-1/hive-1"));
+        assert!(registry.remove("apiary-1/hive-1"));
+        assert!(!registry.contains("apiary-1/hive-1"));
+    }
+
+    #[test]
+    fn removing_an_unknown_topic_is_a_no_op() {
+        let registry = TopicRegistry::new();
+        assert!(!registry.remove("apiary-1/hive-1"));
+    }
+
+    #[test]
+    fn reconcile_returns_missing_and_stale_topics() {
+        let registry = TopicRegistry::new();
+        registry.add("apiary-1/hive-1");
+        registry.add("apiary-1/hive-2");
+
+        let diff = registry.reconcile(HashMap::from([
+            ("apiary-1/hive-1".to_string(), 2),
+            ("apiary-1/hive-3".to_string(), 1),
+        ]));
+
+        assert_eq!(
+            diff,
+            TopicsDiff {
+                missing: vec!["apiary-1/hive-3".to_string()],
+                stale: vec!["apiary-1/hive-2".to_string()],
+            }
+        );
+        assert_eq!(
+            registry.topics(),
+            vec!["apiary-1/hive-1", "apiary-1/hive-3"]
+        );
+        // The subscription count is taken from the database as well.
+        assert!(!registry.remove("apiary-1/hive-1"));
+    }
+
+    #[test]
+    fn topics_are_listed_in_order() {
+        let registry = TopicRegistry::new();
+        registry.add("apiary-2/hive-1");
+        registry.add("apiary-1/hive-1");
+
+        assert_eq!(
+            registry.topics(),
+            vec!["apiary-1/hive-1", "apiary-2/hive-1"]
+        );
+    }
+
+    #[test]
+    fn active_subscriptions_gauge_follows_the_topics() {
+        let metrics = Metrics::new();
+        let registry = TopicRegistry::new().with_metrics(&metrics);
+
+        registry.add("apiary-1/hive-1");
+        registry.add("apiary-1/hive-1");
+        registry.add("apiary-1/hive-2");
+        assert_eq!(metrics.active_subscriptions.get(), 2);
+
+        registry.remove("apiary-1/hive-1");
+        assert_eq!(metrics.active_subscriptions.get(), 2);
+        registry.remove("apiary-1/hive-1");
+        assert_eq!(metrics.active_subscriptions.get(), 1);
+
+        registry.clear();
+        assert_eq!(metrics.active_subscriptions.get(), 0);
+    }
+
+    #[test]
+    fn topics_are_acknowledged_by_the_packet_id_of_their_request() {
+        let registry = TopicRegistry::new();
+        for topic in ["apiary-1/hive-1", "apiary-1/hive-2"] {
+            registry.add(topic);
+            registry
+                .request_subscription(topic, || Ok::<_, ()>(()))
+                .unwrap();
+        }
+        registry.subscription_sent(7);
+        registry.subscription_sent(8);
+        assert!(registry.acknowledged_topics().is_empty());
+
+        assert_eq!(
+            registry.subscription_acknowledged(8).as_deref(),
+            Some("apiary-1/hive-2")
+        );
+        assert_eq!(registry.acknowledged_topics(), vec!["apiary-1/hive-2"]);
+    }
+
+    #[test]
+    fn refused_topics_are_not_subscribed_anymore() {
+        let registry = TopicRegistry::new();
+        registry.add("apiary-1/hive-1");
+        registry
+            .request_subscription("apiary-1/hive-1", || Ok::<_, ()>(()))
+            .unwrap();
+        registry.subscription_sent(1);
+
+        assert_eq!(
+            registry.subscription_refused(1).as_deref(),
+            Some("apiary-1/hive-1")
+        );
+        assert!(!registry.contains("apiary-1/hive-1"));
+        assert!(registry.acknowledged_topics().is_empty());
+    }
+
+    #[test]
+    fn failed_subscribe_requests_are_not_queued() {
+        let registry = TopicRegistry::new();
+        registry.add("apiary-1/hive-1");
+
+        assert!(registry
+            .request_subscription("apiary-1/hive-1", || Err(()))
+            .is_err());
+        registry.subscription_sent(1);
+        assert_eq!(registry.subscription_acknowledged(1), None);
+    }
+}
@@ -0,0 +1,285 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp, TestUser};
+use beesbuddy_bumblebee::domain::Role;
+use chrono::Utc;
+use uuid::Uuid;
+
+fn topic_body(organization_id: Uuid, device_id: Uuid) -> String {
+    serde_urlencoded::to_string(serde_json::json!({
+        "organization_id": organization_id.to_string(),
+        "device_id": device_id.to_string(),
+        "device_name": "hive-1",
+        "topic_prefix": "apiary-1",
+        "payload_format": "json"
+    }))
+    .unwrap()
+}
+
+async fn insert_topic(app: &TestApp, organization_id: Uuid, device_name: &str) -> Uuid {
+    let device_id = app.register_device(organization_id, device_name).await;
+    sqlx::query!(
+        r#"
+    INSERT INTO subscriptions_topics (id, organization_id, device_id, device_name, topic_prefix, payload_format, created_at, updated_at)
+    VALUES ($1, $2, $3, $4, 'apiary-1', 'json', $5, $5)
+    "#,
+        Uuid::new_v4(),
+        organization_id,
+        device_id,
+        device_name,
+        Utc::now()
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    device_id
+}
+
+async fn insert_pending_device(app: &TestApp, organization_id: Uuid) -> Uuid {
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+    INSERT INTO pending_devices (id, organization_id, topic_prefix, device_name, first_seen_at, last_seen_at)
+    VALUES ($1, $2, 'apiary-1', $3, $4, $4)
+    "#,
+        id,
+        organization_id,
+        format!("hive-{id}"),
+        Utc::now()
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    id
+}
+
+#[tokio::test]
+async fn members_only_see_the_topics_of_their_organizations() {
+    let app = spawn_app().await;
+    let (apiary, other_apiary) = (
+        app.register_organization().await,
+        app.register_organization().await,
+    );
+    insert_topic(&app, apiary, "hive-1").await;
+    insert_topic(&app, other_apiary, "hive-2").await;
+
+    TestUser::member_of(&app, apiary, Role::Beekeeper)
+        .await
+        .login(&app)
+        .await;
+    let html_page = app.get_admin_subscriptions_topics_html().await;
+
+    assert!(html_page.contains("topic: apiary-1/hive-1"));
+    assert!(!html_page.contains("topic: apiary-1/hive-2"));
+}
+
+#[tokio::test]
+async fn superadmins_see_the_topics_of_every_organization() {
+    let app = spawn_app().await;
+    insert_topic(&app, app.register_organization().await, "hive-1").await;
+    insert_topic(&app, app.register_organization().await, "hive-2").await;
+
+    app.test_user.login(&app).await;
+    let html_page = app.get_admin_subscriptions_topics_html().await;
+
+    assert!(html_page.contains("topic: apiary-1/hive-1"));
+    assert!(html_page.contains("topic: apiary-1/hive-2"));
+}
+
+#[tokio::test]
+async fn organization_admins_cannot_subscribe_topics_of_other_organizations() {
+    let app = spawn_app().await;
+    let (apiary, other_apiary) = (
+        app.register_organization().await,
+        app.register_organization().await,
+    );
+    TestUser::member_of(&app, apiary, Role::Admin)
+        .await
+        .login(&app)
+        .await;
+
+    let other_device_id = app.register_device(other_apiary, "hive-2").await;
+    let response = app
+        .post_subscriptions_topics(topic_body(other_apiary, other_device_id))
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    let device_id = app.register_device(apiary, "hive-1").await;
+    let response = app
+        .post_subscriptions_topics(topic_body(apiary, device_id))
+        .await;
+    assert_is_redirect_to(&response, "/admin/subscriptions/topics/view");
+
+    let organizations = sqlx::query_scalar!("SELECT organization_id FROM subscriptions_topics")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(organizations, vec![apiary]);
+}
+
+#[tokio::test]
+async fn beekeepers_cannot_subscribe_topics() {
+    let app = spawn_app().await;
+    let apiary = app.register_organization().await;
+    TestUser::member_of(&app, apiary, Role::Beekeeper)
+        .await
+        .login(&app)
+        .await;
+
+    let device_id = app.register_device(apiary, "hive-1").await;
+    let response = app
+        .post_subscriptions_topics(topic_body(apiary, device_id))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn pending_devices_of_other_organizations_are_hidden() {
+    let app = spawn_app().await;
+    let (apiary, other_apiary) = (
+        app.register_organization().await,
+        app.register_organization().await,
+    );
+    let id = insert_pending_device(&app, other_apiary).await;
+    TestUser::member_of(&app, apiary, Role::Admin)
+        .await
+        .login(&app)
+        .await;
+
+    let html_page = app.get_admin_pending_devices_html().await;
+    assert!(!html_page.contains(&id.to_string()));
+
+    for action in ["approve", "reject"] {
+        let response = app.post_pending_device(id, action).await;
+        assert_eq!(response.status().as_u16(), 404);
+    }
+    let status = sqlx::query_scalar!("SELECT status FROM pending_devices WHERE id = $1", id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "pending");
+}
+
+#[tokio::test]
+async fn beekeepers_cannot_approve_pending_devices() {
+    let app = spawn_app().await;
+    let apiary = app.register_organization().await;
+    let id = insert_pending_device(&app, apiary).await;
+    TestUser::member_of(&app, apiary, Role::Beekeeper)
+        .await
+        .login(&app)
+        .await;
+
+    let html_page = app.get_admin_pending_devices_html().await;
+    assert!(html_page.contains(&id.to_string()));
+
+    let response = app.post_pending_device(id, "approve").await;
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn organization_admins_cannot_calibrate_devices_of_other_organizations() {
+    let app = spawn_app().await;
+    let (apiary, other_apiary) = (
+        app.register_organization().await,
+        app.register_organization().await,
+    );
+    let device_id = app.register_device(apiary, "hive-1").await;
+    let other_device_id = app.register_device(other_apiary, "hive-2").await;
+    TestUser::member_of(&app, apiary, Role::Admin)
+        .await
+        .login(&app)
+        .await;
+
+    for (device_id, status) in [(other_device_id, 403), (device_id, 303)] {
+        let body = serde_urlencoded::to_string(serde_json::json!({
+            "device_id": device_id.to_string(),
+            "tare_offset": "0",
+            "scale_factor": "1",
+            "temperature_coefficient": "0",
+            "reference_temperature": "20",
+            "valid_from": ""
+        }))
+        .unwrap();
+        let response = app.post_calibration(body).await;
+        assert_eq!(response.status().as_u16(), status);
+    }
+
+    let html_page = app.get_admin_calibrations_html().await;
+    assert!(html_page.contains(&format!("hive {device_id} version 1")));
+    assert!(!html_page.contains(&other_device_id.to_string()));
+}
+
+#[tokio::test]
+async fn only_superadmins_see_quarantined_readings() {
+    let app = spawn_app().await;
+    TestUser::member_of(&app, app.register_organization().await, Role::Admin)
+        .await
+        .login(&app)
+        .await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/quarantine/readings/view", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn only_superadmins_register_apiaries() {
+    let app = spawn_app().await;
+    let body = serde_urlencoded::to_string(serde_json::json!({
+        "name": "Meadow apiary",
+        "location": "north field",
+        "description": ""
+    }))
+    .unwrap();
+
+    TestUser::member_of(&app, app.register_organization().await, Role::Admin)
+        .await
+        .login(&app)
+        .await;
+    let response = app.post_organization(body.clone()).await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    app.test_user.login(&app).await;
+    let response = app.post_organization(body).await;
+    assert_is_redirect_to(&response, "/admin/organizations/view");
+    let html_page = app.get_admin_organizations_html().await;
+    assert!(html_page.contains("Meadow apiary"));
+}
+
+#[tokio::test]
+async fn organization_admins_register_hives_in_their_organizations_only() {
+    let app = spawn_app().await;
+    let (apiary, other_apiary) = (
+        app.register_organization().await,
+        app.register_organization().await,
+    );
+    TestUser::member_of(&app, apiary, Role::Admin)
+        .await
+        .login(&app)
+        .await;
+
+    for (organization_id, status) in [(other_apiary, 403), (apiary, 303)] {
+        let body = serde_urlencoded::to_string(serde_json::json!({
+            "organization_id": organization_id.to_string(),
+            "name": "hive-1",
+            "location": "",
+            "description": "south row"
+        }))
+        .unwrap();
+        let response = app.post_device(body).await;
+        assert_eq!(response.status().as_u16(), status);
+    }
+
+    let html_page = app.get_admin_devices_html().await;
+    assert!(html_page.contains("hive-1 ("));
+    let organizations = sqlx::query_scalar!("SELECT organization_id FROM devices")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(organizations, vec![apiary]);
+}
@@ -0,0 +1,82 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use uuid::Uuid;
+
+fn calibration_body(device_id: Uuid, scale_factor: &str) -> String {
+    serde_urlencoded::to_string(serde_json::json!({
+        "device_id": device_id.to_string(),
+        "tare_offset": "1000",
+        "scale_factor": scale_factor,
+        "temperature_coefficient": "0.01",
+        "reference_temperature": "20",
+        "valid_from": "2023-06-01T00:00:00Z"
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn admin_calibrations_are_saved_as_new_versions() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let device_id = Uuid::new_v4();
+
+    for scale_factor in ["0.001", "0.002"] {
+        let response = app
+            .post_calibration(calibration_body(device_id, scale_factor))
+            .await;
+        assert_is_redirect_to(&response, "/admin/calibrations/view");
+    }
+
+    let versions = sqlx::query!(
+        "SELECT version, scale_factor FROM device_calibrations WHERE device_id = $1 ORDER BY version",
+        device_id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+
+    assert_eq!(versions.len(), 2);
+    assert_eq!((versions[0].version, versions[0].scale_factor), (1, 0.001));
+    assert_eq!((versions[1].version, versions[1].scale_factor), (2, 0.002));
+
+    let html_page = app.get_admin_calibrations_html().await;
+    assert!(html_page.contains("Calibration version 2 has been saved."));
+    assert!(html_page.contains(&format!("hive {} version 1", device_id)));
+}
+
+#[tokio::test]
+async fn concurrent_calibrations_of_a_hive_get_distinct_versions() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let device_id = Uuid::new_v4();
+
+    let responses = tokio::join!(
+        app.post_calibration(calibration_body(device_id, "0.001")),
+        app.post_calibration(calibration_body(device_id, "0.002")),
+        app.post_calibration(calibration_body(device_id, "0.003")),
+        app.post_calibration(calibration_body(device_id, "0.004")),
+    );
+    for response in [responses.0, responses.1, responses.2, responses.3] {
+        assert_is_redirect_to(&response, "/admin/calibrations/view");
+    }
+
+    let versions = sqlx::query_scalar!(
+        "SELECT version FROM device_calibrations WHERE device_id = $1 ORDER BY version",
+        device_id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(versions, vec![1, 2, 3, 4]);
+}
+
+#[tokio::test]
+async fn admin_calibrations_with_invalid_scale_factor_returns_a_400() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_calibration(calibration_body(Uuid::new_v4(), "heavy"))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -0,0 +1,174 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+async fn insert_pending_device(pool: &PgPool, organization_id: Uuid, device_name: &str) -> Uuid {
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+    INSERT INTO pending_devices (id, organization_id, topic_prefix, device_name, first_seen_at, last_seen_at)
+    VALUES ($1, $2, 'apiary-7', $3, $4, $4)
+    "#,
+        id,
+        organization_id,
+        device_name,
+        Utc::now()
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+    id
+}
+
+#[tokio::test]
+async fn pending_devices_are_listed_with_their_actions() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let id = insert_pending_device(&app.db_pool, Uuid::new_v4(), "hive-41").await;
+
+    let html_page = app.get_admin_pending_devices_html().await;
+
+    assert!(html_page.contains("topic: apiary-7/hive-41"));
+    assert!(html_page.contains(&format!("/admin/devices/pending/{id}/approve")));
+    assert!(html_page.contains(&format!("/admin/devices/pending/{id}/reject")));
+}
+
+#[tokio::test]
+async fn approving_a_pending_device_subscribes_it() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let organization_id = app.register_organization().await;
+    let id = insert_pending_device(&app.db_pool, organization_id, "hive-41").await;
+
+    let response = app.post_pending_device(id, "approve").await;
+    assert_is_redirect_to(&response, "/admin/devices/pending/view");
+
+    let subscription = sqlx::query!(
+        "SELECT organization_id, device_id, topic_prefix, device_name, payload_format FROM subscriptions_topics"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    let device = sqlx::query!("SELECT organization_id, name FROM devices WHERE id = $1", subscription.device_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(device.organization_id, organization_id);
+    assert_eq!(device.name, "hive-41");
+    assert_eq!(subscription.organization_id, organization_id);
+    assert_eq!(subscription.topic_prefix, "apiary-7");
+    assert_eq!(subscription.device_name, "hive-41");
+    assert_eq!(subscription.payload_format, "json");
+
+    let pending = sqlx::query!("SELECT id FROM pending_devices")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(pending.is_empty());
+
+    let html_page = app.get_admin_pending_devices_html().await;
+    assert!(html_page.contains("Device apiary-7/hive-41 has been added to subscribe list"));
+}
+
+#[tokio::test]
+async fn rejecting_a_pending_device_keeps_it_as_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let id = insert_pending_device(&app.db_pool, Uuid::new_v4(), "hive-41").await;
+
+    let response = app.post_pending_device(id, "reject").await;
+    assert_is_redirect_to(&response, "/admin/devices/pending/view");
+
+    let device = sqlx::query!("SELECT status FROM pending_devices WHERE id = $1", id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(device.status, "rejected");
+
+    // A rejected device cannot be approved anymore.
+    let response = app.post_pending_device(id, "approve").await;
+    assert_eq!(response.status().as_u16(), 404);
+    let subscriptions = sqlx::query!("SELECT id FROM subscriptions_topics")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(subscriptions.is_empty());
+}
+
+#[tokio::test]
+async fn approving_an_unknown_device_returns_a_404() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_pending_device(Uuid::new_v4(), "approve").await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn approving_a_device_of_an_unregistered_apiary_returns_a_400() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let id = insert_pending_device(&app.db_pool, Uuid::new_v4(), "hive-41").await;
+
+    let response = app.post_pending_device(id, "approve").await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let device = sqlx::query!("SELECT status FROM pending_devices WHERE id = $1", id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(device.status, "pending");
+}
+
+#[tokio::test]
+async fn device_names_in_flash_messages_are_escaped() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let organization_id = app.register_organization().await;
+    let id = insert_pending_device(
+        &app.db_pool,
+        organization_id,
+        "<img src=x onerror=alert(1)>",
+    )
+    .await;
+
+    let response = app.post_pending_device(id, "approve").await;
+    assert_is_redirect_to(&response, "/admin/devices/pending/view");
+
+    let html_page = app.get_admin_pending_devices_html().await;
+    assert!(html_page.contains("apiary-7/&lt;img src=x onerror=alert(1)&gt;"));
+    assert!(!html_page.contains("<img"));
+}
+
+#[tokio::test]
+async fn approving_a_device_of_an_already_subscribed_apiary_adds_a_subscription() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let organization_id = app.register_organization().await;
+    let device_id = app.register_device(organization_id, "hive-1").await;
+    let body = serde_urlencoded::to_string(serde_json::json!({
+        "organization_id": organization_id.to_string(),
+        "device_id": device_id.to_string(),
+        "device_name": "hive-1",
+        "topic_prefix": "apiary-7",
+        "payload_format": "json"
+    }))
+    .unwrap();
+    let response = app.post_subscriptions_topics(body).await;
+    assert_is_redirect_to(&response, "/admin/subscriptions/topics/view");
+    let id = insert_pending_device(&app.db_pool, organization_id, "hive-41").await;
+
+    let response = app.post_pending_device(id, "approve").await;
+    assert_is_redirect_to(&response, "/admin/devices/pending/view");
+
+    let device_names: Vec<String> = sqlx::query_scalar!(
+        "SELECT device_name FROM subscriptions_topics WHERE organization_id = $1 ORDER BY device_name",
+        organization_id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(device_names, vec!["hive-1", "hive-41"]);
+}
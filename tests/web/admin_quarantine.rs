@@ -0,0 +1,31 @@
+use crate::helpers::spawn_app;
+use chrono::Utc;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn admin_quarantined_readings_are_listed_with_their_reason() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    sqlx::query!(
+        r#"
+    INSERT INTO quarantined_readings (id, topic, device_name, line_point, reason, received_at, created_at)
+    VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        Uuid::new_v4(),
+        "apiary-1/hive-1",
+        "<hive-1>",
+        "hive_sensors humidity=250",
+        "humidity 250 is above the maximum of 100",
+        Utc::now(),
+        Utc::now()
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let html_page = app.get_admin_quarantined_readings_html().await;
+    assert!(html_page.contains("Quarantined readings:"));
+    assert!(html_page.contains("humidity 250 is above the maximum of 100"));
+    assert!(html_page.contains("&lt;hive-1&gt;"));
+}
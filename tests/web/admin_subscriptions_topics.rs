@@ -4,6 +4,7 @@ use crate::helpers::{assert_is_redirect_to, spawn_app};
 #[tokio::test]
 async fn admin_subscriptions_topics_view_all_returns_a_200() {
     let app = spawn_app().await;
+    app.test_user.login(&app).await;
 
     let html_page = app.get_admin_subscriptions_topics_html().await;
     assert!(html_page.contains("Available topics:"));
@@ -12,13 +13,17 @@ async fn admin_subscriptions_topics_view_all_returns_a_200() {
 #[tokio::test]
 async fn admin_subscriptions_topics_add_new_and_return_a_200() {
     let app = spawn_app().await;
+    app.test_user.login(&app).await;
 
-    let organization_id = Uuid::new_v4().to_string();
-    let device_id = Uuid::new_v4().to_string();
+    let organization_id = app.register_organization().await.to_string();
+    let device_id = app.register_device(organization_id.parse().unwrap(), "hive-1").await.to_string();
 
     let body = serde_urlencoded::to_string(&serde_json::json!({
         "organization_id": organization_id,
-        "device_id": device_id
+        "device_id": device_id,
+        "device_name": "hive-1",
+        "topic_prefix": "apiary-1",
+        "payload_format": "cbor"
     })).unwrap();
 
     let response = app.post_subscriptions_topics(body)
@@ -28,3 +33,124 @@ async fn admin_subscriptions_topics_add_new_and_return_a_200() {
 
     assert_is_redirect_to(&response, "/admin/subscriptions/topics/view")
 }
+
+#[tokio::test]
+async fn admin_subscriptions_topics_add_new_with_unknown_payload_format_returns_a_400() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let body = serde_urlencoded::to_string(serde_json::json!({
+        "organization_id": Uuid::new_v4().to_string(),
+        "device_id": Uuid::new_v4().to_string(),
+        "device_name": "hive-1",
+        "topic_prefix": "apiary-1",
+        "payload_format": "xml"
+    })).unwrap();
+
+    let response = app.post_subscriptions_topics(body).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn admin_subscriptions_topics_add_new_wildcard_pattern_and_return_a_200() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let organization_id = app.register_organization().await;
+    let device_id = app.register_device(organization_id, "gateway").await;
+
+    let body = serde_urlencoded::to_string(serde_json::json!({
+        "organization_id": organization_id.to_string(),
+        "device_id": device_id.to_string(),
+        "device_name": "+",
+        "topic_prefix": "apiary-7",
+        "payload_format": "json"
+    })).unwrap();
+
+    let response = app.post_subscriptions_topics(body).await;
+    assert_is_redirect_to(&response, "/admin/subscriptions/topics/view");
+
+    let html_page = app.get_admin_subscriptions_topics_html().await;
+    assert!(html_page.contains("topic: apiary-7/+"));
+}
+
+#[tokio::test]
+async fn admin_subscriptions_topics_add_new_without_hive_is_only_accepted_for_wildcards() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let organization_id = app.register_organization().await;
+
+    for (device_name, status) in [("#", 303), ("hive-1", 400)] {
+        let body = serde_urlencoded::to_string(serde_json::json!({
+            "organization_id": organization_id.to_string(),
+            "device_id": "",
+            "device_name": device_name,
+            "topic_prefix": "apiary-7",
+            "payload_format": "json"
+        })).unwrap();
+
+        let response = app.post_subscriptions_topics(body).await;
+
+        assert_eq!(response.status().as_u16(), status, "apiary-7/{device_name}");
+    }
+
+    let html_page = app.get_admin_subscriptions_topics_html().await;
+    assert!(html_page.contains(&format!("topic: apiary-7/#, for apiary {organization_id} and all its hives")));
+}
+
+#[tokio::test]
+async fn admin_subscriptions_topics_add_new_with_invalid_pattern_returns_a_400() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    for (topic_prefix, device_name) in [("apiary-7", "#/data"), ("apiary-7", "hive+"), ("apiary/+", "hive-1")] {
+        let body = serde_urlencoded::to_string(serde_json::json!({
+            "organization_id": Uuid::new_v4().to_string(),
+            "device_id": Uuid::new_v4().to_string(),
+            "device_name": device_name,
+            "topic_prefix": topic_prefix,
+            "payload_format": "json"
+        })).unwrap();
+
+        let response = app.post_subscriptions_topics(body).await;
+
+        assert_eq!(response.status().as_u16(), 400, "{topic_prefix}/{device_name} was accepted");
+    }
+}
+
+#[tokio::test]
+async fn admin_subscriptions_topics_create_form_offers_the_registered_apiaries_and_hives() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let organization_id = app.register_organization().await;
+    let device_id = app.register_device(organization_id, "hive-1").await;
+
+    let html_page = app.get_admin_create_subscriptions_topics_html().await;
+
+    assert!(html_page.contains(&format!(r#"<option value="{organization_id}">Apiary {organization_id}</option>"#)));
+    assert!(html_page.contains(&format!(r#"<option value="{device_id}">Apiary {organization_id} / hive-1</option>"#)));
+}
+
+#[tokio::test]
+async fn admin_subscriptions_topics_add_new_with_unregistered_hive_returns_a_400() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let organization_id = app.register_organization().await;
+    let other_organization_id = app.register_organization().await;
+    let other_device_id = app.register_device(other_organization_id, "hive-2").await;
+
+    for device_id in [Uuid::new_v4(), other_device_id] {
+        let body = serde_urlencoded::to_string(serde_json::json!({
+            "organization_id": organization_id.to_string(),
+            "device_id": device_id.to_string(),
+            "device_name": "hive-1",
+            "topic_prefix": "apiary-1",
+            "payload_format": "json"
+        })).unwrap();
+
+        let response = app.post_subscriptions_topics(body).await;
+
+        assert_eq!(response.status().as_u16(), 400);
+    }
+}
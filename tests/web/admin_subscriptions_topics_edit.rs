@@ -0,0 +1,254 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp, TestUser};
+use beesbuddy_bumblebee::domain::Role;
+use uuid::Uuid;
+
+struct Subscription {
+    id: Uuid,
+    organization_id: Uuid,
+    device_id: Uuid,
+}
+
+fn topic_body(subscription: &Subscription, topic_prefix: &str, device_name: &str) -> String {
+    serde_urlencoded::to_string(serde_json::json!({
+        "organization_id": subscription.organization_id.to_string(),
+        "device_id": subscription.device_id.to_string(),
+        "device_name": device_name,
+        "topic_prefix": topic_prefix,
+        "payload_format": "json"
+    }))
+    .unwrap()
+}
+
+/// Subscribes a newly registered hive of a newly registered apiary as the test user.
+async fn subscribe(app: &TestApp, topic_prefix: &str, device_name: &str) -> Subscription {
+    let organization_id = app.register_organization().await;
+    let device_id = app.register_device(organization_id, device_name).await;
+    let mut subscription = Subscription {
+        id: Uuid::nil(),
+        organization_id,
+        device_id,
+    };
+    let response = app
+        .post_subscriptions_topics(topic_body(&subscription, topic_prefix, device_name))
+        .await;
+    assert_is_redirect_to(&response, "/admin/subscriptions/topics/view");
+    subscription.id = sqlx::query_scalar!(
+        "SELECT id FROM subscriptions_topics WHERE device_id = $1",
+        device_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    subscription
+}
+
+#[tokio::test]
+async fn topics_are_listed_with_edit_and_delete_links() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscription = subscribe(&app, "apiary-1", "hive-1").await;
+
+    let html_page = app.get_admin_subscriptions_topics_html().await;
+
+    let id = subscription.id;
+    assert!(html_page.contains(&format!("/admin/subscriptions/topics/{id}/edit")));
+    assert!(html_page.contains(&format!("/admin/subscriptions/topics/{id}/delete")));
+}
+
+#[tokio::test]
+async fn edit_form_is_filled_in_with_the_topic() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscription = subscribe(&app, "apiary-1", "hive-1").await;
+
+    let html_page = app
+        .get_admin_subscriptions_topic_html(subscription.id, "edit")
+        .await;
+
+    assert!(html_page.contains(r#"value="apiary-1""#));
+    assert!(html_page.contains(r#"value="hive-1""#));
+    assert!(html_page.contains(&format!(
+        r#"<option value="{}" selected>"#,
+        subscription.device_id
+    )));
+}
+
+#[tokio::test]
+async fn editing_a_topic_updates_it() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscription = subscribe(&app, "apiary-1", "hive-1").await;
+
+    let response = app
+        .post_subscriptions_topic(
+            subscription.id,
+            "edit",
+            topic_body(&subscription, "apiary-2", "hive-1"),
+        )
+        .await;
+    assert_is_redirect_to(&response, "/admin/subscriptions/topics/view");
+
+    let topic_prefix = sqlx::query_scalar!(
+        "SELECT topic_prefix FROM subscriptions_topics WHERE id = $1",
+        subscription.id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(topic_prefix, "apiary-2");
+
+    let html_page = app.get_admin_subscriptions_topics_html().await;
+    assert!(html_page
+        .contains("<p><i>The topic apiary-1/hive-1 has been updated to apiary-2/hive-1.</i></p>"));
+}
+
+#[tokio::test]
+async fn editing_a_topic_with_an_invalid_pattern_returns_a_400() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscription = subscribe(&app, "apiary-1", "hive-1").await;
+
+    for (topic_prefix, device_name) in [
+        ("apiary-1", "#/data"),
+        ("apiary/+", "hive-1"),
+        ("", "hive-1"),
+    ] {
+        let response = app
+            .post_subscriptions_topic(
+                subscription.id,
+                "edit",
+                topic_body(&subscription, topic_prefix, device_name),
+            )
+            .await;
+
+        assert_eq!(response.status().as_u16(), 400);
+    }
+}
+
+#[tokio::test]
+async fn editing_a_topic_into_a_duplicate_is_rejected_with_a_flash_message() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    subscribe(&app, "apiary-1", "hive-1").await;
+    let subscription = subscribe(&app, "apiary-1", "hive-2").await;
+
+    let response = app
+        .post_subscriptions_topic(
+            subscription.id,
+            "edit",
+            topic_body(&subscription, "apiary-1", "hive-1"),
+        )
+        .await;
+    let edit_location = format!("/admin/subscriptions/topics/{}/edit", subscription.id);
+    assert_is_redirect_to(&response, &edit_location);
+
+    let html_page = app
+        .get_admin_subscriptions_topic_html(subscription.id, "edit")
+        .await;
+    assert!(html_page.contains("<p><i>The topic apiary-1/hive-1 is already subscribed.</i></p>"));
+
+    let device_name = sqlx::query_scalar!(
+        "SELECT device_name FROM subscriptions_topics WHERE id = $1",
+        subscription.id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(device_name, "hive-2");
+}
+
+#[tokio::test]
+async fn creating_a_duplicate_topic_is_rejected_with_a_flash_message() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscription = subscribe(&app, "apiary-1", "hive-1").await;
+
+    let response = app
+        .post_subscriptions_topics(topic_body(&subscription, "apiary-1", "hive-1"))
+        .await;
+    assert_is_redirect_to(&response, "/admin/subscriptions/topics/create");
+
+    let html_page = app.get_admin_create_subscriptions_topics_html().await;
+    assert!(html_page.contains("<p><i>The topic apiary-1/hive-1 is already subscribed.</i></p>"));
+}
+
+#[tokio::test]
+async fn deleting_a_topic_asks_for_confirmation() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscription = subscribe(&app, "apiary-1", "hive-1").await;
+
+    let html_page = app
+        .get_admin_subscriptions_topic_html(subscription.id, "delete")
+        .await;
+
+    assert!(html_page.contains("Delete the subscription of topic apiary-1/hive-1"));
+    assert!(html_page.contains(&format!(
+        r#"<form action="/admin/subscriptions/topics/{}/delete" method="post">"#,
+        subscription.id
+    )));
+}
+
+#[tokio::test]
+async fn deleting_a_topic_removes_it() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscription = subscribe(&app, "apiary-1", "hive-1").await;
+
+    let response = app
+        .post_subscriptions_topic(subscription.id, "delete", String::new())
+        .await;
+    assert_is_redirect_to(&response, "/admin/subscriptions/topics/view");
+
+    let topics = sqlx::query!("SELECT id FROM subscriptions_topics")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(topics.is_empty());
+
+    let html_page = app.get_admin_subscriptions_topics_html().await;
+    assert!(html_page.contains("<p><i>The topic apiary-1/hive-1 has been deleted.</i></p>"));
+
+    // The topic is gone, so deleting it again fails.
+    let response = app
+        .post_subscriptions_topic(subscription.id, "delete", String::new())
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn topics_of_other_organizations_cannot_be_edited_or_deleted() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let subscription = subscribe(&app, "apiary-1", "hive-1").await;
+    let other_apiary = app.register_organization().await;
+
+    let outsider = TestUser::member_of(&app, other_apiary, Role::Admin).await;
+    let beekeeper = TestUser::member_of(&app, subscription.organization_id, Role::Beekeeper).await;
+
+    for (user, status) in [(outsider, 404), (beekeeper, 403)] {
+        user.login(&app).await;
+
+        let response = app
+            .post_subscriptions_topic(
+                subscription.id,
+                "edit",
+                topic_body(&subscription, "apiary-2", "hive-1"),
+            )
+            .await;
+        assert_eq!(response.status().as_u16(), status);
+        let response = app
+            .post_subscriptions_topic(subscription.id, "delete", String::new())
+            .await;
+        assert_eq!(response.status().as_u16(), status);
+    }
+
+    let topic_prefix = sqlx::query_scalar!(
+        "SELECT topic_prefix FROM subscriptions_topics WHERE id = $1",
+        subscription.id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(topic_prefix, "apiary-1");
+}
@@ -0,0 +1,320 @@
+use crate::helpers::{spawn_app, TestApp, TestUser};
+use beesbuddy_bumblebee::domain::Role;
+use reqwest::{Method, StatusCode};
+use serde_json::Value;
+use uuid::Uuid;
+
+fn topic_body(organization_id: Uuid, device_id: Uuid, device_name: &str) -> Value {
+    serde_json::json!({
+        "organization_id": organization_id,
+        "device_id": device_id,
+        "device_name": device_name,
+        "topic_prefix": "apiary-1",
+        "payload_format": "json"
+    })
+}
+
+/// Subscribes a newly registered hive of the apiary with the api.
+async fn create_topic(app: &TestApp, token: &str, organization_id: Uuid, name: &str) -> Value {
+    let device_id = app.register_device(organization_id, name).await;
+    let response = app
+        .api_request(Method::POST, "/subscriptions/topics", token)
+        .json(&topic_body(organization_id, device_id, name))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    response.json().await.unwrap()
+}
+
+async fn assert_error(response: reqwest::Response, status: StatusCode, code: &str) {
+    assert_eq!(response.status(), status);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], code);
+    assert!(body["error"]["message"].is_string());
+}
+
+#[tokio::test]
+async fn requests_without_a_valid_token_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/api/v1/subscriptions/topics", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["WWW-Authenticate"], "Bearer");
+    assert_error(response, StatusCode::UNAUTHORIZED, "unauthorized").await;
+
+    let response = app
+        .api_request(Method::GET, "/subscriptions/topics", "not-a-token")
+        .send()
+        .await
+        .unwrap();
+    assert_error(response, StatusCode::UNAUTHORIZED, "unauthorized").await;
+}
+
+#[tokio::test]
+async fn created_topic_can_be_read_back() {
+    let app = spawn_app().await;
+    let token = app.test_user.api_token(&app).await;
+    let organization_id = app.register_organization().await;
+
+    let device_id = app.register_device(organization_id, "hive-1").await;
+    let response = app
+        .api_request(Method::POST, "/subscriptions/topics", &token)
+        .json(&topic_body(organization_id, device_id, "hive-1"))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let location = response.headers()["Location"].to_str().unwrap().to_string();
+    let created: Value = response.json().await.unwrap();
+    assert_eq!(created["topic"], "apiary-1/hive-1");
+    assert_eq!(
+        location,
+        format!(
+            "/api/v1/subscriptions/topics/{}",
+            created["id"].as_str().unwrap()
+        )
+    );
+    let response = app
+        .api_request(Method::GET, location.trim_start_matches("/api/v1"), &token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.json::<Value>().await.unwrap(), created);
+}
+
+#[tokio::test]
+async fn topics_are_paginated_and_filtered_by_apiary() {
+    let app = spawn_app().await;
+    let token = app.test_user.api_token(&app).await;
+    let organization_id = app.register_organization().await;
+    for name in ["hive-1", "hive-2", "hive-3"] {
+        create_topic(&app, &token, organization_id, name).await;
+    }
+    let other_organization_id = app.register_organization().await;
+    create_topic(&app, &token, other_organization_id, "hive-4").await;
+
+    let path = format!("/subscriptions/topics?organization_id={organization_id}&per_page=2");
+    let first: Value = app
+        .api_request(Method::GET, &path, &token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let second: Value = app
+        .api_request(Method::GET, &format!("{path}&page=2"), &token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(first["total"], 3);
+    assert_eq!(first["items"].as_array().unwrap().len(), 2);
+    assert_eq!(second["items"].as_array().unwrap().len(), 1);
+    assert_eq!(second["items"][0]["device_name"], "hive-3");
+    assert_eq!(
+        second["items"][0]["organization_id"],
+        organization_id.to_string()
+    );
+}
+
+#[tokio::test]
+async fn invalid_requests_get_an_error_object() {
+    let app = spawn_app().await;
+    let token = app.test_user.api_token(&app).await;
+    let organization_id = app.register_organization().await;
+    let device_id = app.register_device(organization_id, "hive-1").await;
+
+    let mut body = topic_body(organization_id, device_id, "hive-1");
+    body["topic_prefix"] = "apiary/+".into();
+    let response = app
+        .api_request(Method::POST, "/subscriptions/topics", &token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_error(response, StatusCode::BAD_REQUEST, "validation_error").await;
+
+    let response = app
+        .api_request(Method::POST, "/subscriptions/topics", &token)
+        .header("Content-Type", "application/json")
+        .body("{\"device_name\":")
+        .send()
+        .await
+        .unwrap();
+    assert_error(response, StatusCode::BAD_REQUEST, "bad_request").await;
+
+    let response = app
+        .api_request(Method::GET, "/subscriptions/topics?per_page=1000", &token)
+        .send()
+        .await
+        .unwrap();
+    assert_error(response, StatusCode::BAD_REQUEST, "bad_request").await;
+}
+
+#[tokio::test]
+async fn only_wildcard_topics_can_leave_out_the_hive() {
+    let app = spawn_app().await;
+    let token = app.test_user.api_token(&app).await;
+    let organization_id = app.register_organization().await;
+
+    let mut body = topic_body(organization_id, Uuid::new_v4(), "+");
+    body["device_id"] = Value::Null;
+    let response = app
+        .api_request(Method::POST, "/subscriptions/topics", &token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let created: Value = response.json().await.unwrap();
+    assert_eq!(created["topic"], "apiary-1/+");
+    assert!(created["device_id"].is_null());
+
+    body["device_name"] = "hive-1".into();
+    let response = app
+        .api_request(Method::POST, "/subscriptions/topics", &token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_error(response, StatusCode::BAD_REQUEST, "validation_error").await;
+}
+
+#[tokio::test]
+async fn duplicate_topic_is_a_conflict() {
+    let app = spawn_app().await;
+    let token = app.test_user.api_token(&app).await;
+    let organization_id = app.register_organization().await;
+    let created = create_topic(&app, &token, organization_id, "hive-1").await;
+
+    let response = app
+        .api_request(Method::POST, "/subscriptions/topics", &token)
+        .json(&topic_body(
+            organization_id,
+            created["device_id"].as_str().unwrap().parse().unwrap(),
+            "hive-1",
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert_error(response, StatusCode::CONFLICT, "duplicate_topic").await;
+}
+
+#[tokio::test]
+async fn topic_can_be_updated_and_deleted() {
+    let app = spawn_app().await;
+    let token = app.test_user.api_token(&app).await;
+    let organization_id = app.register_organization().await;
+    let created = create_topic(&app, &token, organization_id, "hive-1").await;
+    let path = format!("/subscriptions/topics/{}", created["id"].as_str().unwrap());
+
+    let mut body = topic_body(
+        organization_id,
+        created["device_id"].as_str().unwrap().parse().unwrap(),
+        "+",
+    );
+    body["payload_format"] = "cbor".into();
+    let response = app
+        .api_request(Method::PUT, &path, &token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let updated: Value = response.json().await.unwrap();
+    assert_eq!(updated["topic"], "apiary-1/+");
+    assert_eq!(updated["payload_format"], "cbor");
+
+    let response = app
+        .api_request(Method::DELETE, &path, &token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = app
+        .api_request(Method::GET, &path, &token)
+        .send()
+        .await
+        .unwrap();
+    assert_error(response, StatusCode::NOT_FOUND, "not_found").await;
+}
+
+#[tokio::test]
+async fn topics_of_other_apiaries_are_hidden_and_beekeepers_cannot_edit() {
+    let app = spawn_app().await;
+    let token = app.test_user.api_token(&app).await;
+    let organization_id = app.register_organization().await;
+    let created = create_topic(&app, &token, organization_id, "hive-1").await;
+    let path = format!("/subscriptions/topics/{}", created["id"].as_str().unwrap());
+    let other_organization_id = app.register_organization().await;
+    let outsider = TestUser::member_of(&app, other_organization_id, Role::Admin).await;
+    let outsider_token = outsider.api_token(&app).await;
+    let beekeeper = TestUser::member_of(&app, organization_id, Role::Beekeeper).await;
+    let beekeeper_token = beekeeper.api_token(&app).await;
+
+    let response = app
+        .api_request(Method::GET, &path, &outsider_token)
+        .send()
+        .await
+        .unwrap();
+    assert_error(response, StatusCode::NOT_FOUND, "not_found").await;
+    let page: Value = app
+        .api_request(Method::GET, "/subscriptions/topics", &outsider_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(page["total"], 0);
+
+    let response = app
+        .api_request(Method::GET, &path, &beekeeper_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app
+        .api_request(Method::DELETE, &path, &beekeeper_token)
+        .send()
+        .await
+        .unwrap();
+    assert_error(response, StatusCode::FORBIDDEN, "forbidden").await;
+}
+
+#[tokio::test]
+async fn openapi_document_describes_the_endpoints() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/api/v1/openapi.json", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let document: Value = response.json().await.unwrap();
+    assert!(document["openapi"].as_str().unwrap().starts_with("3."));
+    let paths = &document["paths"];
+    for method in ["get", "post"] {
+        assert!(paths["/api/v1/subscriptions/topics"][method].is_object());
+    }
+    for method in ["get", "put", "delete"] {
+        assert!(paths["/api/v1/subscriptions/topics/{id}"][method].is_object());
+    }
+    assert!(document["components"]["securitySchemes"]["api_token"].is_object());
+    assert!(document["components"]["schemas"]["ErrorBody"].is_object());
+}
@@ -18,3 +18,35 @@ async fn health_check_works() {
     assert!(response.status().is_success());
     assert_eq!(Some(0), response.content_length());
 }
+
+#[tokio::test]
+async fn health_ready_returns_a_503_while_mqtt_is_disconnected() {
+    let app = spawn_app().await;
+
+    let response = app.get_health_ready().await;
+
+    assert_eq!(response.status().as_u16(), 503);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "not_ready");
+    assert_eq!(body["dependencies"]["database"]["status"], "up");
+    assert_eq!(body["dependencies"]["mqtt"]["status"], "down");
+}
+
+#[tokio::test]
+async fn health_ready_returns_a_200_when_critical_dependencies_are_up() {
+    let app = spawn_app().await;
+    app.health.set_mqtt_connected(true);
+    app.health
+        .record_influxdb_write(&Err(anyhow::anyhow!("influxdb is unreachable").into()));
+
+    let response = app.get_health_ready().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "ready");
+    assert_eq!(body["dependencies"]["influxdb"]["status"], "down");
+    assert_eq!(
+        body["dependencies"]["influxdb"]["last_error"],
+        "influxdb is unreachable"
+    );
+}
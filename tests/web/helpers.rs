@@ -1,8 +1,27 @@
-use beesbuddy_bumblebee::application::{get_connection_pool, Application};
-use beesbuddy_bumblebee::configuration::{get_configuration, DatabaseSettings};
+use beesbuddy_bumblebee::application::{self, get_connection_pool, Application};
+use beesbuddy_bumblebee::authentication::{
+    add_organization_member, create_api_token, create_user,
+};
+use beesbuddy_bumblebee::configuration::{get_configuration, DatabaseSettings, MqttSettings};
+use beesbuddy_bumblebee::domain::{Role, UserName};
+use beesbuddy_bumblebee::health::DependencyHealth;
+use beesbuddy_bumblebee::metrics::Metrics;
+use beesbuddy_bumblebee::mqtt;
+use beesbuddy_bumblebee::mqtt_client::{MqttClient, MqttEvent, MqttEventLoop};
+use beesbuddy_bumblebee::supervisor::Supervisor;
 use beesbuddy_bumblebee::telemetry::{get_subscriber, init_subscriber};
+use chrono::Utc;
 use once_cell::sync::Lazy;
+use rumqttd::{Broker, Config, ConnectionSettings, RouterConfig, ServerSettings};
+use secrecy::{ExposeSecret, Secret};
 use sqlx::{Connection, Executor, PgConnection, PgPool};
+use std::collections::HashMap;
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 use wiremock::MockServer;
 
@@ -25,9 +44,138 @@ pub struct TestApp {
     pub api_client: reqwest::Client,
     pub db_pool: PgPool,
     pub email_server: MockServer,
+    pub shutdown: CancellationToken,
+    pub application_task: JoinHandle<Result<(), application::Error>>,
+    pub health: DependencyHealth,
+    pub test_user: TestUser,
+}
+
+pub struct TestUser {
+    pub username: String,
+    pub password: String,
+}
+
+impl TestUser {
+    async fn store(pool: &PgPool, superadmin: bool) -> Self {
+        let username = Uuid::new_v4().to_string();
+        let password = Uuid::new_v4().to_string();
+        create_user(
+            &UserName::parse(username.clone()).unwrap(),
+            Secret::new(password.clone()),
+            superadmin,
+            pool,
+        )
+        .await
+        .expect("Failed to store the test user.");
+        Self { username, password }
+    }
+
+    /// Stores a user that is not a superadmin with a role in the organization.
+    pub async fn member_of(app: &TestApp, organization_id: Uuid, role: Role) -> Self {
+        let user = Self::store(&app.db_pool, false).await;
+        add_organization_member(
+            &UserName::parse(user.username.clone()).unwrap(),
+            organization_id,
+            role,
+            &app.db_pool,
+        )
+        .await
+        .expect("Failed to store the organization member.");
+        user
+    }
+
+    pub async fn login(&self, app: &TestApp) {
+        let response = app
+            .post_login(&serde_json::json!({
+                "username": &self.username,
+                "password": &self.password
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/admin/dashboard");
+    }
+
+    pub async fn api_token(&self, app: &TestApp) -> String {
+        create_api_token(
+            &UserName::parse(self.username.clone()).unwrap(),
+            "test",
+            &app.db_pool,
+        )
+        .await
+        .expect("Failed to create the api token.")
+        .expose_secret()
+        .clone()
+    }
 }
 
 impl TestApp {
+    /// Registers an apiary without going through the admin pages.
+    pub async fn register_organization(&self) -> Uuid {
+        let organization_id = Uuid::new_v4();
+        sqlx::query!(
+            r#"
+        INSERT INTO organizations (id, name, created_at, updated_at)
+        VALUES ($1, $2, $3, $3)
+        "#,
+            organization_id,
+            format!("Apiary {organization_id}"),
+            Utc::now()
+        )
+        .execute(&self.db_pool)
+        .await
+        .expect("Failed to register the apiary.");
+        organization_id
+    }
+
+    /// Registers a hive of the apiary without going through the admin pages.
+    pub async fn register_device(&self, organization_id: Uuid, name: &str) -> Uuid {
+        let device_id = Uuid::new_v4();
+        sqlx::query!(
+            r#"
+        INSERT INTO devices (id, organization_id, name, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $4)
+        "#,
+            device_id,
+            organization_id,
+            name,
+            Utc::now()
+        )
+        .execute(&self.db_pool)
+        .await
+        .expect("Failed to register the hive.");
+        device_id
+    }
+
+    pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/login", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_login_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/login", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_logout(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/logout", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn get_admin_dashboard(&self) -> reqwest::Response {
         self.api_client
             .get(&format!("{}/admin/dashboard", &self.address))
@@ -59,6 +207,142 @@ impl TestApp {
             .unwrap()
     }
 
+    pub async fn get_admin_quarantined_readings_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/quarantine/readings/view", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_health_ready(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/health/ready", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_metrics(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/metrics", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_calibrations_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/calibrations/view", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_calibration(&self, body: String) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/calibrations/edit", &self.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_create_subscriptions_topics_html(&self) -> String {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscriptions/topics/create",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_admin_organizations_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/organizations/view", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_organization(&self, body: String) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/organizations/create", &self.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_devices_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/devices/view", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_device(&self, body: String) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/devices/create", &self.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_subscriptions_topic_html(&self, id: Uuid, action: &str) -> String {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscriptions/topics/{}/{}",
+                &self.address, id, action
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_subscriptions_topic(
+        &self,
+        id: Uuid,
+        action: &str,
+        body: String,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/subscriptions/topics/{}/{}",
+                &self.address, id, action
+            ))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_subscriptions_topics(&self, body: String) -> reqwest::Response {
         self.api_client
             .post(&format!(
@@ -71,6 +355,41 @@ impl TestApp {
             .await
             .expect("Failed to execute request.")
     }
+
+    /// A request to the json api authenticated with the api token.
+    pub fn api_request(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        token: &str,
+    ) -> reqwest::RequestBuilder {
+        self.api_client
+            .request(method, format!("{}/api/v1{}", &self.address, path))
+            .bearer_auth(token)
+    }
+
+    pub async fn get_admin_pending_devices_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/devices/pending/view", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    /// Approves or rejects a pending device.
+    pub async fn post_pending_device(&self, id: Uuid, action: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/devices/pending/{id}/{action}",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
 }
 
 pub async fn spawn_app() -> TestApp {
@@ -86,18 +405,27 @@ pub async fn spawn_app() -> TestApp {
         c.database.database_name = Uuid::new_v4().to_string();
         // Use a random OS port
         c.application.port = 0;
+        c.redis_uri = Secret::new(spawn_redis().await);
         c
     };
 
     // Create and migrate the database
-    configure_database(&configuration.database).await;
+    let db_pool = configure_database(&configuration.database).await;
+    let test_user = TestUser::store(&db_pool, true).await;
 
     // Launch the application as a background task
-    let application = Application::build(configuration.clone())
-        .await
-        .expect("Failed to build application.");
+    let health = DependencyHealth::new();
+    let application = Application::build(
+        configuration.clone(),
+        health.clone(),
+        Supervisor::new(configuration.supervisor.clone()),
+        Metrics::new(),
+    )
+    .await
+    .expect("Failed to build application.");
     let application_port = application.port();
-    tokio::spawn(application.run_until_stopped());
+    let shutdown = CancellationToken::new();
+    let application_task = tokio::spawn(application.run_until_stopped(shutdown.clone()));
 
     let client = reqwest::Client::builder()
         .redirect(reqwest::redirect::Policy::none())
@@ -111,6 +439,10 @@ pub async fn spawn_app() -> TestApp {
         api_client: client,
         db_pool: get_connection_pool(&configuration.database),
         email_server,
+        shutdown,
+        application_task,
+        health,
+        test_user,
     }
 }
 
@@ -140,3 +472,144 @@ pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
     assert_eq!(response.status().as_u16(), 303);
     assert_eq!(response.headers().get("Location").unwrap(), location);
 }
+
+/// Ports of the MQTT 3.1.1 and MQTT 5 listeners of an in-process broker.
+pub struct MqttBroker {
+    pub port: u16,
+    pub v5_port: u16,
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn mqtt_listener(name: &str, port: u16) -> HashMap<String, ServerSettings> {
+    let server = ServerSettings {
+        name: name.into(),
+        listen: ([127, 0, 0, 1], port).into(),
+        tls: None,
+        next_connection_delay_ms: 1,
+        connections: ConnectionSettings {
+            connection_timeout_ms: 60000,
+            max_payload_size: 20480,
+            max_inflight_count: 100,
+            auth: None,
+            external_auth: None,
+            dynamic_filters: true,
+        },
+    };
+    HashMap::from([("1".to_string(), server)])
+}
+
+/// Starts an in-process broker standing in for mosquitto.
+pub fn spawn_mqtt_broker() -> MqttBroker {
+    let broker = MqttBroker {
+        port: free_port(),
+        v5_port: free_port(),
+    };
+    let config = Config {
+        router: RouterConfig {
+            max_connections: 100,
+            max_outgoing_packet_count: 200,
+            max_segment_size: 1024 * 1024,
+            max_segment_count: 10,
+            ..RouterConfig::default()
+        },
+        v4: Some(mqtt_listener("v4", broker.port)),
+        v5: Some(mqtt_listener("v5", broker.v5_port)),
+        ..Config::default()
+    };
+    std::thread::spawn(move || Broker::new(config).start().unwrap());
+    broker
+}
+
+/// Connects with the client of the mqtt worker and polls the event loop until the broker
+/// acknowledges the connection, the broker may still be starting.
+pub async fn connect_mqtt(settings: &MqttSettings) -> (MqttClient, MqttEventLoop) {
+    let (client, mut event_loop) = mqtt::client(settings).unwrap();
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            match event_loop.poll().await {
+                Ok(MqttEvent::Connected { .. }) => return,
+                Ok(_) => {}
+                Err(_) => tokio::time::sleep(Duration::from_millis(50)).await,
+            }
+        }
+    })
+    .await
+    .expect("Failed to connect to the broker.");
+    (client, event_loop)
+}
+
+/// Starts an in-process stand-in for redis that understands the commands of the session
+/// store and returns its uri, key expiry is not implemented.
+async fn spawn_redis() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let store = Arc::new(Mutex::new(HashMap::new()));
+    tokio::spawn(async move {
+        while let Ok((socket, _)) = listener.accept().await {
+            tokio::spawn(serve_redis(socket, store.clone()));
+        }
+    });
+    format!("redis://{address}")
+}
+
+async fn serve_redis(socket: tokio::net::TcpStream, store: Arc<Mutex<HashMap<String, String>>>) {
+    let (reader, mut writer) = socket.into_split();
+    let mut reader = BufReader::new(reader);
+    while let Some(command) = read_redis_command(&mut reader).await {
+        let reply = {
+            let mut store = store.lock().unwrap();
+            match command[0].to_uppercase().as_str() {
+                "GET" => match store.get(&command[1]) {
+                    Some(value) => format!("${}\r\n{value}\r\n", value.len()),
+                    None => "$-1\r\n".to_string(),
+                },
+                "SET" => {
+                    let exists = store.contains_key(&command[1]);
+                    let options: Vec<String> =
+                        command[3..].iter().map(|o| o.to_uppercase()).collect();
+                    let only_new = options.contains(&"NX".to_string());
+                    let only_existing = options.contains(&"XX".to_string());
+                    if (only_new && exists) || (only_existing && !exists) {
+                        "$-1\r\n".to_string()
+                    } else {
+                        store.insert(command[1].clone(), command[2].clone());
+                        "+OK\r\n".to_string()
+                    }
+                }
+                "DEL" => format!(":{}\r\n", store.remove(&command[1]).is_some() as i32),
+                "EXPIRE" => format!(":{}\r\n", store.contains_key(&command[1]) as i32),
+                _ => "+OK\r\n".to_string(),
+            }
+        };
+        if writer.write_all(reply.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Reads a command sent as an array of bulk strings.
+async fn read_redis_command(
+    reader: &mut BufReader<tokio::net::tcp::OwnedReadHalf>,
+) -> Option<Vec<String>> {
+    let mut line = String::new();
+    reader.read_line(&mut line).await.ok()?;
+    let count: usize = line.trim_end().strip_prefix('*')?.parse().ok()?;
+    let mut command = Vec::with_capacity(count);
+    for _ in 0..count {
+        line.clear();
+        reader.read_line(&mut line).await.ok()?;
+        let length: usize = line.trim_end().strip_prefix('$')?.parse().ok()?;
+        let mut argument = vec![0; length + 2];
+        reader.read_exact(&mut argument).await.ok()?;
+        argument.truncate(length);
+        command.push(String::from_utf8(argument).ok()?);
+    }
+    Some(command)
+}
@@ -0,0 +1,172 @@
+use crate::helpers::spawn_app;
+use beesbuddy_bumblebee::influxdb_client::{InfluxDbClient, InfluxDbWriteError};
+use beesbuddy_bumblebee::influxdb_spool::InfluxDbSpool;
+use beesbuddy_bumblebee::metrics::Metrics;
+use secrecy::Secret;
+use wiremock::matchers::{body_string_contains, method};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn spooled_points_are_read_back_in_insertion_order() {
+    let app = spawn_app().await;
+    let spool = InfluxDbSpool::new(app.db_pool.clone(), 10);
+
+    spool.push("hive_sensors weight=1i").await.unwrap();
+    spool.push("hive_sensors weight=2i").await.unwrap();
+
+    let points = spool.oldest(10).await.unwrap();
+    let payloads: Vec<&str> = points.iter().map(|p| p.payload.as_str()).collect();
+    assert_eq!(
+        payloads,
+        vec!["hive_sensors weight=1i", "hive_sensors weight=2i"]
+    );
+    assert_eq!(spool.depth(), 2);
+
+    spool.remove(&points).await.unwrap();
+    assert!(spool.is_empty());
+}
+
+#[tokio::test]
+async fn oldest_points_are_dropped_when_spool_is_full() {
+    let app = spawn_app().await;
+    let spool = InfluxDbSpool::new(app.db_pool.clone(), 2);
+
+    for weight in 1..=3 {
+        spool
+            .push(&format!("hive_sensors weight={weight}i"))
+            .await
+            .unwrap();
+    }
+
+    let points = spool.oldest(10).await.unwrap();
+    let payloads: Vec<&str> = points.iter().map(|p| p.payload.as_str()).collect();
+    assert_eq!(
+        payloads,
+        vec!["hive_sensors weight=2i", "hive_sensors weight=3i"]
+    );
+    assert_eq!(spool.depth(), 2);
+}
+
+#[tokio::test]
+async fn batch_is_spooled_in_order_and_trimmed_at_once() {
+    let app = spawn_app().await;
+    let spool = InfluxDbSpool::new(app.db_pool.clone(), 3);
+    spool.push("hive_sensors weight=1i").await.unwrap();
+
+    let batch: Vec<String> = (2..=4)
+        .map(|weight| format!("hive_sensors weight={weight}i"))
+        .collect();
+    spool.push_all(&batch).await.unwrap();
+
+    let points = spool.oldest(10).await.unwrap();
+    let payloads: Vec<&str> = points.iter().map(|p| p.payload.as_str()).collect();
+    assert_eq!(
+        payloads,
+        vec![
+            "hive_sensors weight=2i",
+            "hive_sensors weight=3i",
+            "hive_sensors weight=4i"
+        ]
+    );
+    assert_eq!(spool.depth(), 3);
+}
+
+#[tokio::test]
+async fn rejected_points_are_dropped_without_blocking_the_others() {
+    let influxdb = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("firmware"))
+        .respond_with(ResponseTemplate::new(400).set_body_string("field type conflict"))
+        .with_priority(1)
+        .mount(&influxdb)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(204))
+        .mount(&influxdb)
+        .await;
+    let metrics = Metrics::new();
+    let client = influxdb_client(&influxdb).with_metrics(metrics.clone());
+
+    let outcome = client
+        .write_lines(&["hive_sensors weight=1i", "hive_sensors firmware=1i"])
+        .await;
+
+    assert!(outcome.is_ok());
+    assert_eq!(metrics.influxdb_rejected_points.get(), 1);
+    let requests = influxdb.received_requests().await.unwrap();
+    let bodies: Vec<String> = requests
+        .iter()
+        .map(|request| String::from_utf8(request.body.clone()).unwrap())
+        .collect();
+    assert!(bodies.contains(&"hive_sensors weight=1i".to_string()));
+}
+
+#[tokio::test]
+async fn rejected_batch_is_bisected_to_find_the_bad_point() {
+    let influxdb = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_string_contains("firmware"))
+        .respond_with(ResponseTemplate::new(400).set_body_string("field type conflict"))
+        .with_priority(1)
+        .mount(&influxdb)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(204))
+        .mount(&influxdb)
+        .await;
+    let metrics = Metrics::new();
+    let client = influxdb_client(&influxdb).with_metrics(metrics.clone());
+    let mut lines: Vec<String> = (1..16)
+        .map(|weight| format!("hive_sensors weight={weight}i"))
+        .collect();
+    lines.insert(5, "hive_sensors firmware=1i".to_string());
+    let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+
+    let outcome = client.write_lines(&lines).await;
+
+    assert!(outcome.is_ok());
+    assert_eq!(metrics.influxdb_rejected_points.get(), 1);
+    // The batch, then both halves of the 16, 8, 4 and 2 rejected lines.
+    assert_eq!(influxdb.received_requests().await.unwrap().len(), 9);
+}
+
+#[tokio::test]
+async fn server_errors_are_returned_to_be_retried() {
+    let influxdb = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&influxdb)
+        .await;
+    let metrics = Metrics::new();
+    let client = influxdb_client(&influxdb).with_metrics(metrics.clone());
+
+    let outcome = client.write_lines(&["hive_sensors weight=1i"]).await;
+
+    assert!(matches!(outcome, Err(InfluxDbWriteError::Unavailable(_))));
+    assert_eq!(metrics.influxdb_rejected_points.get(), 0);
+}
+
+#[tokio::test]
+async fn spool_depth_is_exported_as_a_gauge() {
+    let app = spawn_app().await;
+    let metrics = Metrics::new();
+    let spool = InfluxDbSpool::new(app.db_pool.clone(), 10).with_metrics(&metrics);
+
+    spool.push("hive_sensors weight=1i").await.unwrap();
+
+    assert_eq!(metrics.influxdb_spool_depth.get(), 1);
+    assert!(metrics
+        .encode()
+        .unwrap()
+        .contains("bumblebee_influxdb_spool_depth 1"));
+}
+
+fn influxdb_client(influxdb: &MockServer) -> InfluxDbClient {
+    InfluxDbClient::new(
+        influxdb.uri(),
+        "bucket".into(),
+        "organization".into(),
+        Secret::new("token".into()),
+        std::time::Duration::from_secs(2),
+    )
+}
@@ -0,0 +1,77 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[tokio::test]
+async fn anonymous_users_are_redirected_to_the_login_form() {
+    let app = spawn_app().await;
+
+    for path in ["/admin/dashboard", "/admin/subscriptions/topics/view"] {
+        let response = app
+            .api_client
+            .get(format!("{}{}", &app.address, path))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_is_redirect_to(&response, "/login");
+    }
+}
+
+#[tokio::test]
+async fn an_error_flash_message_is_set_on_failure() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": "not-the-right-password"
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Authentication failed</i></p>"));
+
+    // The flash message is gone after a reload
+    let html_page = app.get_login_html().await;
+    assert!(!html_page.contains("Authentication failed"));
+}
+
+#[tokio::test]
+async fn unknown_users_cannot_log_in() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": "unknown-user",
+            "password": &app.test_user.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn logged_in_users_can_access_the_admin_dashboard() {
+    let app = spawn_app().await;
+
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains("Available actions:"));
+}
+
+#[tokio::test]
+async fn logout_clears_the_session() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_logout().await;
+    assert_is_redirect_to(&response, "/login");
+
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>You have successfully logged out.</i></p>"));
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
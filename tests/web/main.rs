@@ -1,4 +1,18 @@
 mod helpers;
 mod health_check;
 mod admin_subscriptions_topics;
-mod admin_dashboard;
\ No newline at end of file
+mod admin_dashboard;
+mod admin_quarantine;
+mod admin_calibrations;
+mod influxdb_spool;
+mod subscriptions_notifications;
+mod shutdown;
+mod metrics;
+mod mqtt_session;
+mod mqtt_shared_subscriptions;
+mod mqtt_v5;
+mod admin_pending_devices;
+mod login;
+mod admin_access;
+mod admin_subscriptions_topics_edit;
+mod api_subscriptions_topics;
\ No newline at end of file
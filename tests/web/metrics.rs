@@ -0,0 +1,54 @@
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn metrics_are_exposed_in_the_prometheus_text_format() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_metrics().await;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    assert!(response
+        .headers()
+        .get("content-type")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain; version=0.0.4"));
+    let body = response.text().await.unwrap();
+    assert!(body.contains("# TYPE bumblebee_influxdb_write_duration_seconds histogram"));
+    assert!(body.contains("bumblebee_mqtt_active_subscriptions 0"));
+}
+
+#[tokio::test]
+async fn http_request_durations_are_recorded_per_route() {
+    // Arrange
+    let app = spawn_app().await;
+    app.api_client
+        .get(format!("{}/health_check", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    app.api_client
+        .get(format!(
+            "{}/not/a/route/{}",
+            &app.address,
+            uuid::Uuid::new_v4()
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    // Act
+    let body = app.get_metrics().await.text().await.unwrap();
+
+    // Assert
+    assert!(body.contains(
+        r#"bumblebee_http_request_duration_seconds_count{method="GET",route="/health_check",status="200"} 1"#
+    ));
+    assert!(body.contains(
+        r#"bumblebee_http_request_duration_seconds_count{method="GET",route="unmatched",status="404"} 1"#
+    ));
+}
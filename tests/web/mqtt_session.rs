@@ -0,0 +1,36 @@
+use crate::helpers::spawn_app;
+use beesbuddy_bumblebee::workers::MqttSession;
+
+#[tokio::test]
+async fn session_topics_are_replaced_on_store() {
+    let app = spawn_app().await;
+    let session = MqttSession::new(app.db_pool.clone(), "bumblebee-1".into());
+
+    session
+        .store(&["apiary-1/hive-2".to_string(), "apiary-1/hive-1".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(
+        session.topics().await.unwrap(),
+        vec!["apiary-1/hive-1", "apiary-1/hive-2"]
+    );
+
+    session
+        .store(&["apiary-1/hive-3".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(session.topics().await.unwrap(), vec!["apiary-1/hive-3"]);
+}
+
+#[tokio::test]
+async fn sessions_of_other_clients_are_kept_apart() {
+    let app = spawn_app().await;
+    let first = MqttSession::new(app.db_pool.clone(), "bumblebee-1".into());
+    let second = MqttSession::new(app.db_pool.clone(), "bumblebee-2".into());
+
+    first.store(&["apiary-1/hive-1".to_string()]).await.unwrap();
+    second.store(&[]).await.unwrap();
+
+    assert_eq!(first.topics().await.unwrap(), vec!["apiary-1/hive-1"]);
+    assert!(second.topics().await.unwrap().is_empty());
+}
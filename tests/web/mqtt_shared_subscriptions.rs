@@ -0,0 +1,109 @@
+use crate::helpers::{connect_mqtt, spawn_mqtt_broker};
+use beesbuddy_bumblebee::configuration::{get_configuration, MqttSettings};
+use beesbuddy_bumblebee::mqtt;
+use beesbuddy_bumblebee::mqtt_client::{MqttClient, MqttEvent};
+use rumqttc::QoS;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const TOPIC: &str = "apiary-1/hive-1";
+
+fn mqtt_settings(port: u16, shared_subscriptions: bool) -> MqttSettings {
+    let mut settings = get_configuration()
+        .expect("Failed to read configuration.")
+        .mqtt;
+    settings.host = "127.0.0.1".into();
+    settings.port = port;
+    settings.shared_subscriptions = shared_subscriptions;
+    settings
+}
+
+/// Subscribes an instance the way the mqtt worker does and forwards the topics of the
+/// publishes it receives.
+async fn spawn_instance(
+    settings: &MqttSettings,
+    received: mpsc::UnboundedSender<(usize, String)>,
+    id: usize,
+) {
+    let (client, mut event_loop) = connect_mqtt(settings).await;
+    let filter = mqtt::subscription_filter(mqtt::share_group(settings).as_deref(), TOPIC);
+    client
+        .try_subscribe(filter, mqtt::qos(settings).unwrap())
+        .unwrap();
+
+    loop {
+        if let Ok(MqttEvent::Subscribed { .. }) = event_loop.poll().await {
+            break;
+        }
+    }
+    tokio::spawn(async move {
+        while let Ok(event) = event_loop.poll().await {
+            if let MqttEvent::Message(message) = event {
+                let _ = received.send((id, message.topic));
+            }
+        }
+    });
+}
+
+/// Publishes `messages` messages and returns the number of messages each of the two
+/// instances received.
+async fn deliveries(shared_subscriptions: bool, messages: usize) -> [usize; 2] {
+    let port = spawn_mqtt_broker().port;
+    let settings = mqtt_settings(port, shared_subscriptions);
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    spawn_instance(&settings, tx.clone(), 0).await;
+    spawn_instance(&settings, tx, 1).await;
+
+    let (MqttClient::V4(publisher), mut publisher_event_loop) =
+        connect_mqtt(&mqtt_settings(port, false)).await
+    else {
+        panic!("The default protocol version is 3.1.1.");
+    };
+    tokio::spawn(async move { while publisher_event_loop.poll().await.is_ok() {} });
+    for weight in 0..messages {
+        publisher
+            .publish(
+                TOPIC,
+                QoS::AtLeastOnce,
+                false,
+                format!(r#"{{"weight":{weight}}}"#),
+            )
+            .await
+            .unwrap();
+    }
+
+    let mut counts = [0, 0];
+    // Messages that are not balanced are delivered to both instances.
+    let expected = if shared_subscriptions {
+        messages
+    } else {
+        messages * 2
+    };
+    for _ in 0..expected {
+        let (id, topic) = tokio::time::timeout(Duration::from_secs(10), rx.recv())
+            .await
+            .expect("Not all messages were delivered.")
+            .unwrap();
+        // Shared subscriptions deliver the publishes with their own topic.
+        assert_eq!(topic, TOPIC);
+        counts[id] += 1;
+    }
+    counts
+}
+
+#[tokio::test]
+async fn shared_subscriptions_balance_messages_across_instances() {
+    let [first, second] = deliveries(true, 10).await;
+
+    assert_eq!(first + second, 10);
+    assert!(first > 0, "the first instance received no message");
+    assert!(second > 0, "the second instance received no message");
+}
+
+#[tokio::test]
+async fn every_instance_receives_every_message_without_shared_subscriptions() {
+    let [first, second] = deliveries(false, 10).await;
+
+    assert_eq!(first, 10);
+    assert_eq!(second, 10);
+}
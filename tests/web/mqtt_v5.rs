@@ -0,0 +1,83 @@
+use crate::helpers::{connect_mqtt, spawn_mqtt_broker};
+use beesbuddy_bumblebee::configuration::{get_configuration, MqttProtocolVersion, MqttSettings};
+use beesbuddy_bumblebee::mqtt;
+use beesbuddy_bumblebee::mqtt_client::{MqttClient, MqttEvent, MqttMessage};
+use rumqttc::v5::mqttbytes::v5::PublishProperties;
+use rumqttc::v5::mqttbytes::QoS;
+use std::time::Duration;
+
+const TOPIC: &str = "apiary-1/hive-1";
+
+fn mqtt_settings(port: u16) -> MqttSettings {
+    let mut settings = get_configuration()
+        .expect("Failed to read configuration.")
+        .mqtt;
+    settings.host = "127.0.0.1".into();
+    settings.port = port;
+    settings.protocol_version = MqttProtocolVersion::V5;
+    settings
+}
+
+/// Subscribes like the mqtt worker and returns the first message published with
+/// `properties`.
+async fn receive_with_properties(properties: PublishProperties) -> MqttMessage {
+    let settings = mqtt_settings(spawn_mqtt_broker().v5_port);
+    let (client, mut event_loop) = connect_mqtt(&settings).await;
+    client
+        .try_subscribe(TOPIC.to_string(), mqtt::qos(&settings).unwrap())
+        .unwrap();
+    loop {
+        if let Ok(MqttEvent::Subscribed { .. }) = event_loop.poll().await {
+            break;
+        }
+    }
+
+    let (MqttClient::V5(publisher), mut publisher_event_loop) = connect_mqtt(&settings).await
+    else {
+        panic!("The client has to use MQTT 5.");
+    };
+    tokio::spawn(async move { while publisher_event_loop.poll().await.is_ok() {} });
+    publisher
+        .publish_with_properties(
+            TOPIC,
+            QoS::AtLeastOnce,
+            false,
+            r#"{"weight":1200}"#,
+            properties,
+        )
+        .await
+        .unwrap();
+
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            if let Ok(MqttEvent::Message(message)) = event_loop.poll().await {
+                return message;
+            }
+        }
+    })
+    .await
+    .expect("The message was not delivered.")
+}
+
+#[tokio::test]
+async fn v5_messages_carry_the_user_properties() {
+    let message = receive_with_properties(PublishProperties {
+        user_properties: vec![("firmware_version".into(), "1.4.2".into())],
+        ..PublishProperties::default()
+    })
+    .await;
+
+    assert_eq!(message.topic, TOPIC);
+    assert_eq!(message.payload, br#"{"weight":1200}"#);
+    assert_eq!(
+        message.user_properties,
+        vec![("firmware_version".to_string(), "1.4.2".to_string())]
+    );
+}
+
+#[tokio::test]
+async fn v5_messages_without_properties_have_no_user_properties() {
+    let message = receive_with_properties(PublishProperties::default()).await;
+
+    assert!(message.user_properties.is_empty());
+}
@@ -0,0 +1,23 @@
+use crate::helpers::spawn_app;
+use claims::assert_ok;
+use std::time::Duration;
+
+#[tokio::test]
+async fn application_stops_when_shutdown_is_triggered() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    app.shutdown.cancel();
+    let outcome = tokio::time::timeout(Duration::from_secs(5), app.application_task)
+        .await
+        .expect("Application did not stop within the deadline.");
+
+    // Assert
+    assert_ok!(outcome.unwrap());
+    assert!(reqwest::Client::new()
+        .get(format!("{}/health_check", &app.address))
+        .send()
+        .await
+        .is_err());
+}
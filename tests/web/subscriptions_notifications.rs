@@ -0,0 +1,101 @@
+use crate::helpers::spawn_app;
+use beesbuddy_bumblebee::configuration::get_configuration;
+use beesbuddy_bumblebee::health::DependencyHealth;
+use beesbuddy_bumblebee::metrics::Metrics;
+use beesbuddy_bumblebee::workers::{
+    run_subscription_worker_until_stopped, ActionType, SubscriptionTopicsNotificationPayload,
+    SubscriptionsEvent,
+};
+use chrono::Utc;
+use claims::{assert_matches, assert_none};
+use sqlx::postgres::PgListener;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+async fn next_payload(listener: &mut PgListener) -> SubscriptionTopicsNotificationPayload {
+    let notification = tokio::time::timeout(Duration::from_secs(5), listener.recv())
+        .await
+        .expect("No notification received.")
+        .unwrap();
+    serde_json::from_str(notification.payload()).unwrap()
+}
+
+#[tokio::test]
+async fn subscriptions_topics_update_notification_carries_old_and_new_topic() {
+    let app = spawn_app().await;
+    let mut listener = PgListener::connect_with(&app.db_pool).await.unwrap();
+    listener.listen("subscriptions_topics").await.unwrap();
+    let id = Uuid::new_v4();
+    let organization_id = app.register_organization().await;
+    let device_id = app.register_device(organization_id, "hive-1").await;
+
+    sqlx::query!(
+        r#"
+    INSERT INTO subscriptions_topics (id, organization_id, device_id, device_name, topic_prefix, created_at, updated_at)
+    VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        id,
+        organization_id,
+        device_id,
+        "hive-1",
+        "apiary-1",
+        Utc::now(),
+        Utc::now()
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let payload = next_payload(&mut listener).await;
+    assert_matches!(payload.action_type, ActionType::INSERT);
+    assert_eq!(payload.topic(), "apiary-1/hive-1");
+    assert_none!(payload.old_topic());
+
+    sqlx::query!(
+        "UPDATE subscriptions_topics SET topic_prefix = $1 WHERE id = $2",
+        "apiary-2",
+        id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let payload = next_payload(&mut listener).await;
+    assert_matches!(payload.action_type, ActionType::UPDATE);
+    assert_eq!(payload.topic(), "apiary-2/hive-1");
+    assert_eq!(payload.old_topic().as_deref(), Some("apiary-1/hive-1"));
+
+    sqlx::query!("DELETE FROM subscriptions_topics WHERE id = $1", id)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let payload = next_payload(&mut listener).await;
+    assert_matches!(payload.action_type, ActionType::DELETE);
+    assert_eq!(payload.old_topic().as_deref(), Some("apiary-2/hive-1"));
+}
+
+#[tokio::test]
+async fn subscriptions_worker_asks_for_a_reconciliation_when_it_starts_listening() {
+    let configuration = get_configuration().expect("Failed to read configuration.");
+    let (tx, mut rx) = broadcast::channel(16);
+    let shutdown = CancellationToken::new();
+    let worker = tokio::spawn(run_subscription_worker_until_stopped(
+        configuration,
+        tx,
+        DependencyHealth::new(),
+        Metrics::new(),
+        shutdown.clone(),
+    ));
+
+    let event = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("No subscriptions event received.")
+        .unwrap();
+
+    assert_matches!(event, SubscriptionsEvent::ListenerReconnected);
+    shutdown.cancel();
+    worker.await.unwrap().unwrap();
+}